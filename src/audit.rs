@@ -0,0 +1,461 @@
+use crate::backend::ProgrammerBackend;
+use crate::device::DeviceIdentity;
+use crate::hardware::HardwareType;
+use crate::settings::Settings;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const AUDIT_FILE: &str = "audit.jsonl";
+/// Ende jeder Zeile vor dem Einsetzen des Hashes
+const UNHASHED_END: &str = "\"hash\":\"\"}";
+
+/// Protokollierter Vorgang
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Download,
+    Flash,
+    Verify,
+    ReadOut,
+    Erase,
+    Restore,
+    SetSerial,
+    SetCapacity,
+    OptionBytes,
+    Login,
+    Logout,
+    OperatorProfile,
+    FirmwareBundle,
+}
+
+impl Action {
+    pub fn all() -> &'static [Action] {
+        &[
+            Action::Download,
+            Action::Flash,
+            Action::Verify,
+            Action::ReadOut,
+            Action::Erase,
+            Action::Restore,
+            Action::SetSerial,
+            Action::SetCapacity,
+            Action::OptionBytes,
+            Action::Login,
+            Action::Logout,
+            Action::OperatorProfile,
+            Action::FirmwareBundle,
+        ]
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Action::Download => "Download",
+            Action::Flash => "Flash",
+            Action::Verify => "Verify",
+            Action::ReadOut => "Read out",
+            Action::Erase => "Erase",
+            Action::Restore => "Restore backup",
+            Action::SetSerial => "Set serial",
+            Action::SetCapacity => "Set capacity",
+            Action::OptionBytes => "Option bytes",
+            Action::Login => "Login",
+            Action::Logout => "Logout",
+            Action::OperatorProfile => "Operator profile",
+            Action::FirmwareBundle => "Firmware bundle",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Firmware-Datei mit Release-Tag und Prüfsumme
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirmwareRef {
+    pub tag: Option<String>,
+    pub asset: String,
+    pub sha256: String,
+}
+
+impl FirmwareRef {
+    pub fn from_file(path: &Path, tag: Option<&str>) -> Self {
+        Self {
+            tag: tag.map(str::to_string),
+            asset: path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            sha256: sha256_file(path).unwrap_or_default(),
+        }
+    }
+}
+
+impl std::fmt::Display for FirmwareRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(tag) = &self.tag {
+            write!(f, "{}/", tag)?;
+        }
+        write!(f, "{}", self.asset)
+    }
+}
+
+pub fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let data = std::fs::read(path)?;
+    Ok(format!("{:x}", Sha256::digest(&data)))
+}
+
+/// Eintrag im Audit-Log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub time: DateTime<Local>,
+    pub operator: Option<String>,
+    pub action: Action,
+    pub hw_type: Option<String>,
+    #[serde(default)]
+    pub firmware: Vec<FirmwareRef>,
+    /// Debug-Probe (`VID:PID:Seriennummer`) oder Demo-Gerät
+    pub probe: Option<String>,
+    /// 96-Bit-Unique-ID des Geräts
+    #[serde(default)]
+    pub unique_id: Option<String>,
+    pub serial: Option<String>,
+    pub success: bool,
+    pub details: String,
+    /// SHA-256 über den Hash des vorherigen Eintrags und die geschriebene Zeile
+    /// mit leerem Hash; macht nachträgliche Änderungen oder gelöschte Einträge
+    /// erkennbar. Muss das letzte Feld bleiben.
+    #[serde(default)]
+    pub hash: String,
+    /// Zeile, wie sie im Log steht (nur bei geladenen Einträgen)
+    #[serde(skip)]
+    line: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(action: Action, hw_type: Option<HardwareType>) -> Self {
+        Self {
+            time: Local::now(),
+            operator: crate::operator::current(),
+            action,
+            hw_type: hw_type.map(|hw| hw.id().to_string()),
+            firmware: Vec::new(),
+            probe: None,
+            unique_id: None,
+            serial: None,
+            success: false,
+            details: String::new(),
+            hash: String::new(),
+            line: None,
+        }
+    }
+
+    /// Übernimmt Probe und Geräte-Kennung der Verbindung
+    pub fn device(&mut self, backend: &dyn ProgrammerBackend, identity: &DeviceIdentity) {
+        self.probe = backend.probe();
+        self.unique_id = identity.unique_id.clone();
+    }
+
+    /// Zeile mit leerem Hash, über die der Hash gebildet wird. Bei geladenen
+    /// Einträgen genau die gelesenen Bytes, damit z.B. eine andere Zeitzone
+    /// beim Prüfen keine Rolle spielt.
+    fn unhashed_line(&self) -> anyhow::Result<String> {
+        if let Some(line) = &self.line {
+            let head = line
+                .trim_end()
+                .strip_suffix(&format!("\"hash\":\"{}\"}}", self.hash))
+                .ok_or_else(|| anyhow::anyhow!("Hash steht nicht am Ende der Zeile"))?;
+            return Ok(format!("{}{}", head, UNHASHED_END));
+        }
+        let unhashed = AuditRecord {
+            hash: String::new(),
+            ..self.clone()
+        };
+        Ok(serde_json::to_string(&unhashed)?)
+    }
+
+    pub fn firmware_summary(&self) -> String {
+        self.firmware
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+pub fn path(settings: &Settings) -> anyhow::Result<PathBuf> {
+    Ok(settings.data_dir()?.join(AUDIT_FILE))
+}
+
+fn chain_hash(previous: &str, unhashed_line: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(previous.as_bytes());
+    hasher.update(unhashed_line.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Exklusive Sperre für Lesen der letzten Zeile und Anhängen, auch über
+/// Prozessgrenzen, damit nie zwei Einträge denselben Vorgänger-Hash verwenden.
+/// Eigene Sperrdatei wie beim Seriennummern-Register.
+fn lock(path: &Path) -> anyhow::Result<std::fs::File> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.with_extension("lock"))?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Hängt einen Eintrag an. Das Log wird nie umgeschrieben; gelesen wird nur
+/// die letzte Zeile für den Hash des Vorgängers.
+pub fn append(settings: &Settings, record: &AuditRecord) -> anyhow::Result<()> {
+    let path = path(settings)?;
+    let _lock = lock(&path)?;
+    let previous = match last_line(&path)? {
+        Some(line) => {
+            serde_json::from_str::<AuditRecord>(&line)
+                .map_err(|e| anyhow::anyhow!("{}, letzte Zeile: {}", path.display(), e))?
+                .hash
+        }
+        None => String::new(),
+    };
+    let unhashed = AuditRecord {
+        hash: String::new(),
+        line: None,
+        ..record.clone()
+    };
+    let unhashed = serde_json::to_string(&unhashed)?;
+    let head = unhashed
+        .strip_suffix(UNHASHED_END)
+        .ok_or_else(|| anyhow::anyhow!("Hash ist nicht das letzte Feld des Eintrags"))?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(
+        file,
+        "{}\"hash\":\"{}\"}}",
+        head,
+        chain_hash(&previous, &unhashed)
+    )?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Hängt einen Eintrag an; Fehler landen nur im Vorgangsprotokoll. Für
+/// Einträge, deren Verlust den Vorgang selbst nicht ungültig macht.
+pub fn append_logged(settings: &Settings, record: &AuditRecord) {
+    if let Err(e) = append(settings, record) {
+        crate::oplog::record(format!(
+            "Audit-Log konnte nicht geschrieben werden: {:#}",
+            e
+        ));
+    }
+}
+
+/// Letzte nicht leere Zeile, ohne die ganze Datei zu lesen
+fn last_line(path: &Path) -> anyhow::Result<Option<String>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut file = std::fs::File::open(path)?;
+    let mut pos = file.metadata()?.len();
+    let mut tail: Vec<u8> = Vec::new();
+    loop {
+        let chunk = pos.min(4096);
+        pos -= chunk;
+        file.seek(SeekFrom::Start(pos))?;
+        let mut buf = vec![0u8; chunk as usize];
+        file.read_exact(&mut buf)?;
+        buf.extend_from_slice(&tail);
+        tail = buf;
+        let trimmed = tail.trim_ascii_end();
+        let start = match trimmed.iter().rposition(|b| *b == b'\n') {
+            Some(newline) => newline + 1,
+            None if pos == 0 => 0,
+            None => continue,
+        };
+        let line = &trimmed[start..];
+        return Ok((!line.is_empty()).then(|| String::from_utf8_lossy(line).into_owned()));
+    }
+}
+
+/// Führt einen Vorgang aus und protokolliert Ergebnis bzw. Fehler. `f` kann
+/// `success` zurücksetzen, wenn der Vorgang ohne Fehler ein negatives Ergebnis
+/// liefert (z.B. Abweichungen beim Verify).
+pub fn run<T>(
+    settings: &Settings,
+    mut record: AuditRecord,
+    f: impl FnOnce(&mut AuditRecord) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    record.success = true;
+    let result = f(&mut record);
+    record.success &= result.is_ok();
+    if let Err(e) = &result {
+        record.details = format!("{:#}", e);
+    }
+    let audit = append(settings, &record);
+    let value = result?;
+    audit.map_err(|e| e.context("Vorgang ausgeführt, aber nicht im Audit-Log festgehalten"))?;
+    Ok(value)
+}
+
+/// Alle Einträge, älteste zuerst
+pub fn load(settings: &Settings) -> anyhow::Result<Vec<AuditRecord>> {
+    let path = path(settings)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut records = Vec::new();
+    for (i, line) in std::fs::read_to_string(&path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut record: AuditRecord = serde_json::from_str(line)
+            .map_err(|e| anyhow::anyhow!("{}, Zeile {}: {}", path.display(), i + 1, e))?;
+        record.line = Some(line.to_string());
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Prüft die Hash-Kette; `Err` mit dem Index des ersten veränderten Eintrags
+pub fn verify_chain(records: &[AuditRecord]) -> Result<(), usize> {
+    let mut previous = String::new();
+    for (i, record) in records.iter().enumerate() {
+        match record.unhashed_line() {
+            Ok(line) if chain_hash(&previous, &line) == record.hash => {
+                previous = record.hash.clone()
+            }
+            _ => return Err(i),
+        }
+    }
+    Ok(())
+}
+
+pub fn export_json(records: &[AuditRecord], path: &Path) -> anyhow::Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(records)?)?;
+    Ok(())
+}
+
+pub fn export_csv(records: &[AuditRecord], path: &Path) -> anyhow::Result<()> {
+    let mut file = std::fs::File::create(path)?;
+    writeln!(
+        file,
+        "time,operator,action,hw_type,firmware,sha256,probe,unique_id,serial,success,details"
+    )?;
+    for r in records {
+        let fields = [
+            r.time.to_rfc3339(),
+            r.operator.clone().unwrap_or_default(),
+            serde_json::to_string(&r.action)?
+                .trim_matches('"')
+                .to_string(),
+            r.hw_type.clone().unwrap_or_default(),
+            r.firmware_summary(),
+            r.firmware
+                .iter()
+                .map(|f| f.sha256.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            r.probe.clone().unwrap_or_default(),
+            r.unique_id.clone().unwrap_or_default(),
+            r.serial.clone().unwrap_or_default(),
+            r.success.to_string(),
+            r.details.clone(),
+        ];
+        let line: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        writeln!(file, "{}", line.join(","))?;
+    }
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(details: &str) -> AuditRecord {
+        let mut record = AuditRecord::new(Action::Verify, None);
+        record.details = details.to_string();
+        record
+    }
+
+    #[test]
+    fn appended_records_form_a_chain() {
+        let (_dir, settings) = crate::testing::environment();
+        for i in 0..3 {
+            append(&settings, &record(&format!("Eintrag {}", i))).unwrap();
+        }
+        let records = load(&settings).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(verify_chain(&records), Ok(()));
+
+        let mut tampered = records.clone();
+        let last = tampered.len() - 1;
+        let line = tampered[last].line.take().unwrap();
+        tampered[last].line = Some(line.replace("Eintrag 2", "Eintrag 9"));
+        assert_eq!(verify_chain(&tampered), Err(last));
+    }
+
+    #[test]
+    fn concurrent_appends_keep_the_chain() {
+        let (_dir, settings) = crate::testing::environment();
+        std::thread::scope(|scope| {
+            for t in 0..4 {
+                let settings = &settings;
+                scope.spawn(move || {
+                    for i in 0..5 {
+                        append(settings, &record(&format!("Thread {} Eintrag {}", t, i))).unwrap();
+                    }
+                });
+            }
+        });
+        let records = load(&settings).unwrap();
+        assert_eq!(records.len(), 20);
+        assert_eq!(verify_chain(&records), Ok(()));
+    }
+
+    #[test]
+    fn chain_uses_the_written_bytes() {
+        // Anderer Zeitzonen-Offset als bei der Serialisierung mit `Local`
+        let unhashed = r#"{"time":"2026-03-29T01:30:00+05:45","operator":null,"action":"login","hw_type":null,"firmware":[],"probe":null,"unique_id":null,"serial":null,"success":true,"details":"","hash":""}"#;
+        let hash = chain_hash("", unhashed);
+        let line = unhashed.replace(r#""hash":"""#, &format!(r#""hash":"{}""#, hash));
+        let mut record: AuditRecord = serde_json::from_str(&line).unwrap();
+        record.line = Some(line);
+        assert_eq!(verify_chain(&[record]), Ok(()));
+    }
+
+    #[test]
+    fn last_line_reads_only_the_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(AUDIT_FILE);
+        assert_eq!(last_line(&path).unwrap(), None);
+        let mut content = String::new();
+        for i in 0..1000 {
+            content.push_str(&format!("{{\"zeile\":{}}}\n", i));
+        }
+        content.push('\n');
+        std::fs::write(&path, &content).unwrap();
+        assert_eq!(
+            last_line(&path).unwrap().as_deref(),
+            Some("{\"zeile\":999}")
+        );
+        std::fs::write(&path, "einzige").unwrap();
+        assert_eq!(last_line(&path).unwrap().as_deref(), Some("einzige"));
+    }
+}
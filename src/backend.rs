@@ -0,0 +1,119 @@
+use crate::hardware::MemoryRange;
+use probe_rs::flashing::{DownloadOptions, FlashProgress};
+use probe_rs::{MemoryInterface, Session};
+use std::time::Duration;
+
+/// Zeit, die der Kern zum Anhalten bekommt
+const HALT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Zugriff auf ein angeschlossenes Gerät. Die gesamte Flash-Logik arbeitet nur
+/// über diese Schnittstelle, damit sie auch mit dem simulierten Gerät läuft.
+pub trait ProgrammerBackend {
+    /// Kurzbeschreibung der Verbindung für Anzeige und Protokoll
+    fn info(&self) -> String;
+
+    /// Verwendete Debug-Probe (`VID:PID:Seriennummer`), falls bekannt
+    fn probe(&self) -> Option<String>;
+
+    fn read(&mut self, address: u64, data: &mut [u8]) -> anyhow::Result<()>;
+
+    fn read_word_32(&mut self, address: u64) -> anyhow::Result<u32> {
+        let mut bytes = [0u8; 4];
+        self.read(address, &mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Schreibt ein Wort in RAM oder Peripherie-Register (nicht in den Flash)
+    fn write_word_32(&mut self, address: u64, value: u32) -> anyhow::Result<()>;
+
+    /// Programmiert Datenblöcke in den Flash; betroffene Sektoren werden gelöscht
+    fn flash(&mut self, data: &[(u64, &[u8])]) -> anyhow::Result<()>;
+
+    /// Löscht einen Flash-Bereich oder, ohne `range`, den gesamten Chip
+    fn erase(&mut self, range: Option<MemoryRange>) -> anyhow::Result<()>;
+
+    fn reset(&mut self) -> anyhow::Result<()>;
+
+    fn reset_and_halt(&mut self) -> anyhow::Result<()>;
+
+    /// Hält den Kern an und liefert den Program Counter
+    fn halt(&mut self) -> anyhow::Result<u64>;
+
+    fn run(&mut self) -> anyhow::Result<()>;
+}
+
+/// Echtes Gerät über eine probe-rs-Session
+pub struct ProbeRsBackend {
+    session: Session,
+    probe: Option<String>,
+}
+
+impl ProbeRsBackend {
+    pub fn new(session: Session, probe: Option<String>) -> Self {
+        Self { session, probe }
+    }
+}
+
+impl ProgrammerBackend for ProbeRsBackend {
+    fn info(&self) -> String {
+        format!("probe-rs, {}", self.session.target().name)
+    }
+
+    fn probe(&self) -> Option<String> {
+        self.probe.clone()
+    }
+
+    fn read(&mut self, address: u64, data: &mut [u8]) -> anyhow::Result<()> {
+        self.session.core(0)?.read(address, data)?;
+        Ok(())
+    }
+
+    fn read_word_32(&mut self, address: u64) -> anyhow::Result<u32> {
+        Ok(self.session.core(0)?.read_word_32(address)?)
+    }
+
+    fn write_word_32(&mut self, address: u64, value: u32) -> anyhow::Result<()> {
+        self.session.core(0)?.write_word_32(address, value)?;
+        Ok(())
+    }
+
+    fn flash(&mut self, data: &[(u64, &[u8])]) -> anyhow::Result<()> {
+        let mut loader = self.session.target().flash_loader();
+        for (address, bytes) in data {
+            loader.add_data(*address, bytes)?;
+        }
+        loader.commit(&mut self.session, DownloadOptions::default())?;
+        Ok(())
+    }
+
+    fn erase(&mut self, range: Option<MemoryRange>) -> anyhow::Result<()> {
+        match range {
+            Some(range) => probe_rs::flashing::erase(
+                &mut self.session,
+                FlashProgress::empty(),
+                range.base..range.end(),
+            )?,
+            None => probe_rs::flashing::erase_all(&mut self.session, FlashProgress::empty())?,
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) -> anyhow::Result<()> {
+        self.session.core(0)?.reset()?;
+        Ok(())
+    }
+
+    fn reset_and_halt(&mut self) -> anyhow::Result<()> {
+        self.session.core(0)?.reset_and_halt(HALT_TIMEOUT)?;
+        Ok(())
+    }
+
+    fn halt(&mut self) -> anyhow::Result<u64> {
+        Ok(self.session.core(0)?.halt(HALT_TIMEOUT)?.pc)
+    }
+
+    fn run(&mut self) -> anyhow::Result<()> {
+        self.session.core(0)?.run()?;
+        Ok(())
+    }
+}
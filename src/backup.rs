@@ -0,0 +1,382 @@
+use crate::audit::{Action, AuditRecord};
+use crate::backend::ProgrammerBackend;
+use crate::device::DeviceIdentity;
+use crate::hardware::{HardwareType, MemoryRange, ParameterKind};
+use crate::probe::ProbeConfig;
+use crate::settings::Settings;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const META_FILE: &str = "backup.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupRegion {
+    pub base: u64,
+    pub size: u64,
+    /// Dateiname relativ zum Backup-Verzeichnis
+    pub file: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupMeta {
+    pub hw_type: String,
+    pub device_serial: Option<String>,
+    pub idcode: Option<u32>,
+    /// 96-Bit-Unique-ID des Geräts
+    #[serde(default)]
+    pub unique_id: Option<String>,
+    pub firmware_version: Option<String>,
+    pub created: DateTime<Local>,
+    pub regions: Vec<BackupRegion>,
+}
+
+/// Gesicherter Flash-Inhalt eines Geräts
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub dir: PathBuf,
+    pub meta: BackupMeta,
+}
+
+impl Backup {
+    pub fn hw_type(&self) -> Option<HardwareType> {
+        HardwareType::from_id(&self.meta.hw_type)
+    }
+
+    pub fn label(&self) -> String {
+        format!(
+            "{}  {}  {}",
+            self.meta.created.format("%Y-%m-%d %H:%M:%S"),
+            self.meta.device_serial.as_deref().unwrap_or("no serial"),
+            self.meta
+                .firmware_version
+                .as_deref()
+                .map(|v| format!("v{}", v))
+                .unwrap_or_default()
+        )
+    }
+}
+
+/// Backup stammt nicht nachweislich vom angeschlossenen Gerät
+#[derive(Debug)]
+pub struct DeviceMismatch {
+    pub backup: String,
+    pub connected: String,
+}
+
+impl std::fmt::Display for DeviceMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Backup stammt von {}, angeschlossen ist {}",
+            self.backup, self.connected
+        )
+    }
+}
+
+impl std::error::Error for DeviceMismatch {}
+
+fn describe_device(serial: Option<&str>, unique_id: Option<&str>) -> String {
+    match (serial, unique_id) {
+        (Some(serial), _) => format!("Seriennummer {}", serial),
+        (None, Some(unique_id)) => format!("Unique-ID {}", unique_id),
+        (None, None) => "ein unbekanntes Gerät".to_string(),
+    }
+}
+
+/// `Some(true)`, wenn Unique-ID bzw. Seriennummer übereinstimmen; `None`, wenn
+/// sich das nicht feststellen lässt (z.B. Flash gelöscht, älteres Backup)
+fn same_device(meta: &BackupMeta, serial: Option<&str>, unique_id: Option<&str>) -> Option<bool> {
+    if let (Some(backup), Some(connected)) = (meta.unique_id.as_deref(), unique_id) {
+        return Some(backup == connected);
+    }
+    if let (Some(backup), Some(connected)) = (meta.device_serial.as_deref(), serial) {
+        return Some(backup.eq_ignore_ascii_case(connected));
+    }
+    None
+}
+
+/// Zu sichernde Bereiche: der gesamte Flash und Parameter außerhalb davon
+fn backup_ranges(hw_type: HardwareType) -> Vec<MemoryRange> {
+    let model = hw_type.model();
+    let mut ranges = vec![model.flash];
+    for parameter in &model.parameters {
+        if !model.flash.contains(parameter.address) {
+            ranges.push(MemoryRange {
+                base: parameter.address,
+                size: parameter.length as u64,
+            });
+        }
+    }
+    ranges
+}
+
+/// Liest die Seriennummer aus einem gesicherten Bereich
+fn serial_from_dump(hw_type: HardwareType, range: &MemoryRange, data: &[u8]) -> Option<String> {
+    let parameter = hw_type
+        .model()
+        .parameters
+        .iter()
+        .find(|p| p.name == "serial" && p.kind == ParameterKind::String)?;
+    if !range.contains(parameter.address) {
+        return None;
+    }
+    let offset = (parameter.address - range.base) as usize;
+    let raw = data.get(offset..offset + parameter.length)?;
+    crate::device::decode_string_parameter(raw)
+}
+
+/// Nur Zeichen, die in Verzeichnisnamen unproblematisch sind
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Sichert den Flash des Geräts über eine bestehende Verbindung nach `root`
+pub fn create(
+    backend: &mut dyn ProgrammerBackend,
+    hw_type: HardwareType,
+    identity: &DeviceIdentity,
+    root: &Path,
+) -> anyhow::Result<Backup> {
+    let mut dumps = Vec::new();
+    let mut device_serial = None;
+    for range in backup_ranges(hw_type) {
+        let mut data = vec![0u8; range.size as usize];
+        backend.read(range.base, &mut data)?;
+        if device_serial.is_none() {
+            device_serial = serial_from_dump(hw_type, &range, &data);
+        }
+        dumps.push((range, data));
+    }
+
+    let created = Local::now();
+    let device_name = match (&device_serial, &identity.unique_id) {
+        (Some(serial), _) => sanitize(serial),
+        (None, Some(unique_id)) => format!("uid{}", unique_id),
+        (None, None) => "unknown".to_string(),
+    };
+    let dir = root.join(hw_type.id()).join(format!(
+        "{}_{}",
+        device_name,
+        created.format("%Y%m%d-%H%M%S")
+    ));
+    std::fs::create_dir_all(&dir)?;
+
+    let mut regions = Vec::new();
+    for (range, data) in dumps {
+        let file = format!("{:08X}.bin", range.base);
+        std::fs::write(dir.join(&file), &data)?;
+        regions.push(BackupRegion {
+            base: range.base,
+            size: range.size,
+            file,
+        });
+    }
+    let meta = BackupMeta {
+        hw_type: hw_type.id().to_string(),
+        device_serial,
+        idcode: identity.idcode,
+        unique_id: identity.unique_id.clone(),
+        firmware_version: identity
+            .firmware
+            .as_ref()
+            .and_then(|f| f.version.as_ref())
+            .map(|v| v.to_string()),
+        created,
+        regions,
+    };
+    std::fs::write(dir.join(META_FILE), serde_json::to_string_pretty(&meta)?)?;
+    Ok(Backup { dir, meta })
+}
+
+/// Alle Backups unter `root`, optional gefiltert nach Hardware-Typ, neueste zuerst
+pub fn list(root: &Path, hw_type: Option<HardwareType>) -> Vec<Backup> {
+    let mut backups = Vec::new();
+    let Ok(types) = std::fs::read_dir(root) else {
+        return backups;
+    };
+    for type_dir in types.flatten() {
+        let Ok(entries) = std::fs::read_dir(type_dir.path()) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            let Some(meta) = std::fs::read_to_string(dir.join(META_FILE))
+                .ok()
+                .and_then(|s| serde_json::from_str::<BackupMeta>(&s).ok())
+            else {
+                continue;
+            };
+            let backup = Backup { dir, meta };
+            if hw_type.is_none() || backup.hw_type() == hw_type {
+                backups.push(backup);
+            }
+        }
+    }
+    backups.sort_by_key(|b| std::cmp::Reverse(b.meta.created));
+    backups
+}
+
+/// Löscht die ältesten Backups vollständig, bis alle zusammen höchstens
+/// `max_bytes` belegen. `keep` bleibt in jedem Fall erhalten.
+pub fn prune(root: &Path, max_bytes: u64, keep: &Path) {
+    let mut total = 0u64;
+    for backup in list(root, None) {
+        let size: u64 = backup.meta.regions.iter().map(|r| r.size).sum();
+        total = total.saturating_add(size);
+        if total <= max_bytes || backup.dir == keep {
+            continue;
+        }
+        match std::fs::remove_dir_all(&backup.dir) {
+            Ok(()) => total -= size,
+            Err(e) => crate::oplog::record(format!(
+                "Backup {} nicht gelöscht: {}",
+                backup.dir.display(),
+                e
+            )),
+        }
+    }
+}
+
+/// Schreibt ein Backup zurück auf das angeschlossene Gerät. Stammt es nicht
+/// nachweislich von diesem Gerät, nur mit `allow_other_device`; sonst
+/// `DeviceMismatch` als Fehler.
+pub fn restore(
+    settings: &Settings,
+    backup: &Backup,
+    allow_other_device: bool,
+) -> anyhow::Result<()> {
+    let hw_type = backup
+        .hw_type()
+        .ok_or_else(|| anyhow::anyhow!("Unbekannter Hardware-Typ: {}", backup.meta.hw_type))?;
+    let mut record = AuditRecord::new(Action::Restore, Some(hw_type));
+    record.serial = backup.meta.device_serial.clone();
+    record.details = backup.dir.display().to_string();
+    crate::audit::run(settings, record, |record| {
+        let probe = ProbeConfig::from_settings(settings);
+        let mut backend = crate::probe::attach(&probe, Some(&hw_type.model().target))?;
+        let identity = crate::device::identify(backend.as_mut())?;
+        record.device(backend.as_ref(), &identity);
+        if identity.contradicts(hw_type) {
+            anyhow::bail!(
+                "Backup für {} passt nicht zum angeschlossenen Gerät: {}",
+                hw_type,
+                identity.summary()
+            );
+        }
+        let serial = crate::parameters::read(backend.as_mut(), hw_type, "serial", None)
+            .ok()
+            .flatten()
+            .map(|serial| serial.to_string());
+        let unique_id = identity.unique_id.as_deref();
+        if same_device(&backup.meta, serial.as_deref(), unique_id) != Some(true) {
+            let mismatch = DeviceMismatch {
+                backup: describe_device(
+                    backup.meta.device_serial.as_deref(),
+                    backup.meta.unique_id.as_deref(),
+                ),
+                connected: describe_device(serial.as_deref(), unique_id),
+            };
+            if !allow_other_device {
+                return Err(mismatch.into());
+            }
+            record.details = format!("{}; {}", record.details, mismatch);
+        }
+        let mut blocks = Vec::new();
+        for region in &backup.meta.regions {
+            let data = std::fs::read(backup.dir.join(&region.file))?;
+            if data.len() as u64 != region.size {
+                anyhow::bail!("Backup-Datei {} ist unvollständig", region.file);
+            }
+            blocks.push((region.base, data));
+        }
+        let data: Vec<(u64, &[u8])> = blocks
+            .iter()
+            .map(|(base, data)| (*base, data.as_slice()))
+            .collect();
+        backend.flash(&data)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::read;
+
+    #[test]
+    fn backup_restores_flash() {
+        let device = crate::testing::device();
+        let hw_type = device.hw_type;
+        let mut backend = device.connect();
+        let identity = crate::device::identify(backend.as_mut()).unwrap();
+        let backup = create(backend.as_mut(), hw_type, &identity, device.dir.path()).unwrap();
+        let original = read(backend.as_mut(), hw_type.model().flash).unwrap();
+        backend.erase(Some(device.region("application"))).unwrap();
+        drop(backend);
+
+        restore(&device.settings, &backup, false).unwrap();
+        let mut backend = device.connect();
+        assert_eq!(
+            read(backend.as_mut(), hw_type.model().flash).unwrap(),
+            original
+        );
+    }
+
+    #[test]
+    fn prune_removes_oldest_backups() {
+        let device = crate::testing::device();
+        let mut backend = device.connect();
+        let identity = crate::device::identify(backend.as_mut()).unwrap();
+        let root = device.dir.path().join("backups");
+        let mut dirs = Vec::new();
+        for i in 0..3 {
+            let mut backup = create(backend.as_mut(), device.hw_type, &identity, &root).unwrap();
+            // Zeitstempel auseinanderziehen, statt im Test zu warten
+            let dir = backup.dir.with_file_name(format!("backup{}", i));
+            std::fs::rename(&backup.dir, &dir).unwrap();
+            backup.meta.created -= chrono::Duration::hours(3 - i);
+            std::fs::write(
+                dir.join(META_FILE),
+                serde_json::to_string(&backup.meta).unwrap(),
+            )
+            .unwrap();
+            dirs.push(dir);
+        }
+        let size = device.hw_type.model().flash.size;
+        prune(&root, 2 * size, &dirs[2]);
+        assert!(!dirs[0].exists());
+        assert!(dirs[1].exists());
+        // Das gerade erstellte Backup bleibt auch über der Grenze erhalten
+        prune(&root, 0, &dirs[2]);
+        assert!(!dirs[1].exists());
+        assert!(dirs[2].exists());
+    }
+
+    #[test]
+    fn backup_of_other_device_needs_confirmation() {
+        let device = crate::testing::device();
+        let mut backend = device.connect();
+        let identity = crate::device::identify(backend.as_mut()).unwrap();
+        let mut backup = create(
+            backend.as_mut(),
+            device.hw_type,
+            &identity,
+            device.dir.path(),
+        )
+        .unwrap();
+        drop(backend);
+        backup.meta.unique_id = Some("00000000000000000000FFFF".to_string());
+
+        let error = restore(&device.settings, &backup, false).unwrap_err();
+        assert!(error.downcast_ref::<DeviceMismatch>().is_some());
+        restore(&device.settings, &backup, true).unwrap();
+    }
+}
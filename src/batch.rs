@@ -0,0 +1,334 @@
+use crate::audit::{Action, AuditRecord};
+use crate::flash::{FlashConfig, ImageFile};
+use crate::hardware::HardwareType;
+use crate::history::Snapshot;
+use crate::image::FirmwareImage;
+use crate::label::LabelData;
+use crate::parameters::ParameterValue;
+use crate::probe::{AfterFlash, ProbeConfig};
+use crate::serial::{Ledger, SerialFormat};
+use crate::settings::Settings;
+use chrono::{DateTime, Local};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+/// Abfrageintervall für Anstecken und Abziehen
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Wartezeit nach dem Anstecken, bis Kontakte und Versorgung stabil sind
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+/// Angezeigte Ergebnisse im Produktionsmodus
+const MAX_RESULTS: usize = 200;
+
+/// Abschnitt im Produktionsablauf
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    WaitingForDevice,
+    Flashing,
+    Verifying,
+    WritingSerial,
+    WaitingForRemoval,
+    Stopped,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Phase::WaitingForDevice => "Waiting for device...",
+            Phase::Flashing => "Flashing...",
+            Phase::Verifying => "Verifying...",
+            Phase::WritingSerial => "Writing serial number...",
+            Phase::WaitingForRemoval => "Remove the device",
+            Phase::Stopped => "Stopped",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Ergebnis für ein Gerät
+#[derive(Debug, Clone)]
+pub struct UnitResult {
+    pub time: DateTime<Local>,
+    pub passed: bool,
+    pub serial: Option<String>,
+    pub message: String,
+}
+
+/// Festgelegte Vorgaben für eine Serie
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub hw_type: HardwareType,
+    pub firmware_tag: String,
+    pub images: Vec<String>,
+    /// Format der vergebenen Seriennummern, `None` = keine Seriennummer schreiben
+    pub serial_format: Option<SerialFormat>,
+    pub verify: bool,
+    pub skip_rdp: bool,
+    pub settings: Settings,
+}
+
+enum Event {
+    Phase(Phase),
+    Unit(UnitResult),
+    NextSerial(Option<String>),
+}
+
+/// Laufende Serie; der Ablauf läuft im Hintergrund, Zustand per `poll` abholen
+pub struct Batch {
+    pub job: Job,
+    pub phase: Phase,
+    pub passed: usize,
+    pub failed: usize,
+    /// Neueste zuerst
+    pub results: Vec<UnitResult>,
+    pub next_serial: Option<String>,
+    rx: Receiver<Event>,
+    stop: Arc<AtomicBool>,
+    next_unit: Arc<AtomicBool>,
+}
+
+impl Batch {
+    pub fn start(job: Job) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let next_unit = Arc::new(AtomicBool::new(false));
+        let worker = Worker {
+            job: job.clone(),
+            tx,
+            stop: stop.clone(),
+            next_unit: next_unit.clone(),
+        };
+        std::thread::spawn(move || worker.run());
+        crate::oplog::record(format!(
+            "Produktionsmodus gestartet: {}, Firmware {}",
+            job.hw_type, job.firmware_tag
+        ));
+        Self {
+            next_serial: peek_serial(&job),
+            job,
+            phase: Phase::WaitingForDevice,
+            passed: 0,
+            failed: 0,
+            results: Vec::new(),
+            rx,
+            stop,
+            next_unit,
+        }
+    }
+
+    pub fn poll(&mut self) {
+        while let Ok(event) = self.rx.try_recv() {
+            match event {
+                Event::Phase(phase) => self.phase = phase,
+                Event::Unit(result) => {
+                    if result.passed {
+                        self.passed += 1;
+                    } else {
+                        self.failed += 1;
+                    }
+                    self.results.insert(0, result);
+                    self.results.truncate(MAX_RESULTS);
+                }
+                Event::NextSerial(serial) => self.next_serial = serial,
+            }
+        }
+    }
+
+    pub fn last(&self) -> Option<&UnitResult> {
+        self.results.first()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.phase != Phase::Stopped
+    }
+
+    /// Beendet die Serie nach dem aktuellen Gerät
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    /// Nicht auf das Abziehen warten (z.B. Nadeladapter oder Demo-Gerät)
+    pub fn next_unit(&self) {
+        self.next_unit.store(true, Ordering::Relaxed);
+    }
+}
+
+struct Worker {
+    job: Job,
+    tx: Sender<Event>,
+    stop: Arc<AtomicBool>,
+    next_unit: Arc<AtomicBool>,
+}
+
+impl Worker {
+    fn run(self) {
+        let probe = ProbeConfig::from_settings(&self.job.settings);
+        let target = self.job.hw_type.model().target.clone();
+        let connected = || crate::probe::is_connected(&probe, Some(&target));
+        loop {
+            self.phase(Phase::WaitingForDevice);
+            if !self.wait_for(&connected) {
+                break;
+            }
+            std::thread::sleep(SETTLE_TIME);
+            let result = self.program_unit();
+            crate::oplog::record(format!(
+                "Produktion: {} {}: {}",
+                if result.passed { "PASS" } else { "FAIL" },
+                result.serial.as_deref().unwrap_or("-"),
+                result.message
+            ));
+            let _ = self.tx.send(Event::Unit(result));
+            let _ = self.tx.send(Event::NextSerial(peek_serial(&self.job)));
+
+            self.phase(Phase::WaitingForRemoval);
+            self.next_unit.store(false, Ordering::Relaxed);
+            if !self.wait_for(&|| !connected() || self.next_unit.swap(false, Ordering::Relaxed)) {
+                break;
+            }
+        }
+        crate::oplog::record("Produktionsmodus beendet");
+        self.phase(Phase::Stopped);
+    }
+
+    fn phase(&self, phase: Phase) {
+        let _ = self.tx.send(Event::Phase(phase));
+    }
+
+    /// Wartet, bis `condition` erfüllt ist; `false`, wenn die Serie beendet wurde
+    fn wait_for(&self, condition: &dyn Fn() -> bool) -> bool {
+        loop {
+            if self.stop.load(Ordering::Relaxed) {
+                return false;
+            }
+            if condition() {
+                return true;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn program_unit(&self) -> UnitResult {
+        let mut serial = None;
+        let result = self.flash_verify_serial(&mut serial);
+        UnitResult {
+            time: Local::now(),
+            passed: result.is_ok(),
+            serial,
+            message: match result {
+                Ok(msg) => msg,
+                Err(e) => format!("{:#}", e),
+            },
+        }
+    }
+
+    /// Flashen, Prüfen und Seriennummer schreiben. Readout-Protection und Reset
+    /// erst ganz am Ende, da danach der Flash nicht mehr lesbar ist. Die
+    /// Seriennummer wird erst nach erfolgreichem Flashen vergeben.
+    fn flash_verify_serial(&self, serial: &mut Option<String>) -> anyhow::Result<String> {
+        let job = &self.job;
+        let mut config = FlashConfig::new(&job.images, job.hw_type, &job.settings);
+        config.firmware_tag = Some(job.firmware_tag.clone());
+        // Nacharbeit mit derselben Version ist im Produktionsmodus normal
+        config.allow_version_change = true;
+        // Neue Geräte haben nichts zu sichern, ein Backup je Gerät füllt nur die Platte
+        config.backup_dir = None;
+        let final_rdp = crate::flash::production_rdp(job.hw_type).filter(|_| !job.skip_rdp);
+        let after_flash = std::mem::replace(&mut config.after_flash, AfterFlash::Leave);
+
+        self.phase(Phase::Flashing);
+        crate::flash::flash_audited(&config)?;
+        let mut msg = "Flashed".to_string();
+        if job.verify {
+            self.phase(Phase::Verifying);
+            let report = crate::operations::verify(&config)?;
+            if !report.is_match() {
+                anyhow::bail!(report.summary());
+            }
+            msg.push_str(", verified");
+        }
+        let (mut backend, identity) = crate::flash::open_session(&config)?;
+        let elf = job
+            .images
+            .iter()
+            .map(|path| ImageFile::for_model(path.clone(), job.hw_type))
+            .filter_map(|file| FirmwareImage::load(Path::new(&file.path), file.base).ok())
+            .find(|image| image.is_elf);
+        if let Some(format) = &job.serial_format {
+            self.phase(Phase::WritingSerial);
+            // Nacharbeit: ein bereits registriertes Gerät behält seine Nummer
+            let mut ledger = Ledger::open(&job.settings)?;
+            let issued = identity
+                .unique_id
+                .as_deref()
+                .and_then(|unique_id| ledger.issued_to(job.hw_type, unique_id))
+                .map(|entry| entry.serial.clone());
+            let reused = issued.is_some();
+            let allocated = match issued {
+                Some(issued) => issued,
+                None => ledger.allocate(job.hw_type, format, identity.unique_id.as_deref())?,
+            };
+            let serial = serial.insert(allocated);
+            // Vorhandene Seriennummer (Nacharbeit) im Ergebnis festhalten
+            let previous =
+                crate::parameters::read(backend.as_mut(), job.hw_type, "serial", elf.as_ref())
+                    .ok()
+                    .flatten();
+            let mut record = AuditRecord::new(Action::SetSerial, Some(job.hw_type));
+            record.device(backend.as_ref(), &identity);
+            record.serial = Some(serial.clone());
+            record.details = previous
+                .as_ref()
+                .map_or(String::new(), |p| format!("was {}", p));
+            crate::audit::run(&job.settings, record, |_| {
+                crate::parameters::write(
+                    backend.as_mut(),
+                    job.hw_type,
+                    "serial",
+                    &ParameterValue::String(serial.clone()),
+                    elf.as_ref(),
+                )
+            })?;
+            msg.push_str(&format!(", serial {}", serial));
+            if reused {
+                msg.push_str(" (already registered)");
+            }
+            if let Some(previous) = previous {
+                msg.push_str(&format!(" (was {})", previous));
+            }
+        }
+        // Ausgangszustand für Geräteverlauf und Etikett, vor der Readout-Protection
+        let snapshot = Snapshot::capture(backend.as_mut(), job.hw_type, &identity, elf.as_ref());
+        if let Err(e) = crate::history::append(&job.settings, &snapshot) {
+            crate::oplog::record(format!("Momentaufnahme nicht gespeichert: {:#}", e));
+        }
+        if let (Some(level), Some(kind)) = (final_rdp, job.hw_type.model().option_bytes) {
+            let mut record = AuditRecord::new(Action::OptionBytes, Some(job.hw_type));
+            record.device(backend.as_ref(), &identity);
+            record.serial = serial.clone();
+            record.details = level.to_string();
+            crate::audit::run(&job.settings, record, |_| {
+                crate::option_bytes::set_rdp(backend.as_mut(), kind, level)
+            })?;
+            msg.push_str(&format!(", {}", level));
+        }
+        crate::probe::after_flash(backend.as_mut(), after_flash)?;
+        if job.settings.label.after_production {
+            // Ein fehlgeschlagener Etikettendruck macht das Gerät nicht fehlerhaft
+            let data = LabelData::from_snapshot(job.hw_type, &snapshot, &identity);
+            match crate::label::output(&job.settings.label, &data) {
+                Ok(_) => msg.push_str(", label printed"),
+                Err(e) => msg.push_str(&format!(", label failed: {:#}", e)),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+/// Nächste Seriennummer laut Register, nur zur Anzeige
+fn peek_serial(job: &Job) -> Option<String> {
+    let format = job.serial_format.as_ref()?;
+    Ledger::open(&job.settings).ok()?.peek(format).ok()
+}
@@ -0,0 +1,443 @@
+//! Offline-Firmware-Pakete für Servicepartner ohne Internetzugang: ausgewählte
+//! Releases mit Assets, Manifesten, Prüfsummen und Release Notes in einer
+//! signierten ZIP-Datei. Importierte Pakete werden als Firmware-Quelle
+//! `bundle:<Verzeichnis>` eingetragen.
+//!
+//! Aufbau: `bundle.json` (Inhaltsverzeichnis mit SHA-256 jeder Datei),
+//! `bundle.json.sig`, `SHA256SUMS` und je Release `<hw_type>/<tag>/` mit den
+//! Assets, `manifest.json` und `RELEASE_NOTES.md`.
+
+use crate::flash::Release;
+use crate::hardware::HardwareType;
+use crate::settings::Settings;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Dateiendung der Pakete
+pub const EXTENSION: &str = "irfw";
+/// Präfix der Firmware-Quelle für importierte Pakete
+pub const SOURCE_PREFIX: &str = "bundle:";
+const INDEX_FILE: &str = "bundle.json";
+const SIGNATURE_FILE: &str = "bundle.json.sig";
+const CHECKSUM_FILE: &str = "SHA256SUMS";
+const MANIFEST_FILE: &str = "manifest.json";
+const NOTES_FILE: &str = "RELEASE_NOTES.md";
+const BUNDLE_DIR: &str = "bundles";
+
+/// Release im Paket; wird auch als `manifest.json` im Release-Verzeichnis abgelegt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleRelease {
+    pub hw_type: String,
+    /// Repository, aus dem das Release stammt
+    pub repo: String,
+    pub tag: String,
+    pub prerelease: bool,
+    /// SHA-256 je Asset
+    pub assets: BTreeMap<String, String>,
+}
+
+/// Inhaltsverzeichnis des Pakets; die Signatur gilt für diese Datei
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleIndex {
+    pub created: DateTime<Local>,
+    pub created_by: Option<String>,
+    pub releases: Vec<BundleRelease>,
+    /// SHA-256 jeder weiteren Datei im Paket
+    pub files: BTreeMap<String, String>,
+}
+
+/// Ergebnis eines Imports
+#[derive(Debug, Clone)]
+pub struct Imported {
+    pub dir: PathBuf,
+    pub hw_types: Vec<HardwareType>,
+    pub releases: usize,
+    /// Signatur mit dem eingestellten Schlüssel geprüft
+    pub signed: bool,
+}
+
+impl Imported {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} release(s) for {} imported{}",
+            self.releases,
+            self.hw_types
+                .iter()
+                .map(|hw| hw.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            if self.signed {
+                ", signature verified"
+            } else {
+                ", signature NOT verified (no public key configured)"
+            }
+        )
+    }
+}
+
+fn sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Verzeichnis eines Releases im Paket
+fn release_dir(hw_type: &str, tag: &str) -> String {
+    format!("{}/{}", hw_type, tag.replace(['/', '\\'], "_"))
+}
+
+/// Lädt die Assets der gewählten Releases und schreibt das signierte Paket nach `path`
+pub fn export(
+    settings: &Settings,
+    releases: &[(HardwareType, Release)],
+    path: &Path,
+    progress: impl Fn(String),
+) -> anyhow::Result<String> {
+    let key_file = settings
+        .bundle_signing_key
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Kein Signaturschlüssel in den Einstellungen"))?;
+    if releases.is_empty() {
+        anyhow::bail!("Keine Releases ausgewählt");
+    }
+    // Inhalt zuerst vollständig sammeln, damit kein halbes Paket entsteht
+    let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+    let mut index = BundleIndex {
+        created: Local::now(),
+        created_by: crate::operator::current(),
+        releases: Vec::new(),
+        files: BTreeMap::new(),
+    };
+    for (hw_type, release) in releases {
+        let repo = settings.firmware_source(*hw_type);
+        let dir = release_dir(hw_type.id(), &release.tag_name);
+        let mut entry = BundleRelease {
+            hw_type: hw_type.id().to_string(),
+            repo: repo.clone(),
+            tag: release.tag_name.clone(),
+            prerelease: release.prerelease,
+            assets: BTreeMap::new(),
+        };
+        for asset in &release.stm32_assets {
+            progress(format!("{} {}: {}", hw_type, release.tag_name, asset));
+            let local = crate::flash::download_github_asset_progress_gui(
+                settings,
+                &repo,
+                &release.tag_name,
+                asset,
+                |_| {},
+            )
+            .map_err(|e| anyhow::anyhow!("{}: {}", asset, e))?;
+            let data = std::fs::read(&local)?;
+            entry.assets.insert(asset.clone(), sha256(&data));
+            files.insert(format!("{}/{}", dir, asset), data);
+        }
+        files.insert(
+            format!("{}/{}", dir, NOTES_FILE),
+            release.notes.clone().unwrap_or_default().into_bytes(),
+        );
+        files.insert(
+            format!("{}/{}", dir, MANIFEST_FILE),
+            serde_json::to_vec_pretty(&entry)?,
+        );
+        index.releases.push(entry);
+    }
+    let checksums: String = files
+        .iter()
+        .map(|(name, data)| format!("{}  {}\n", sha256(data), name))
+        .collect();
+    files.insert(CHECKSUM_FILE.to_string(), checksums.into_bytes());
+    index.files = files
+        .iter()
+        .map(|(name, data)| (name.clone(), sha256(data)))
+        .collect();
+    let index_json = serde_json::to_vec_pretty(&index)?;
+    let signature = crate::signing::sign(&index_json, key_file)?;
+
+    progress(format!("Writing {}", path.display()));
+    let mut tmp = tempfile::NamedTempFile::new_in(path.parent().unwrap_or(Path::new(".")))?;
+    {
+        let mut zip = zip::ZipWriter::new(tmp.as_file_mut());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file(INDEX_FILE, options)?;
+        zip.write_all(&index_json)?;
+        zip.start_file(SIGNATURE_FILE, options)?;
+        zip.write_all(signature.as_bytes())?;
+        for (name, data) in &files {
+            zip.start_file(name.as_str(), options)?;
+            zip.write_all(data)?;
+        }
+        zip.finish()?;
+    }
+    tmp.persist(path)?;
+    crate::oplog::record(format!(
+        "Firmware-Paket {} mit {} Release(s) erstellt",
+        path.display(),
+        releases.len()
+    ));
+    Ok(format!(
+        "{} release(s) exported to {}",
+        releases.len(),
+        path.display()
+    ))
+}
+
+/// Prüft Signatur und Prüfsummen und entpackt das Paket ins Konfigurationsverzeichnis.
+/// Ohne eingestellten öffentlichen Schlüssel nur mit `allow_unsigned`
+/// (Bediener mit Berechtigung für die Einstellungen).
+pub fn import(settings: &Settings, path: &Path, allow_unsigned: bool) -> anyhow::Result<Imported> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)
+        .map_err(|e| anyhow::anyhow!("{}: kein Firmware-Paket ({})", path.display(), e))?;
+    let read = |archive: &mut zip::ZipArchive<std::fs::File>, name: &str| {
+        let mut file = archive
+            .by_name(name)
+            .map_err(|_| anyhow::anyhow!("{} fehlt im Paket", name))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        anyhow::Ok(data)
+    };
+    let index_json = read(&mut archive, INDEX_FILE)?;
+    let signed = match settings
+        .bundle_public_key
+        .as_deref()
+        .filter(|k| !k.trim().is_empty())
+    {
+        Some(key) => {
+            let signature = String::from_utf8(read(&mut archive, SIGNATURE_FILE)?)?;
+            crate::signing::verify(&index_json, signature.trim(), key)?;
+            true
+        }
+        None if allow_unsigned => false,
+        None => anyhow::bail!(
+            "Kein öffentlicher Schlüssel für Firmware-Pakete eingestellt; \
+             ungeprüfte Pakete darf nur ein Engineer importieren"
+        ),
+    };
+    let index: BundleIndex = serde_json::from_slice(&index_json)?;
+
+    let name = path
+        .file_stem()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "bundle".to_string());
+    let dir = settings.data_dir()?.join(BUNDLE_DIR).join(format!(
+        "{}-{}",
+        name,
+        &sha256(&index_json)[..8]
+    ));
+    let mut extracted = BTreeMap::new();
+    // Nur im Inhaltsverzeichnis aufgeführte Dateien mit passender Prüfsumme übernehmen
+    for (name, expected) in &index.files {
+        let data = read(&mut archive, name)?;
+        if &sha256(&data) != expected {
+            anyhow::bail!("Prüfsumme von {} stimmt nicht", name);
+        }
+        let relative = Path::new(name);
+        if relative.is_absolute()
+            || relative
+                .components()
+                .any(|c| !matches!(c, std::path::Component::Normal(_)))
+        {
+            anyhow::bail!("Ungültiger Pfad im Paket: {}", name);
+        }
+        extracted.insert(dir.join(relative), data);
+    }
+    for release in &index.releases {
+        for asset in release.assets.keys() {
+            let name = format!("{}/{}", release_dir(&release.hw_type, &release.tag), asset);
+            if !index.files.contains_key(&name) {
+                anyhow::bail!("{} fehlt im Paket", name);
+            }
+        }
+    }
+    extracted.insert(dir.join(INDEX_FILE), index_json);
+    for (target, data) in &extracted {
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(target, data)?;
+    }
+
+    let mut hw_types: Vec<HardwareType> = Vec::new();
+    for release in &index.releases {
+        match HardwareType::from_id(&release.hw_type) {
+            Some(hw) if !hw_types.contains(&hw) => hw_types.push(hw),
+            Some(_) => {}
+            None => crate::oplog::record(format!(
+                "Firmware-Paket: Hardware-Typ {} ist nicht im Katalog",
+                release.hw_type
+            )),
+        }
+    }
+    crate::oplog::record(format!(
+        "Firmware-Paket {} importiert nach {}",
+        path.display(),
+        dir.display()
+    ));
+    Ok(Imported {
+        dir,
+        hw_types,
+        releases: index.releases.len(),
+        signed,
+    })
+}
+
+/// Firmware-Quelle für ein importiertes Paket
+pub fn source(dir: &Path) -> String {
+    format!("{}{}", SOURCE_PREFIX, dir.display())
+}
+
+/// Paketverzeichnis, wenn die Firmware-Quelle auf ein importiertes Paket verweist
+pub fn source_dir(repo: &str) -> Option<PathBuf> {
+    repo.strip_prefix(SOURCE_PREFIX).map(PathBuf::from)
+}
+
+fn load_index(dir: &Path) -> anyhow::Result<BundleIndex> {
+    let path = dir.join(INDEX_FILE);
+    let data = std::fs::read(&path).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// Releases eines importierten Pakets für den Hardware-Typ
+pub fn releases(dir: &Path, hw_type: HardwareType) -> anyhow::Result<Vec<Release>> {
+    let model = hw_type.model();
+    Ok(load_index(dir)?
+        .releases
+        .into_iter()
+        .filter(|r| r.hw_type == hw_type.id())
+        .map(|r| {
+            let notes =
+                std::fs::read_to_string(dir.join(release_dir(&r.hw_type, &r.tag)).join(NOTES_FILE))
+                    .ok()
+                    .filter(|n| !n.trim().is_empty());
+            Release {
+                stm32_assets: r
+                    .assets
+                    .keys()
+                    .filter(|a| model.accepts_asset(a))
+                    .cloned()
+                    .collect(),
+                tag_name: r.tag,
+                prerelease: r.prerelease,
+                notes,
+            }
+        })
+        .filter(|r| !r.stm32_assets.is_empty())
+        .collect())
+}
+
+/// Pfad eines Assets im Paket; die Prüfsumme wird vor jeder Verwendung erneut geprüft
+pub fn asset_path(dir: &Path, tag: &str, asset: &str) -> anyhow::Result<PathBuf> {
+    let index = load_index(dir)?;
+    let (release, expected) = index
+        .releases
+        .iter()
+        .filter(|r| r.tag == tag)
+        .find_map(|r| r.assets.get(asset).map(|sha| (r, sha)))
+        .ok_or_else(|| anyhow::anyhow!("Asset '{}' nicht im Paket", asset))?;
+    let path = dir
+        .join(release_dir(&release.hw_type, &release.tag))
+        .join(asset);
+    if &sha256(&std::fs::read(&path)?) != expected {
+        anyhow::bail!("Prüfsumme von {} stimmt nicht", path.display());
+    }
+    Ok(path)
+}
+
+/// Nachrichten aus dem Hintergrund-Thread der Export-Ansicht
+pub enum BundleMsg {
+    Releases(Result<Vec<(HardwareType, Release)>, String>),
+    Progress(String),
+    Exported(Result<String, String>),
+}
+
+/// Lädt Releases bzw. erstellt ein Paket im Hintergrund
+pub struct BundleHandle {
+    pub rx: std::sync::mpsc::Receiver<BundleMsg>,
+}
+
+impl BundleHandle {
+    /// Releases aller gewählten Hardware-Typen aus ihren Firmware-Quellen
+    pub fn load_releases(settings: Settings, hw_types: Vec<HardwareType>) -> Self {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut releases = Vec::new();
+            for hw_type in hw_types {
+                let repo = settings.firmware_source(hw_type);
+                match crate::flash::fetch_releases(&repo, settings.github_token.as_deref(), hw_type)
+                {
+                    Ok(list) => releases.extend(
+                        list.into_iter()
+                            .filter(|r| settings.show_prereleases || !r.prerelease)
+                            .map(|r| (hw_type, r)),
+                    ),
+                    Err(e) => {
+                        let _ = tx.send(BundleMsg::Releases(Err(format!("{}: {}", hw_type, e))));
+                        return;
+                    }
+                }
+            }
+            let _ = tx.send(BundleMsg::Releases(Ok(releases)));
+        });
+        Self { rx }
+    }
+
+    pub fn export(
+        settings: Settings,
+        releases: Vec<(HardwareType, Release)>,
+        path: PathBuf,
+    ) -> Self {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let progress = tx.clone();
+            let result = export(&settings, &releases, &path, |msg| {
+                let _ = progress.send(BundleMsg::Progress(msg));
+            })
+            .map_err(|e| format!("{:#}", e));
+            let _ = tx.send(BundleMsg::Exported(result));
+        });
+        Self { rx }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Paket ohne Signatur und ohne Releases
+    fn unsigned_bundle(dir: &Path) -> PathBuf {
+        let index = BundleIndex {
+            created: Local::now(),
+            created_by: None,
+            releases: Vec::new(),
+            files: BTreeMap::new(),
+        };
+        let path = dir.join(format!("unsigned.{}", EXTENSION));
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        zip.start_file(INDEX_FILE, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(&serde_json::to_vec(&index).unwrap()).unwrap();
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn unsigned_bundles_need_permission() {
+        let (dir, settings) = crate::testing::environment();
+        let path = unsigned_bundle(dir.path());
+        assert!(import(&settings, &path, false).is_err());
+        assert!(!import(&settings, &path, true).unwrap().signed);
+    }
+
+    #[test]
+    fn configured_key_requires_signature() {
+        let (dir, mut settings) = crate::testing::environment();
+        let path = unsigned_bundle(dir.path());
+        settings.bundle_public_key = Some(base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD,
+            [7u8; 32],
+        ));
+        assert!(import(&settings, &path, true).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+use crate::audit::{Action, AuditRecord};
+use crate::diagnostics::Unrelated;
+use crate::flash::{DownloadMsg, FirmwareDownloadHandle, FlashConfig, ImageFile};
+use crate::hardware::{HardwareType, MemoryRange};
+use crate::image::FirmwareImage;
+use crate::operations::{self, parse_number};
+use crate::operator::{Operator, Operators, Permission};
+use crate::policy::Policy;
+use crate::probe::ProbeConfig;
+use crate::settings::Settings;
+use crate::version::Version;
+use std::path::PathBuf;
+
+const COMMANDS: &[&str] = &["flash", "verify", "read", "erase"];
+
+/// Gerätebefehl, der ohne Oberfläche ausgeführt wird
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// Assets eines Releases herunterladen und flashen
+    Flash {
+        tag: String,
+        assets: Vec<String>,
+    },
+    Verify(Vec<String>),
+    Read {
+        path: PathBuf,
+        range: Option<MemoryRange>,
+    },
+    /// `None` = gesamter Chip
+    Erase(Option<MemoryRange>),
+}
+
+/// Trennt Optionen und Befehl: alles ab dem ersten Befehlswort gehört zum Befehl
+pub fn split_args(args: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let mut options = args;
+    let position = options.iter().position(|a| COMMANDS.contains(&a.as_str()));
+    let command = match position {
+        Some(position) => options.split_off(position),
+        None => Vec::new(),
+    };
+    (options, command)
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Option<Command>, String> {
+        let Some((name, rest)) = args.split_first() else {
+            return Ok(None);
+        };
+        let range = |address: &String, size: &String| -> Result<MemoryRange, String> {
+            Ok(MemoryRange {
+                base: parse_number(address)
+                    .ok_or_else(|| format!("Ungültige Adresse: {}", address))?,
+                size: parse_number(size).ok_or_else(|| format!("Ungültige Größe: {}", size))?,
+            })
+        };
+        let command = match (name.as_str(), rest) {
+            ("flash", [tag, assets @ ..]) if !assets.is_empty() => Command::Flash {
+                tag: tag.clone(),
+                assets: assets.to_vec(),
+            },
+            ("verify", files) if !files.is_empty() => Command::Verify(files.to_vec()),
+            ("read", [path]) => Command::Read {
+                path: PathBuf::from(path),
+                range: None,
+            },
+            ("read", [path, address, size]) => Command::Read {
+                path: PathBuf::from(path),
+                range: Some(range(address, size)?),
+            },
+            ("erase", [chip]) if chip == "chip" => Command::Erase(None),
+            ("erase", [address, size]) => Command::Erase(Some(range(address, size)?)),
+            _ => {
+                return Err(format!(
+                    "Ungültige Argumente für {}\n\n{}",
+                    name,
+                    crate::settings::USAGE
+                ));
+            }
+        };
+        Ok(Some(command))
+    }
+
+    /// Berechtigung, die der Befehl über die Operator-Rolle hinaus erfordert
+    fn permission(&self) -> Option<Permission> {
+        match self {
+            Command::Flash { tag, .. }
+                if Version::parse(tag).is_some_and(|version| version.pre.is_some()) =>
+            {
+                Some(Permission::PrereleaseFirmware)
+            }
+            Command::Flash { .. } | Command::Verify(_) => None,
+            Command::Read { .. } | Command::Erase(_) => Some(Permission::ReadOutErase),
+        }
+    }
+
+    /// Meldet den Bediener an, führt den Befehl aus und liefert den Exit-Code
+    pub fn run(&self, settings: &Settings, operator: Option<&str>, pin: Option<&str>) -> i32 {
+        let result = login(settings, operator, pin).and_then(|operator| {
+            if let Some(permission) = self.permission()
+                && !operator.allows(permission)
+            {
+                return Err(Unrelated(format!(
+                    "{} requires the {} role.",
+                    permission,
+                    permission.required_role()
+                ))
+                .into());
+            }
+            self.execute(settings)
+        });
+        match result {
+            Ok(msg) => {
+                println!("{}", msg);
+                0
+            }
+            Err(e) => {
+                eprintln!("{:#}", e);
+                let probe = ProbeConfig::from_settings(settings);
+                if let Some(diagnosis) = crate::diagnostics::diagnose_error(&e, &probe) {
+                    eprintln!("\n{}", diagnosis.report(settings.language));
+                }
+                1
+            }
+        }
+    }
+
+    fn execute(&self, settings: &Settings) -> anyhow::Result<String> {
+        let hw_type = hw_type_for(settings)?;
+        let files = match self {
+            Command::Verify(files) => files.as_slice(),
+            _ => &[],
+        };
+        let config = FlashConfig::new(files, hw_type, settings);
+        match self {
+            Command::Flash { tag, assets } => {
+                // Freigabeliste vor dem Download prüfen
+                Policy::load(settings)
+                    .check_assets(hw_type, tag, assets)
+                    .map_err(|e| Unrelated(e.to_string()))?;
+                let paths = download(settings, hw_type, tag, assets)
+                    .map_err(|e| Unrelated(format!("{:#}", e)))?;
+                let images = paths
+                    .iter()
+                    .map(|path| {
+                        let file = ImageFile::for_model(path.clone(), hw_type);
+                        FirmwareImage::load(std::path::Path::new(&file.path), file.base)
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+                    .map_err(|e| Unrelated(format!("{:#}", e)))?;
+                let findings = crate::sanity::check_images(&images, hw_type, false);
+                for finding in &findings {
+                    eprintln!("{}", finding.message);
+                }
+                if crate::sanity::has_errors(&findings) {
+                    return Err(Unrelated("Firmware-Prüfung fehlgeschlagen".to_string()).into());
+                }
+                let mut config = FlashConfig::new(&paths, hw_type, settings);
+                config.firmware_tag = Some(tag.clone());
+                // Der Tag wurde ausdrücklich auf der Kommandozeile angegeben
+                config.allow_version_change = true;
+                let result = crate::flash::flash_hardware(&config);
+                if !result.success {
+                    if result.hardware_error {
+                        anyhow::bail!(result.message);
+                    }
+                    return Err(Unrelated(result.message).into());
+                }
+                Ok(result.message)
+            }
+            Command::Verify(_) => {
+                let report = operations::verify(&config)?;
+                if !report.is_match() {
+                    return Err(Unrelated(report.summary()).into());
+                }
+                Ok(report.summary())
+            }
+            Command::Read { path, range } => {
+                let range = range.unwrap_or(hw_type.model().flash);
+                operations::read_out(&config, range, path)?;
+                Ok(format!("{} gespeichert in {}", range, path.display()))
+            }
+            Command::Erase(range) => {
+                operations::erase(&config, *range)?;
+                Ok(match range {
+                    Some(range) => format!("{} gelöscht", range),
+                    None => "Chip gelöscht".to_string(),
+                })
+            }
+        }
+    }
+}
+
+/// Prüft Name und PIN wie die Anmeldung in der Oberfläche und trägt den
+/// Bediener für das Audit-Log ein
+fn login(settings: &Settings, name: Option<&str>, pin: Option<&str>) -> anyhow::Result<Operator> {
+    let (Some(name), Some(pin)) = (name, pin) else {
+        return Err(Unrelated(
+            "Bitte --operator angeben und die PIN in IROCK_PIN setzen".to_string(),
+        )
+        .into());
+    };
+    let operators = Operators::load(settings)?;
+    if operators.operators.is_empty() {
+        return Err(Unrelated(
+            "Keine Bedienerprofile angelegt, zuerst in der Oberfläche einen Engineer anlegen"
+                .to_string(),
+        )
+        .into());
+    }
+    let result = operators.login(name, pin);
+    let mut record = AuditRecord::new(Action::Login, None);
+    record.operator = Some(name.trim().to_string());
+    record.success = result.is_ok();
+    if let Ok(operator) = &result {
+        record.details = format!("{} (Kommandozeile)", operator.role);
+    }
+    crate::audit::append(settings, &record)?;
+    let operator = result.map_err(|e| Unrelated(e.to_string()))?;
+    crate::operator::set_current(Some(&operator.name));
+    Ok(operator)
+}
+
+/// Lädt Release-Assets aus dem eingestellten Repository, wartet auf das Ende
+fn download(
+    settings: &Settings,
+    hw_type: HardwareType,
+    tag: &str,
+    assets: &[String],
+) -> anyhow::Result<Vec<String>> {
+    let handle = FirmwareDownloadHandle::start(
+        settings.clone(),
+        settings.firmware_source(hw_type),
+        tag.to_string(),
+        assets.to_vec(),
+    );
+    loop {
+        match handle.rx.recv()? {
+            DownloadMsg::Progress(_) => {}
+            DownloadMsg::Done(paths) => return Ok(paths),
+            DownloadMsg::Error(e) => anyhow::bail!(e),
+        }
+    }
+}
+
+/// Hardware-Typ aus den Einstellungen, sonst vom angeschlossenen Gerät
+fn hw_type_for(settings: &Settings) -> anyhow::Result<HardwareType> {
+    if let Some(hw_type) = settings.default_hw_type() {
+        return Ok(hw_type);
+    }
+    let identity = crate::device::detect(&ProbeConfig::from_settings(settings), None)?;
+    identity.detected().ok_or_else(|| {
+        anyhow::anyhow!(
+            "Hardware-Typ nicht eindeutig erkannt ({}), bitte --hw-type angeben",
+            identity.summary()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator::Role;
+
+    fn command(args: &[&str]) -> Command {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        Command::parse(&args).unwrap().unwrap()
+    }
+
+    #[test]
+    fn commands_need_a_logged_in_operator() {
+        let (_dir, settings) = crate::testing::environment();
+        assert!(login(&settings, None, None).is_err());
+        let mut operators = Operators::default();
+        operators
+            .upsert(Operator::new("Eva", Role::Engineer, "1234").unwrap())
+            .unwrap();
+        operators.save(&settings).unwrap();
+        assert!(login(&settings, Some("Eva"), None).is_err());
+        assert!(login(&settings, Some("Eva"), Some("4321")).is_err());
+        assert_eq!(
+            login(&settings, Some("eva"), Some("1234")).unwrap().role,
+            Role::Engineer
+        );
+    }
+
+    #[test]
+    fn commands_apply_role_permissions() {
+        assert_eq!(command(&["flash", "v1.2.0", "app.bin"]).permission(), None);
+        assert_eq!(
+            command(&["flash", "v1.3.0-rc.1", "app.bin"]).permission(),
+            Some(Permission::PrereleaseFirmware)
+        );
+        assert_eq!(
+            command(&["read", "dump.bin"]).permission(),
+            Some(Permission::ReadOutErase)
+        );
+        assert_eq!(
+            command(&["erase", "chip"]).permission(),
+            Some(Permission::ReadOutErase)
+        );
+    }
+}
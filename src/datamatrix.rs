@@ -0,0 +1,325 @@
+//! Data Matrix ECC 200 (ISO/IEC 16022) für Etiketten: quadratische Symbole
+//! bis 48×48 Module, ASCII-Kodierung, ein Reed-Solomon-Block
+
+/// Symbolgröße, Kantenlänge eines Datenbereichs, Datenbereiche je Seite,
+/// Daten- und Fehlerkorrektur-Codewörter
+const SIZES: &[(usize, usize, usize, usize, usize)] = &[
+    (10, 8, 1, 3, 5),
+    (12, 10, 1, 5, 7),
+    (14, 12, 1, 8, 10),
+    (16, 14, 1, 12, 12),
+    (18, 16, 1, 18, 14),
+    (20, 18, 1, 22, 18),
+    (22, 20, 1, 30, 20),
+    (24, 22, 1, 36, 24),
+    (26, 24, 1, 44, 28),
+    (32, 14, 2, 62, 36),
+    (36, 16, 2, 86, 42),
+    (40, 18, 2, 114, 48),
+    (44, 20, 2, 144, 56),
+    (48, 22, 2, 174, 68),
+];
+
+/// Kodiertes Symbol, `true` = dunkles Modul
+pub struct DataMatrix {
+    pub size: usize,
+    modules: Vec<bool>,
+}
+
+impl DataMatrix {
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+}
+
+/// Kodiert `text` im kleinsten passenden Symbol
+pub fn encode(text: &str) -> anyhow::Result<DataMatrix> {
+    let mut data = ascii_codewords(text);
+    let &(size, region, regions, data_len, ecc_len) = SIZES
+        .iter()
+        .find(|s| s.3 >= data.len())
+        .ok_or_else(|| anyhow::anyhow!("Text ist zu lang für einen Data-Matrix-Code"))?;
+    pad(&mut data, data_len);
+    let ecc = reed_solomon(&data, ecc_len);
+    data.extend(ecc);
+
+    let mapping = regions * region;
+    let placement = place(mapping, mapping);
+    let mut modules = vec![false; size * size];
+    let block = region + 2;
+    for y in 0..size {
+        for x in 0..size {
+            let (iy, ix) = (y % block, x % block);
+            let dark = if iy == block - 1 || ix == 0 {
+                // Durchgehende L-Kante links und unten
+                true
+            } else if iy == 0 {
+                // Taktspur oben
+                ix % 2 == 0
+            } else if ix == block - 1 {
+                // Taktspur rechts
+                iy % 2 == 1
+            } else {
+                let row = y / block * region + iy - 1;
+                let col = x / block * region + ix - 1;
+                match placement[row * mapping + col] {
+                    Module::Fixed(dark) => dark,
+                    Module::Bit(codeword, bit) => data[codeword] >> (7 - bit) & 1 == 1,
+                }
+            };
+            modules[y * size + x] = dark;
+        }
+    }
+    Ok(DataMatrix { size, modules })
+}
+
+/// ASCII-Kodierung; Ziffernpaare werden zu einem Codewort zusammengefasst
+fn ascii_codewords(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut codewords = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        if b.is_ascii_digit() && bytes.get(i + 1).is_some_and(|n| n.is_ascii_digit()) {
+            codewords.push(130 + (b - b'0') * 10 + (bytes[i + 1] - b'0'));
+            i += 2;
+            continue;
+        }
+        if b < 128 {
+            codewords.push(b + 1);
+        } else {
+            // Upper Shift für Zeichen über 127
+            codewords.push(235);
+            codewords.push(b - 127);
+        }
+        i += 1;
+    }
+    codewords
+}
+
+/// Füllt mit dem Pad-Codewort 129 und danach pseudozufälligen Werten auf
+fn pad(data: &mut Vec<u8>, len: usize) {
+    if data.len() < len {
+        data.push(129);
+    }
+    while data.len() < len {
+        let position = data.len() + 1;
+        let value = 129 + (149 * position) % 253 + 1;
+        data.push(if value > 254 { value - 254 } else { value } as u8);
+    }
+}
+
+/// Fehlerkorrektur über GF(256) mit dem Polynom x⁸+x⁵+x³+x²+1
+fn reed_solomon(data: &[u8], ecc_len: usize) -> Vec<u8> {
+    let mut exp = [0u8; 256];
+    let mut log = [0u8; 256];
+    let mut value: u16 = 1;
+    for (i, e) in exp.iter_mut().enumerate().take(255) {
+        *e = value as u8;
+        log[value as usize] = i as u8;
+        value <<= 1;
+        if value & 0x100 != 0 {
+            value ^= 0x12D;
+        }
+    }
+    let mul = |a: u8, b: u8| -> u8 {
+        if a == 0 || b == 0 {
+            0
+        } else {
+            exp[(log[a as usize] as usize + log[b as usize] as usize) % 255]
+        }
+    };
+
+    // Generatorpolynom (x - α¹)(x - α²)…(x - αⁿ), Koeffizienten absteigend ohne führende 1
+    let mut generator = vec![1u8];
+    for i in 1..=ecc_len {
+        let root = exp[i % 255];
+        let mut next = vec![0u8; generator.len() + 1];
+        for (j, &g) in generator.iter().enumerate() {
+            next[j] ^= g;
+            next[j + 1] ^= mul(g, root);
+        }
+        generator = next;
+    }
+
+    let mut ecc = vec![0u8; ecc_len];
+    for &d in data {
+        let factor = d ^ ecc[0];
+        ecc.rotate_left(1);
+        ecc[ecc_len - 1] = 0;
+        for (e, &g) in ecc.iter_mut().zip(&generator[1..]) {
+            *e ^= mul(g, factor);
+        }
+    }
+    ecc
+}
+
+#[derive(Clone, Copy)]
+enum Module {
+    Fixed(bool),
+    /// Codewort und Bit (0 = höchstwertiges Bit)
+    Bit(usize, u8),
+}
+
+/// Anordnung der Codewort-Bits im Datenbereich (ISO/IEC 16022, Anhang F)
+struct Placement {
+    rows: isize,
+    cols: isize,
+    array: Vec<Option<Module>>,
+}
+
+impl Placement {
+    fn is_free(&self, row: isize, col: isize) -> bool {
+        self.array[(row * self.cols + col) as usize].is_none()
+    }
+
+    /// Setzt ein Bit; Koordinaten außerhalb werden auf die Gegenseite umgebrochen
+    fn module(&mut self, mut row: isize, mut col: isize, codeword: usize, bit: u8) {
+        if row < 0 {
+            row += self.rows;
+            col += 4 - ((self.rows + 4) % 8);
+        }
+        if col < 0 {
+            col += self.cols;
+            row += 4 - ((self.cols + 4) % 8);
+        }
+        self.array[(row * self.cols + col) as usize] = Some(Module::Bit(codeword, bit));
+    }
+
+    /// Regulär platziertes Codewort mit dem Bit 7 bei `row`, `col`
+    fn utah(&mut self, row: isize, col: isize, codeword: usize) {
+        let positions = [
+            (row - 2, col - 2),
+            (row - 2, col - 1),
+            (row - 1, col - 2),
+            (row - 1, col - 1),
+            (row - 1, col),
+            (row, col - 2),
+            (row, col - 1),
+            (row, col),
+        ];
+        for (bit, (r, c)) in positions.into_iter().enumerate() {
+            self.module(r, c, codeword, bit as u8);
+        }
+    }
+
+    /// Codewort in einer der vier Sonderformen an den Ecken
+    fn corner(&mut self, kind: usize, codeword: usize) {
+        let (n, m) = (self.rows, self.cols);
+        let positions = match kind {
+            1 => [
+                (n - 1, 0),
+                (n - 1, 1),
+                (n - 1, 2),
+                (0, m - 2),
+                (0, m - 1),
+                (1, m - 1),
+                (2, m - 1),
+                (3, m - 1),
+            ],
+            2 => [
+                (n - 3, 0),
+                (n - 2, 0),
+                (n - 1, 0),
+                (0, m - 4),
+                (0, m - 3),
+                (0, m - 2),
+                (0, m - 1),
+                (1, m - 1),
+            ],
+            3 => [
+                (n - 3, 0),
+                (n - 2, 0),
+                (n - 1, 0),
+                (0, m - 2),
+                (0, m - 1),
+                (1, m - 1),
+                (2, m - 1),
+                (3, m - 1),
+            ],
+            _ => [
+                (n - 1, 0),
+                (n - 1, m - 1),
+                (0, m - 3),
+                (0, m - 2),
+                (0, m - 1),
+                (1, m - 3),
+                (1, m - 2),
+                (1, m - 1),
+            ],
+        };
+        for (bit, (r, c)) in positions.into_iter().enumerate() {
+            self.module(r, c, codeword, bit as u8);
+        }
+    }
+}
+
+fn place(rows: usize, cols: usize) -> Vec<Module> {
+    let mut p = Placement {
+        rows: rows as isize,
+        cols: cols as isize,
+        array: vec![None; rows * cols],
+    };
+    let (nrow, ncol) = (p.rows, p.cols);
+    let mut codeword = 0;
+    let (mut row, mut col) = (4isize, 0isize);
+    loop {
+        if row == nrow && col == 0 {
+            p.corner(1, codeword);
+            codeword += 1;
+        }
+        if row == nrow - 2 && col == 0 && ncol % 4 != 0 {
+            p.corner(2, codeword);
+            codeword += 1;
+        }
+        if row == nrow - 2 && col == 0 && ncol % 8 == 4 {
+            p.corner(3, codeword);
+            codeword += 1;
+        }
+        if row == nrow + 4 && col == 2 && ncol % 8 == 0 {
+            p.corner(4, codeword);
+            codeword += 1;
+        }
+        // Diagonal nach rechts oben
+        loop {
+            if row < nrow && col >= 0 && p.is_free(row, col) {
+                p.utah(row, col, codeword);
+                codeword += 1;
+            }
+            row -= 2;
+            col += 2;
+            if row < 0 || col >= ncol {
+                break;
+            }
+        }
+        row += 1;
+        col += 3;
+        // Diagonal nach links unten
+        loop {
+            if row >= 0 && col < ncol && p.is_free(row, col) {
+                p.utah(row, col, codeword);
+                codeword += 1;
+            }
+            row += 2;
+            col -= 2;
+            if row >= nrow || col < 0 {
+                break;
+            }
+        }
+        row += 3;
+        col += 1;
+        if row >= nrow && col >= ncol {
+            break;
+        }
+    }
+    // Unbelegte Ecke rechts unten erhält ein festes Muster
+    let last = rows * cols - 1;
+    if p.array[last].is_none() {
+        p.array[last] = Some(Module::Fixed(true));
+        p.array[last - cols - 1] = Some(Module::Fixed(true));
+    }
+    p.array
+        .into_iter()
+        .map(|m| m.unwrap_or(Module::Fixed(false)))
+        .collect()
+}
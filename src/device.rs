@@ -0,0 +1,334 @@
+use crate::backend::ProgrammerBackend;
+use crate::hardware::{HardwareModel, HardwareType};
+use crate::probe::{ProbeConfig, RetryPolicy};
+use crate::version::Version;
+use std::sync::mpsc::{self, Receiver};
+
+/// Kennung "IRCK" am Anfang des Info-Blocks
+const INFO_BLOCK_MAGIC: u32 = 0x4B43_5249;
+/// Info-Block: Magic (4), Board-ID (4), Versions-String (32, nullterminiert)
+const INFO_BLOCK_VERSION_OFFSET: u64 = 8;
+const INFO_BLOCK_VERSION_LEN: usize = 32;
+/// Bereich am Anfang der Firmware, in dem nach einem Versions-String gesucht wird
+const VERSION_SCAN_LEN: u64 = 64 * 1024;
+
+/// Herkunft der gelesenen Firmware-Version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionSource {
+    InfoBlock,
+    Embedded,
+}
+
+/// Auf dem Gerät installierte Firmware
+#[derive(Debug, Clone)]
+pub struct InstalledFirmware {
+    pub initial_sp: u32,
+    pub reset_vector: u32,
+    pub version: Option<Version>,
+    pub version_source: Option<VersionSource>,
+}
+
+impl InstalledFirmware {
+    pub fn summary(&self) -> String {
+        match (&self.version, self.version_source) {
+            (Some(version), Some(VersionSource::InfoBlock)) => format!("v{}", version),
+            (Some(version), _) => format!("v{} (embedded string)", version),
+            (None, _) => format!(
+                "unknown version (initial SP 0x{:08X}, reset vector 0x{:08X})",
+                self.initial_sp, self.reset_vector
+            ),
+        }
+    }
+}
+
+/// Erkannte Kennungen des angeschlossenen Geräts
+#[derive(Debug, Clone, Default)]
+pub struct DeviceIdentity {
+    /// Vollständiger Wert von DBGMCU_IDCODE
+    pub idcode: Option<u32>,
+    /// 96-Bit-Unique-ID des Mikrocontrollers als Hex-String; unterscheidet
+    /// einzelne Geräte, anders als DBGMCU_IDCODE
+    pub unique_id: Option<String>,
+    /// Board-Kennung aus dem Info-Block der Firmware
+    pub board_id: Option<u32>,
+    /// Alle Modelle, die zu den gelesenen Kennungen passen
+    pub candidates: Vec<HardwareType>,
+    /// `None`, wenn der Flash leer ist oder keine gültige Vektortabelle enthält
+    pub firmware: Option<InstalledFirmware>,
+    /// Art der Verbindung (probe-rs-Target oder Demo-Gerät)
+    pub connection: String,
+}
+
+impl DeviceIdentity {
+    pub fn device_id(&self) -> Option<u16> {
+        self.idcode.map(|id| (id & 0xFFF) as u16)
+    }
+
+    /// Eindeutig erkanntes Modell
+    pub fn detected(&self) -> Option<HardwareType> {
+        match self.candidates.as_slice() {
+            [hw] => Some(*hw),
+            _ => None,
+        }
+    }
+
+    /// `true`, wenn die Kennungen gelesen wurden und das Modell nicht dazu passt
+    pub fn contradicts(&self, hw_type: HardwareType) -> bool {
+        self.idcode.is_some() && !self.candidates.contains(&hw_type)
+    }
+
+    pub fn summary(&self) -> String {
+        let device = match self.device_id() {
+            Some(id) => format!("device ID 0x{:03X}", id),
+            None => "device ID unknown".to_string(),
+        };
+        let board = match self.board_id {
+            Some(id) => format!("board ID 0x{:04X}", id),
+            None => "no firmware info block".to_string(),
+        };
+        let model = match self.candidates.as_slice() {
+            [] => "unknown model".to_string(),
+            [hw] => hw.to_string(),
+            many => format!(
+                "ambiguous: {}",
+                many.iter()
+                    .map(|hw| hw.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        let firmware = match &self.firmware {
+            Some(firmware) => format!("firmware {}", firmware.summary()),
+            None => "no firmware installed".to_string(),
+        };
+        format!("{} ({}, {}, {})", model, device, board, firmware)
+    }
+}
+
+/// Dekodiert einen String-Parameter; endet am ersten NUL- oder 0xFF-Byte (gelöschter Flash)
+pub fn decode_string_parameter(raw: &[u8]) -> Option<String> {
+    let len = raw
+        .iter()
+        .position(|b| *b == 0 || *b == 0xFF)
+        .unwrap_or(raw.len());
+    let text = std::str::from_utf8(&raw[..len]).ok()?.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Liest Device-ID und Board-Kennung über eine bestehende Verbindung und
+/// ordnet sie den Modellen im Hardware-Katalog zu
+pub fn identify(backend: &mut dyn ProgrammerBackend) -> anyhow::Result<DeviceIdentity> {
+    let mut identity = DeviceIdentity {
+        connection: backend.info(),
+        ..DeviceIdentity::default()
+    };
+
+    let mut idcode_addresses: Vec<u64> = HardwareType::all()
+        .iter()
+        .map(|hw| hw.model().idcode_address)
+        .collect();
+    idcode_addresses.sort_unstable();
+    idcode_addresses.dedup();
+    let mut candidates: Vec<HardwareType> = Vec::new();
+    for address in idcode_addresses {
+        let Ok(idcode) = backend.read_word_32(address) else {
+            continue;
+        };
+        if idcode == 0 {
+            continue;
+        }
+        let device_id = (idcode & 0xFFF) as u16;
+        let matching: Vec<HardwareType> = HardwareType::all()
+            .iter()
+            .copied()
+            .filter(|hw| {
+                let model = hw.model();
+                model.idcode_address == address && model.device_id == Some(device_id)
+            })
+            .collect();
+        if identity.idcode.is_none() || !matching.is_empty() {
+            identity.idcode = Some(idcode);
+        }
+        candidates.extend(matching);
+    }
+
+    let mut info_addresses: Vec<u64> = candidates
+        .iter()
+        .filter_map(|hw| hw.model().info_block)
+        .collect();
+    info_addresses.sort_unstable();
+    info_addresses.dedup();
+    let mut info_version = None;
+    for address in info_addresses {
+        // Ohne gültige Firmware fehlt der Info-Block, das ist kein Fehler
+        if let Ok(INFO_BLOCK_MAGIC) = backend.read_word_32(address) {
+            identity.board_id = backend.read_word_32(address + 4).ok();
+            let mut raw = [0u8; INFO_BLOCK_VERSION_LEN];
+            if backend
+                .read(address + INFO_BLOCK_VERSION_OFFSET, &mut raw)
+                .is_ok()
+            {
+                let len = raw.iter().position(|b| *b == 0).unwrap_or(raw.len());
+                info_version = std::str::from_utf8(&raw[..len])
+                    .ok()
+                    .and_then(Version::parse);
+            }
+            break;
+        }
+    }
+    if let Some(board_id) = identity.board_id {
+        let by_board: Vec<HardwareType> = candidates
+            .iter()
+            .copied()
+            .filter(|hw| hw.model().board_id == Some(board_id))
+            .collect();
+        if !by_board.is_empty() {
+            candidates = by_board;
+        }
+    }
+    if let Some(model) = candidates.first().map(|hw| hw.model()) {
+        identity.unique_id = read_unique_id(backend, model);
+        identity.firmware = read_installed_firmware(backend, model, info_version);
+    }
+    identity.candidates = candidates;
+    Ok(identity)
+}
+
+/// Liest die Unique-ID; höchstwertiges Wort zuerst, wie im Referenzhandbuch
+fn read_unique_id(backend: &mut dyn ProgrammerBackend, model: &HardwareModel) -> Option<String> {
+    let mut raw = [0u8; 12];
+    backend.read(model.unique_id_address, &mut raw).ok()?;
+    if raw.iter().all(|b| *b == 0) || raw.iter().all(|b| *b == 0xFF) {
+        return None;
+    }
+    Some(
+        raw.chunks(4)
+            .rev()
+            .map(|word| {
+                format!(
+                    "{:08X}",
+                    u32::from_le_bytes([word[0], word[1], word[2], word[3]])
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Prüft die Vektortabelle und ermittelt die Version der installierten Firmware.
+/// Ohne Version im Info-Block wird am Anfang der Firmware nach einem
+/// eingebetteten Versions-String gesucht.
+fn read_installed_firmware(
+    backend: &mut dyn ProgrammerBackend,
+    model: &HardwareModel,
+    info_version: Option<Version>,
+) -> Option<InstalledFirmware> {
+    let application = model.application();
+    let base = application.base;
+    let initial_sp = backend.read_word_32(base).ok()?;
+    let reset_vector = backend.read_word_32(base + 4).ok()?;
+    let sp_valid = model.ram.contains(initial_sp as u64) || initial_sp as u64 == model.ram.end();
+    let reset_valid = reset_vector & 1 == 1 && model.flash.contains((reset_vector & !1) as u64);
+    if !sp_valid || !reset_valid {
+        return None;
+    }
+    let (version, version_source) = match info_version {
+        Some(version) => (Some(version), Some(VersionSource::InfoBlock)),
+        None => {
+            let mut image = vec![0u8; VERSION_SCAN_LEN.min(application.size) as usize];
+            let version = backend
+                .read(base, &mut image)
+                .ok()
+                .and_then(|_| crate::version::find_embedded(&image));
+            let source = version.as_ref().map(|_| VersionSource::Embedded);
+            (version, source)
+        }
+    };
+    Some(InstalledFirmware {
+        initial_sp,
+        reset_vector,
+        version,
+        version_source,
+    })
+}
+
+/// Verbindet sich mit dem Gerät und identifiziert es. Mit `hint` wird zuerst
+/// dessen Target verwendet, danach die automatische Erkennung von probe-rs
+/// und schließlich alle Targets aus dem Katalog.
+pub fn detect(config: &ProbeConfig, hint: Option<HardwareType>) -> anyhow::Result<DeviceIdentity> {
+    let mut targets: Vec<Option<&str>> = Vec::new();
+    if let Some(hw) = hint {
+        targets.push(Some(hw.model().target.as_str()));
+    }
+    targets.push(None);
+    for hw in HardwareType::all() {
+        let target = Some(hw.model().target.as_str());
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    // Ohne Probe sind alle weiteren Versuche zwecklos
+    crate::probe::ensure_probe(config)?;
+    // Jedes Target nur einmal versuchen, die Liste der Targets ersetzt die Wiederholungen
+    let config = ProbeConfig {
+        retry: RetryPolicy::once(),
+        ..config.clone()
+    };
+    let mut last_error = None;
+    for target in targets {
+        match crate::probe::attach(&config, target) {
+            Ok(mut backend) => return identify(backend.as_mut()),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("Kein Target verfügbar")))
+}
+
+/// Geräteerkennung im Hintergrund, Ergebnis per `poll` abholen
+pub struct DetectHandle {
+    rx: Receiver<Result<DeviceIdentity, String>>,
+}
+
+impl DetectHandle {
+    pub fn start(config: ProbeConfig, hint: Option<HardwareType>) -> Self {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = detect(&config, hint)
+                .map_err(|e| format!("Fehler bei der Geräteerkennung: {:#}", e));
+            let _ = tx.send(result);
+        });
+        DetectHandle { rx }
+    }
+
+    pub fn poll(&self) -> Option<Result<DeviceIdentity, String>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Some(Err("Fehler bei der Geräteerkennung".to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identify_reads_unique_id() {
+        let device = crate::testing::device();
+        let identity = identify(device.connect().as_mut()).unwrap();
+        assert_eq!(identity.detected(), Some(device.hw_type));
+        assert_eq!(
+            identity.unique_id.as_deref(),
+            Some("323437333530470F002A0031")
+        );
+    }
+
+    #[test]
+    fn detect_finds_device_without_hint() {
+        let device = crate::testing::device();
+        let identity = detect(&device.probe(), None).unwrap();
+        assert_eq!(identity.detected(), Some(device.hw_type));
+    }
+}
@@ -0,0 +1,377 @@
+use crate::probe::ProbeConfig;
+use crate::settings::Language;
+use std::fmt;
+
+/// Unterhalb dieser Spannung gilt das Gerät als nicht versorgt
+const MIN_TARGET_VOLTAGE: f32 = 1.5;
+
+/// Fehlerklassen für Probe- und Verbindungsfehler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    NoProbe,
+    Permission,
+    NotPowered,
+    Wiring,
+    Locked,
+    UnsupportedTarget,
+    Unknown,
+}
+
+impl ErrorKind {
+    pub fn all() -> &'static [ErrorKind] {
+        &[
+            ErrorKind::NoProbe,
+            ErrorKind::Permission,
+            ErrorKind::NotPowered,
+            ErrorKind::Wiring,
+            ErrorKind::Locked,
+            ErrorKind::UnsupportedTarget,
+            ErrorKind::Unknown,
+        ]
+    }
+
+    /// Erkennung anhand der Fehlermeldung (inklusive Ursachenkette). Muster
+    /// gelten nur als ganze Wörter, "is locked" passt also nicht auf "is unlocked".
+    /// Eine von einem anderen Prozess belegte Probe ist ein Berechtigungsproblem
+    /// und wird deshalb vor dem gesperrten Mikrocontroller geprüft.
+    fn from_message(message: &str) -> ErrorKind {
+        let message = message.to_lowercase();
+        let has = |patterns: &[&str]| patterns.iter().any(|p| contains_phrase(&message, p));
+        if has(&[
+            "no probe",
+            "no probes",
+            "no debug probe",
+            "probe was not found",
+            "keine debug-probe",
+            "probe not found",
+        ]) {
+            ErrorKind::NoProbe
+        } else if has(&[
+            "permission denied",
+            "access denied",
+            "libusb_error_access",
+            "insufficient permissions",
+            "locked by another process",
+            "already in use",
+            "resource busy",
+            "libusb_error_busy",
+        ]) {
+            ErrorKind::Permission
+        } else if has(&[
+            "core is locked",
+            "chip is locked",
+            "device is locked",
+            "target is locked",
+            "readout protection",
+            "flash is protected",
+            "write protected",
+        ]) {
+            ErrorKind::Locked
+        } else if has(&[
+            "target not found",
+            "could not find target",
+            "unknown target",
+            "chip not found",
+            "list of known targets",
+            "chip description",
+            "auto-detect",
+            "autodetect",
+        ]) {
+            ErrorKind::UnsupportedTarget
+        } else if has(&[
+            "no acknowledge",
+            "did not respond",
+            "fault response",
+            "wait response",
+            "protocol error",
+            "debug port",
+            "access port",
+            "arm interface",
+            "timeout occurred",
+        ]) {
+            ErrorKind::Wiring
+        } else {
+            ErrorKind::Unknown
+        }
+    }
+
+    pub fn title(&self, language: Language) -> &'static str {
+        match (self, language) {
+            (ErrorKind::NoProbe, Language::De) => "Keine Debug-Probe gefunden",
+            (ErrorKind::NoProbe, Language::En) => "No debug probe found",
+            (ErrorKind::Permission, Language::De) => "Keine Berechtigung für die Probe",
+            (ErrorKind::Permission, Language::En) => "No permission to access the probe",
+            (ErrorKind::NotPowered, Language::De) => "Gerät nicht mit Spannung versorgt",
+            (ErrorKind::NotPowered, Language::En) => "Device not powered",
+            (ErrorKind::Wiring, Language::De) => "Keine Verbindung zum Mikrocontroller",
+            (ErrorKind::Wiring, Language::En) => "No connection to the microcontroller",
+            (ErrorKind::Locked, Language::De) => "Mikrocontroller gesperrt",
+            (ErrorKind::Locked, Language::En) => "Microcontroller locked",
+            (ErrorKind::UnsupportedTarget, Language::De) => "Mikrocontroller nicht unterstützt",
+            (ErrorKind::UnsupportedTarget, Language::En) => "Microcontroller not supported",
+            (ErrorKind::Unknown, Language::De) => "Unbekannter Fehler",
+            (ErrorKind::Unknown, Language::En) => "Unknown error",
+        }
+    }
+
+    pub fn explanation(&self, language: Language) -> &'static str {
+        match (self, language) {
+            (ErrorKind::NoProbe, Language::De) => {
+                "Es ist keine unterstützte Debug-Probe (ST-Link, J-Link, CMSIS-DAP) angeschlossen \
+                 oder die bevorzugte Probe aus den Einstellungen ist nicht vorhanden."
+            }
+            (ErrorKind::NoProbe, Language::En) => {
+                "No supported debug probe (ST-Link, J-Link, CMSIS-DAP) is connected, or the \
+                 preferred probe from the settings is not present."
+            }
+            (ErrorKind::Permission, Language::De) => {
+                "Die Probe wurde gefunden, das Betriebssystem verweigert aber den Zugriff."
+            }
+            (ErrorKind::Permission, Language::En) => {
+                "The probe was found, but the operating system denies access to it."
+            }
+            (ErrorKind::NotPowered, Language::De) => {
+                "Die Probe misst keine oder eine zu niedrige Spannung am Gerät."
+            }
+            (ErrorKind::NotPowered, Language::En) => {
+                "The probe measures no or too low a voltage on the device."
+            }
+            (ErrorKind::Wiring, Language::De) => {
+                "Die Probe bekommt keine Antwort über SWD. Meist ist die Verkabelung fehlerhaft, \
+                 die Leitungen sind zu lang oder die Firmware blockiert die SWD-Pins."
+            }
+            (ErrorKind::Wiring, Language::En) => {
+                "The probe gets no response via SWD. Usually the wiring is wrong, the cables are \
+                 too long or the firmware blocks the SWD pins."
+            }
+            (ErrorKind::Locked, Language::De) => {
+                "Die Readout-Protection oder ein Schreibschutz ist aktiv. Der Flash kann erst \
+                 nach dem Entsperren gelesen oder beschrieben werden."
+            }
+            (ErrorKind::Locked, Language::En) => {
+                "Readout protection or write protection is active. The flash cannot be read or \
+                 written until the device is unlocked."
+            }
+            (ErrorKind::UnsupportedTarget, Language::De) => {
+                "probe-rs kennt den Mikrocontroller nicht oder konnte ihn nicht automatisch erkennen."
+            }
+            (ErrorKind::UnsupportedTarget, Language::En) => {
+                "probe-rs does not know the microcontroller or could not detect it automatically."
+            }
+            (ErrorKind::Unknown, Language::De) => {
+                "Der Fehler konnte keiner bekannten Ursache zugeordnet werden."
+            }
+            (ErrorKind::Unknown, Language::En) => {
+                "The error could not be attributed to a known cause."
+            }
+        }
+    }
+
+    pub fn fix(&self, language: Language) -> &'static str {
+        match (self, language) {
+            (ErrorKind::NoProbe, Language::De) => {
+                "Probe per USB anschließen, anderes Kabel oder anderen USB-Port probieren und die \
+                 bevorzugte Probe in den Einstellungen prüfen."
+            }
+            (ErrorKind::NoProbe, Language::En) => {
+                "Connect the probe via USB, try another cable or USB port and check the \
+                 preferred probe in the settings."
+            }
+            (ErrorKind::Permission, Language::De) => {
+                "Linux: udev-Regeln für die Probe installieren und die Probe neu einstecken. \
+                 Windows: WinUSB-Treiber installieren. Andere Programme, die die Probe nutzen, \
+                 schließen."
+            }
+            (ErrorKind::Permission, Language::En) => {
+                "Linux: install the udev rules for the probe and re-plug it. Windows: install \
+                 the WinUSB driver. Close other programs using the probe."
+            }
+            (ErrorKind::NotPowered, Language::De) => {
+                "Gerät einschalten bzw. Akku anschließen und prüfen, ob VTref der Probe mit der \
+                 Versorgung des Geräts verbunden ist."
+            }
+            (ErrorKind::NotPowered, Language::En) => {
+                "Switch on the device or connect the battery and check that the probe's VTref \
+                 is connected to the device supply."
+            }
+            (ErrorKind::Wiring, Language::De) => {
+                "SWDIO, SWCLK, GND und NRST prüfen, kürzere Kabel verwenden, in den Einstellungen \
+                 eine niedrigere SWD-Geschwindigkeit oder \"Connect under reset\" wählen oder den \
+                 Wiederherstellungs-Assistenten starten."
+            }
+            (ErrorKind::Wiring, Language::En) => {
+                "Check SWDIO, SWCLK, GND and NRST, use shorter cables, select a lower SWD speed \
+                 or \"Connect under reset\" in the settings, or start the recovery wizard."
+            }
+            (ErrorKind::Locked, Language::De) => {
+                "Im Bereich \"Option bytes / readout protection\" die RDP-Regression ausführen \
+                 oder den Wiederherstellungs-Assistenten starten. Achtung: der Flash wird gelöscht."
+            }
+            (ErrorKind::Locked, Language::En) => {
+                "Run the RDP regression in \"Option bytes / readout protection\" or start the \
+                 recovery wizard. Caution: the flash will be erased."
+            }
+            (ErrorKind::UnsupportedTarget, Language::De) => {
+                "Den richtigen Hardware-Typ wählen und das probe-rs-Target im Hardware-Katalog \
+                 prüfen."
+            }
+            (ErrorKind::UnsupportedTarget, Language::En) => {
+                "Select the correct hardware type and check the probe-rs target in the \
+                 hardware catalogue."
+            }
+            (ErrorKind::Unknown, Language::De) => {
+                "Fehlermeldung beachten, Gerät und Probe neu verbinden und erneut versuchen."
+            }
+            (ErrorKind::Unknown, Language::En) => {
+                "Check the error message, reconnect device and probe and try again."
+            }
+        }
+    }
+}
+
+/// Sucht `phrase` als ganze Wörter, nicht als Teil eines längeren Worts
+fn contains_phrase(message: &str, phrase: &str) -> bool {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    message.match_indices(phrase).any(|(start, _)| {
+        !is_word(message[..start].chars().next_back())
+            && !is_word(message[start + phrase.len()..].chars().next())
+    })
+}
+
+/// Fehler ohne Bezug zu Probe oder Verbindung, z.B. Image-Prüfung, Freigabeliste,
+/// fehlende Bestätigung oder Download. Solche Fehler werden nicht diagnostiziert.
+#[derive(Debug)]
+pub struct Unrelated(pub String);
+
+impl fmt::Display for Unrelated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Unrelated {}
+
+/// Probe- oder Flash-Fehler, für den eine Diagnose sinnvoll ist
+pub fn is_hardware_error(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<Unrelated>().is_none()
+}
+
+/// Eingeordneter Fehler mit Messwerten der Probe
+#[derive(Debug, Clone)]
+pub struct Diagnosis {
+    pub kind: ErrorKind,
+    /// Gemessene Versorgungsspannung des Geräts, falls die Probe das unterstützt
+    pub target_voltage: Option<f32>,
+}
+
+impl Diagnosis {
+    /// Text für Kommandozeile und Protokolle
+    pub fn report(&self, language: Language) -> String {
+        let mut text = format!(
+            "{}\n{}\n{}",
+            self.kind.title(language),
+            self.kind.explanation(language),
+            self.kind.fix(language)
+        );
+        if let Some(voltage) = self.target_voltage {
+            text.push_str(&format!("\nVTref: {:.2} V", voltage));
+        }
+        text
+    }
+}
+
+/// Ordnet eine Fehlermeldung ein und liest dazu die Spannung am Gerät
+pub fn diagnose(message: &str, probe: &ProbeConfig) -> Diagnosis {
+    let mut kind = ErrorKind::from_message(message);
+    let target_voltage = match kind {
+        ErrorKind::NoProbe | ErrorKind::Permission => None,
+        _ => crate::probe::target_voltage(probe),
+    };
+    if let Some(voltage) = target_voltage
+        && voltage < MIN_TARGET_VOLTAGE
+        && kind != ErrorKind::Locked
+    {
+        kind = ErrorKind::NotPowered;
+    }
+    Diagnosis {
+        kind,
+        target_voltage,
+    }
+}
+
+/// Wie [`diagnose`], aber `None` für Fehler ohne Bezug zu Probe oder Verbindung
+pub fn diagnose_error(error: &anyhow::Error, probe: &ProbeConfig) -> Option<Diagnosis> {
+    is_hardware_error(error).then(|| diagnose(&format!("{:#}", error), probe))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_messages() {
+        let cases = [
+            ("Probe was not found.", ErrorKind::NoProbe),
+            ("Keine Debug-Probe gefunden", ErrorKind::NoProbe),
+            ("No probes found", ErrorKind::NoProbe),
+            ("USB: LIBUSB_ERROR_ACCESS", ErrorKind::Permission),
+            (
+                "open probe: Permission denied (os error 13)",
+                ErrorKind::Permission,
+            ),
+            ("The core is locked", ErrorKind::Locked),
+            ("Flash is protected against reading", ErrorKind::Locked),
+            ("Sector 2 is write protected", ErrorKind::Locked),
+            ("Chip is locked", ErrorKind::Locked),
+            ("Device unlocked, flashing failed", ErrorKind::Unknown),
+            ("The core is unlocked", ErrorKind::Unknown),
+            (
+                "The probe is locked by another process",
+                ErrorKind::Permission,
+            ),
+            ("Probe is already in use", ErrorKind::Permission),
+            (
+                "USB: LIBUSB_ERROR_BUSY: Device or resource busy",
+                ErrorKind::Permission,
+            ),
+            ("Option erase_all is not set", ErrorKind::Unknown),
+            ("Unknown target 'STM32X'", ErrorKind::UnsupportedTarget),
+            (
+                "Failed to auto-detect the chip",
+                ErrorKind::UnsupportedTarget,
+            ),
+            (
+                "Target device did not respond to request.",
+                ErrorKind::Wiring,
+            ),
+            ("SWD: no acknowledge from target", ErrorKind::Wiring),
+            ("Timeout occurred during operation.", ErrorKind::Wiring),
+            ("Download: operation timed out", ErrorKind::Unknown),
+            ("SWD speed 4000 kHz set", ErrorKind::Unknown),
+            ("Format not supported", ErrorKind::Unknown),
+            ("Erase all sectors failed", ErrorKind::Unknown),
+        ];
+        for (message, kind) in cases {
+            assert_eq!(ErrorKind::from_message(message), kind, "{}", message);
+        }
+    }
+
+    #[test]
+    fn phrases_match_whole_words_only() {
+        assert!(contains_phrase("the core is locked.", "core is locked"));
+        assert!(contains_phrase("locked", "locked"));
+        assert!(!contains_phrase("unlocked", "locked"));
+        assert!(!contains_phrase("lockedown", "locked"));
+        assert!(contains_phrase("x, no probe found", "no probe"));
+    }
+
+    #[test]
+    fn unrelated_errors_are_not_diagnosed() {
+        let probe = ProbeConfig::default();
+        let error = anyhow::Error::new(Unrelated("Firmware-Prüfung fehlgeschlagen".into()))
+            .context("Fehler beim Flashen");
+        assert!(!is_hardware_error(&error));
+        assert!(diagnose_error(&error, &probe).is_none());
+        assert!(is_hardware_error(&anyhow::anyhow!("The core is locked")));
+    }
+}
@@ -1,4 +1,14 @@
-use crate::hardware::HardwareType;
+use crate::audit::{Action, AuditRecord, FirmwareRef};
+use crate::backend::ProgrammerBackend;
+use crate::device::DeviceIdentity;
+use crate::diagnostics::Unrelated;
+use crate::hardware::{HardwareType, MemoryRange};
+use crate::image::{FirmwareImage, Segment};
+use crate::option_bytes::RdpLevel;
+use crate::policy::Policy;
+use crate::probe::{AfterFlash, ProbeConfig};
+use crate::settings::Settings;
+use crate::version::Version;
 use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex};
@@ -8,13 +18,18 @@ pub struct FlashReleaseService {
     state: Arc<Mutex<FlashReleaseState>>,
 }
 
+/// Ergebnis des Release-Abrufs im Hintergrund
+type ReleasesResult = Result<Vec<Release>, Box<dyn std::error::Error + Send + Sync>>;
+
 #[derive(Default)]
 struct FlashReleaseState {
     last_hw_type: Option<HardwareType>,
+    last_repo: Option<String>,
+    github_token: Option<String>,
     releases: Option<Arc<Vec<Release>>>,
     releases_loading: bool,
     releases_error: Option<String>,
-    releases_rx: Option<Receiver<Result<Vec<Release>, Box<dyn std::error::Error + Send + Sync>>>>,
+    releases_rx: Option<Receiver<ReleasesResult>>,
 }
 
 impl FlashReleaseService {
@@ -24,10 +39,16 @@ impl FlashReleaseService {
         }
     }
 
-    pub fn set_hw_type(&self, hw_type: Option<HardwareType>) {
+    pub fn set_hw_type(&self, hw_type: Option<HardwareType>, settings: &Settings) {
+        let repo = hw_type.map(|hw| settings.firmware_source(hw));
         let mut state = self.state.lock().unwrap();
-        if state.last_hw_type != hw_type {
+        if state.last_hw_type != hw_type
+            || state.last_repo != repo
+            || state.github_token != settings.github_token
+        {
             state.last_hw_type = hw_type;
+            state.last_repo = repo;
+            state.github_token = settings.github_token.clone();
             state.releases = None;
             state.releases_error = None;
             state.releases_loading = false;
@@ -37,19 +58,20 @@ impl FlashReleaseService {
 
     pub fn poll(&self) {
         let mut state = self.state.lock().unwrap();
-        if let Some(hw_type) = state.last_hw_type {
-            if state.releases.is_none() && state.releases_rx.is_none() && !state.releases_loading {
-                let repo = hw_type.repo();
-                state.releases_loading = true;
-                state.releases_error = None;
-                let (tx, rx) = mpsc::channel();
-                let repo = repo.to_string();
-                std::thread::spawn(move || {
-                    let result = crate::flash::fetch_releases(&repo);
-                    let _ = tx.send(result);
-                });
-                state.releases_rx = Some(rx);
-            }
+        if let (Some(hw_type), Some(repo)) = (state.last_hw_type, state.last_repo.clone())
+            && state.releases.is_none()
+            && state.releases_rx.is_none()
+            && !state.releases_loading
+        {
+            state.releases_loading = true;
+            state.releases_error = None;
+            let (tx, rx) = mpsc::channel();
+            let token = state.github_token.clone();
+            std::thread::spawn(move || {
+                let result = crate::flash::fetch_releases(&repo, token.as_deref(), hw_type);
+                let _ = tx.send(result);
+            });
+            state.releases_rx = Some(rx);
         }
 
         if let Some(rx) = &state.releases_rx {
@@ -81,10 +103,23 @@ impl FlashReleaseService {
         )
     }
 }
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// GitHub-Client, optional mit persönlichem Zugriffstoken
+fn github_client(
+    token: Option<&str>,
+) -> Result<octocrab::Octocrab, Box<dyn std::error::Error + Send + Sync>> {
+    match token {
+        Some(token) if !token.is_empty() => Ok(octocrab::Octocrab::builder()
+            .personal_token(token.to_string())
+            .build()?),
+        _ => Ok(octocrab::Octocrab::default()),
+    }
+}
 
-// Asset von GitHub herunterladen und temporär speichern, mit Fortschritt für GUI
+// Asset von GitHub herunterladen und im Cache speichern, mit Fortschritt für GUI
 pub fn download_github_asset_progress_gui<F>(
+    settings: &Settings,
     repo: &str,
     tag: &str,
     asset_name: &str,
@@ -93,20 +128,40 @@ pub fn download_github_asset_progress_gui<F>(
 where
     F: FnMut(usize) + Send + 'static,
 {
-    use std::fs::File;
     use std::io::Write;
-    use tempfile::tempdir;
+    // Importiertes Offline-Paket statt GitHub
+    if let Some(dir) = crate::bundle::source_dir(repo) {
+        let path = crate::bundle::asset_path(&dir, tag, asset_name)?;
+        progress_cb(100);
+        return Ok(path);
+    }
     let parts: Vec<&str> = repo.split('/').collect();
     if parts.len() != 2 {
         return Err("Ungültiges Repository-Format".into());
     }
     let owner = parts[0];
     let repo_name = parts[1];
+
+    // Bereits heruntergeladene Assets aus dem Cache verwenden
+    let cache_dir = settings
+        .cache_dir()
+        .join("firmware")
+        .join(owner)
+        .join(repo_name)
+        .join(tag);
+    let final_path = cache_dir.join(asset_name);
+    if final_path.exists() {
+        progress_cb(100);
+        return Ok(final_path);
+    }
+    std::fs::create_dir_all(&cache_dir)?;
+
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?;
+    let token = settings.github_token.clone();
     let asset_url = rt.block_on(async {
-        let octocrab = octocrab::Octocrab::default();
+        let octocrab = github_client(token.as_deref())?;
         let release = octocrab
             .repos(owner, repo_name)
             .releases()
@@ -120,17 +175,22 @@ where
         Ok::<_, Box<dyn std::error::Error + Send + Sync>>(asset.browser_download_url.clone())
     })?;
     // Asset herunterladen mit Fortschritt
-    let response = rt.block_on(async { reqwest::get(asset_url).await })?;
+    let response = rt.block_on(async {
+        let mut request = reqwest::Client::new().get(asset_url);
+        if let Some(token) = token.as_deref().filter(|t| !t.is_empty()) {
+            request = request.bearer_auth(token);
+        }
+        request.send().await?.error_for_status()
+    })?;
     let total = response.content_length().unwrap_or(0);
     let mut downloaded = 0u64;
-    let dir = tempdir()?;
-    let file_path = dir.path().join(asset_name);
-    let mut file = File::create(&file_path)?;
+    // Erst in eine temporäre Datei schreiben, damit abgebrochene Downloads nicht im Cache landen
+    let mut tmp = tempfile::NamedTempFile::new_in(&cache_dir)?;
     let mut stream = response;
     loop {
         let chunk = rt.block_on(async { stream.chunk().await })?;
         if let Some(chunk) = chunk {
-            file.write_all(&chunk)?;
+            tmp.write_all(&chunk)?;
             downloaded += chunk.len() as u64;
             let percent = if total > 0 {
                 (downloaded as f32 / total as f32 * 100.0) as usize
@@ -142,23 +202,69 @@ where
             break;
         }
     }
-    // tempdir lebt nur solange wie das TempDir-Objekt, daher Pfad kopieren
-    let final_path = std::env::temp_dir().join(asset_name);
-    std::fs::copy(&file_path, &final_path)?;
+    tmp.persist(&final_path)?;
+    prune_cache(
+        &settings.cache_dir().join("firmware"),
+        settings.cache_size_mb * 1024 * 1024,
+        &final_path,
+    );
     Ok(final_path)
 }
 
+/// Löscht die ältesten Dateien im Cache, bis die maximale Größe eingehalten wird
+fn prune_cache(dir: &Path, max_bytes: u64, keep: &Path) {
+    fn collect(dir: &Path, files: &mut Vec<(PathBuf, u64, std::time::SystemTime)>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.is_dir() {
+                collect(&path, files);
+            } else {
+                let modified = meta.modified().unwrap_or(std::time::UNIX_EPOCH);
+                files.push((path, meta.len(), modified));
+            }
+        }
+    }
+    let mut files = Vec::new();
+    collect(dir, &mut files);
+    let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+    files.sort_by_key(|(_, _, modified)| *modified);
+    for (path, len, _) in files {
+        if total <= max_bytes {
+            break;
+        }
+        if path == keep {
+            continue;
+        }
+        if std::fs::remove_file(&path).is_ok() {
+            total -= len;
+        }
+    }
+}
+
 pub fn fetch_releases(
     repo: &str,
+    github_token: Option<&str>,
+    hw_type: HardwareType,
 ) -> Result<Vec<Release>, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(dir) = crate::bundle::source_dir(repo) {
+        return Ok(crate::bundle::releases(&dir, hw_type)?);
+    }
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()?;
-    rt.block_on(async_fetch_releases(repo))
+    rt.block_on(async_fetch_releases(repo, github_token, hw_type))
 }
 
 async fn async_fetch_releases(
     repo: &str,
+    github_token: Option<&str>,
+    hw_type: HardwareType,
 ) -> Result<Vec<Release>, Box<dyn std::error::Error + Send + Sync>> {
     let parts: Vec<&str> = repo.split('/').collect();
     if parts.len() != 2 {
@@ -167,7 +273,7 @@ async fn async_fetch_releases(
     let owner = parts[0];
     let repo_name = parts[1];
 
-    let octocrab = octocrab::Octocrab::default();
+    let octocrab = github_client(github_token)?;
     let response = octocrab
         .repos(owner, repo_name)
         .releases()
@@ -176,25 +282,21 @@ async fn async_fetch_releases(
         .send()
         .await?;
 
+    let model = hw_type.model();
     let mut filtered_releases = Vec::new();
     for r in response.items {
         let stm32_assets: Vec<String> = r
             .assets
             .iter()
-            .filter_map(|a| {
-                let name = &a.name;
-                if name.ends_with(".bin") || name.ends_with(".hex") || name.ends_with(".dfu") {
-                    Some(name.clone())
-                } else {
-                    None
-                }
-            })
+            .filter(|a| model.accepts_asset(&a.name))
+            .map(|a| a.name.clone())
             .collect();
         if !stm32_assets.is_empty() {
             filtered_releases.push(Release {
                 tag_name: r.tag_name,
                 prerelease: r.prerelease,
                 stm32_assets,
+                notes: r.body.filter(|b| !b.trim().is_empty()),
             });
         }
     }
@@ -206,61 +308,390 @@ pub struct Release {
     pub tag_name: String,
     pub prerelease: bool,
     pub stm32_assets: Vec<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
 }
 
 // Es gibt nur noch einen Flash-Weg: probe-rs
 // Die Funktion flash_with_probe_rs bleibt erhalten
 
+/// Firmware-Datei und ihre Zieladresse im Flash
+#[derive(Debug, Clone)]
+pub struct ImageFile {
+    pub path: String,
+    pub base: u64,
+}
+
+impl ImageFile {
+    /// Zieladresse anhand des Dateinamens und der Bereiche im Hardware-Katalog
+    pub fn for_model(path: String, hw_type: HardwareType) -> Self {
+        let name = Path::new(&path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let base = hw_type.model().image_address(&name);
+        Self { path, base }
+    }
+}
+
 // Modul für Flash-Logik und Datenabruf
 pub struct FlashConfig {
-    // Beispiel: Pfad zur Firmware-Datei
-    pub firmware_path: String,
-    // Weitere Konfigurationsoptionen hier
+    /// Firmware-Dateien, die in einer Session geflasht werden
+    pub images: Vec<ImageFile>,
+    pub hw_type: HardwareType,
+    pub probe: ProbeConfig,
+    /// Flashen auch dann, wenn das erkannte Modell nicht zum gewählten passt
+    pub allow_model_mismatch: bool,
+    /// Zielverzeichnis für das Backup vor dem Flashen, `None` = kein Backup
+    pub backup_dir: Option<PathBuf>,
+    /// Image darf geschützte Bereiche überschreiben
+    pub allow_protected_overwrite: bool,
+    /// Readout-Protection direkt nach erfolgreichem Flashen, `None` = unverändert
+    /// lassen. Produktionsabläufe setzen sie selbst erst als letzten Schritt.
+    pub final_rdp: Option<RdpLevel>,
+    pub after_flash: AfterFlash,
+    /// Release-Tag der Firmware für das Audit-Log
+    pub firmware_tag: Option<String>,
+    /// Downgrade bzw. erneutes Flashen derselben Version wurde bestätigt
+    pub allow_version_change: bool,
+    /// Einstellungen für Audit-Log und Datenverzeichnis
+    pub settings: Settings,
+}
+
+impl FlashConfig {
+    pub fn new(paths: &[String], hw_type: HardwareType, settings: &Settings) -> Self {
+        Self {
+            images: paths
+                .iter()
+                .map(|path| ImageFile::for_model(path.clone(), hw_type))
+                .collect(),
+            hw_type,
+            probe: ProbeConfig::from_settings(settings),
+            allow_model_mismatch: false,
+            backup_dir: settings.backup_before_flash.then(|| settings.backup_dir()),
+            allow_protected_overwrite: false,
+            final_rdp: None,
+            after_flash: settings.after_flash,
+            firmware_tag: None,
+            allow_version_change: false,
+            settings: settings.clone(),
+        }
+    }
+
+    /// Firmware-Dateien mit Prüfsumme für das Audit-Log
+    pub fn firmware_refs(&self) -> Vec<FirmwareRef> {
+        self.images
+            .iter()
+            .map(|file| FirmwareRef::from_file(Path::new(&file.path), self.firmware_tag.as_deref()))
+            .collect()
+    }
+}
+
+/// Asset-Namen der heruntergeladenen Dateien; der Cache behält die Namen bei
+pub fn asset_names(paths: &[String]) -> Vec<String> {
+    paths
+        .iter()
+        .filter_map(|path| Path::new(path).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Warnung bei Downgrade oder erneutem Flashen der installierten Version;
+/// beides muss bestätigt werden
+pub fn version_warning(identity: &DeviceIdentity, tag: &str) -> Option<String> {
+    let installed = identity.firmware.as_ref()?.version.as_ref()?;
+    let selected = Version::parse(tag)?;
+    match selected.cmp(installed) {
+        std::cmp::Ordering::Less => {
+            Some(format!("Downgrade from v{} to v{}!", installed, selected))
+        }
+        std::cmp::Ordering::Equal => Some(format!("Version v{} is already installed.", installed)),
+        std::cmp::Ordering::Greater => None,
+    }
+}
+
+/// RDP-Stufe laut Katalog, falls das Modell Option-Bytes unterstützt
+pub fn production_rdp(hw_type: HardwareType) -> Option<RdpLevel> {
+    let model = hw_type.model();
+    (model.option_bytes.is_some() && model.production_rdp > RdpLevel::Level0)
+        .then_some(model.production_rdp)
 }
 
 pub struct FlashResult {
     pub success: bool,
     pub message: String,
+    /// Probe- oder Flash-Fehler, keine abgelehnte Prüfung oder Bestätigung
+    pub hardware_error: bool,
 }
 
 /// Führt den Flash-Vorgang aus
 pub fn flash_hardware(config: &FlashConfig) -> FlashResult {
-    let msg = flash_with_probe_rs(&config.firmware_path);
-    let success = msg.contains("erfolgreich");
+    crate::oplog::record(format!(
+        "Flashen gestartet: {} ({} Datei(en))",
+        config.hw_type,
+        config.images.len()
+    ));
+    let result = flash_audited(config);
+    let msg = match &result {
+        Ok(msg) => msg.clone(),
+        Err(e) => format!("Fehler beim Flashen mit probe-rs: {:#}", e),
+    };
+    crate::oplog::record(msg.clone());
     FlashResult {
-        success,
+        success: result.is_ok(),
         message: msg,
+        hardware_error: result
+            .as_ref()
+            .is_err_and(crate::diagnostics::is_hardware_error),
     }
 }
 
-/// Flash-Vorgang mit probe-rs
-pub fn flash_with_probe_rs(firmware_path: &str) -> String {
-    use probe_rs::flashing::DownloadOptions;
-    use probe_rs::{Session, SessionConfig, config::TargetSelector};
-    use std::fs;
-    match (|| -> anyhow::Result<String> {
-        let firmware = fs::read(firmware_path)?;
-        let mut session = Session::auto_attach(TargetSelector::Auto, SessionConfig::default())?;
-        let mut loader = session.target().flash_loader();
-        loader.add_data(0x0800_0000, &firmware)?;
-        loader.commit(&mut session, DownloadOptions::default())?;
-        Ok("Flashen mit probe-rs erfolgreich!".to_string())
-    })() {
-        Ok(msg) => msg,
-        Err(e) => format!("Fehler beim Flashen mit probe-rs: {}", e),
+/// Flashen mit Eintrag im Audit-Log
+pub fn flash_audited(config: &FlashConfig) -> anyhow::Result<String> {
+    let mut record = AuditRecord::new(Action::Flash, Some(config.hw_type));
+    record.firmware = config.firmware_refs();
+    crate::audit::run(&config.settings, record, |record| {
+        let msg = flash_with_probe_rs(config, record)?;
+        record.details = msg.clone();
+        Ok(msg)
+    })
+}
+
+/// Inhalt eines geschützten Bereichs vor dem Flashen
+struct PreservedRegion {
+    name: String,
+    range: MemoryRange,
+    data: Vec<u8>,
+}
+
+/// Liest alle geschützten Bereiche, die die Images nicht selbst beschreiben.
+/// Überschneidungen sind nur mit `allow_overwrite` erlaubt.
+fn preserve_protected_regions(
+    backend: &mut dyn ProgrammerBackend,
+    hw_type: HardwareType,
+    images: &[MemoryRange],
+    allow_overwrite: bool,
+) -> anyhow::Result<Vec<PreservedRegion>> {
+    let mut preserved = Vec::new();
+    for region in hw_type.model().protected_regions() {
+        if let Some(image) = images.iter().find(|image| region.range.overlaps(image)) {
+            if !allow_overwrite {
+                anyhow::bail!(
+                    "Firmware-Image {} überschneidet den geschützten Bereich {}",
+                    image,
+                    region
+                );
+            }
+            continue;
+        }
+        let mut data = vec![0u8; region.range.size as usize];
+        backend.read(region.range.base, &mut data)?;
+        preserved.push(PreservedRegion {
+            name: region.name.clone(),
+            range: region.range,
+            data,
+        });
+    }
+    Ok(preserved)
+}
+
+/// Prüft nach dem Flashen, ob die geschützten Bereiche unverändert sind
+fn validate_preserved_regions(
+    backend: &mut dyn ProgrammerBackend,
+    preserved: &[PreservedRegion],
+) -> anyhow::Result<()> {
+    for region in preserved {
+        let mut data = vec![0u8; region.range.size as usize];
+        backend.read(region.range.base, &mut data)?;
+        if data != region.data {
+            anyhow::bail!(
+                "Geschützter Bereich {} wurde beim Flashen verändert",
+                region.name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Zu schreibende Daten: die Images und die geschützten Bereiche, deren Sektor
+/// für ein Image gelöscht wird. Alle anderen geschützten Bereiche bleiben
+/// unberührt.
+fn write_plan<'a>(
+    hw_type: HardwareType,
+    segments: &'a [Segment],
+    preserved: &'a [PreservedRegion],
+) -> Vec<(u64, &'a [u8])> {
+    let model = hw_type.model();
+    let erased: Vec<MemoryRange> = segments
+        .iter()
+        .flat_map(|s| model.sectors(s.range()))
+        .collect();
+    segments
+        .iter()
+        .map(|s| (s.address, s.data.as_slice()))
+        .chain(
+            preserved
+                .iter()
+                .filter(|r| erased.iter().any(|sector| sector.overlaps(&r.range)))
+                .map(|r| (r.range.base, r.data.as_slice())),
+        )
+        .collect()
+}
+
+/// Programmiert die Daten. Schlägt das fehl, wird neu verbunden und laut
+/// `RetryPolicy` wiederholt, auf Wunsch mit niedrigerer SWD-Geschwindigkeit.
+fn program_with_retry(
+    mut backend: Box<dyn ProgrammerBackend>,
+    config: &FlashConfig,
+    data: &[(u64, &[u8])],
+) -> anyhow::Result<Box<dyn ProgrammerBackend>> {
+    let retry = config.probe.retry;
+    let attempts = retry.flash_attempts.max(1);
+    let mut attempt = 0;
+    loop {
+        let Err(e) = backend.flash(data) else {
+            if attempt > 0 {
+                crate::oplog::record(format!(
+                    "Programmieren im Versuch {}/{} erfolgreich",
+                    attempt + 1,
+                    attempts
+                ));
+            }
+            return Ok(backend);
+        };
+        crate::oplog::record(format!(
+            "Programmierversuch {}/{} fehlgeschlagen: {:#}",
+            attempt + 1,
+            attempts,
+            e
+        ));
+        attempt += 1;
+        if attempt >= attempts {
+            return Err(e);
+        }
+        // Neu verbinden, die alte Verbindung hält die Probe belegt
+        drop(backend);
+        let probe = ProbeConfig {
+            swd_speed_khz: retry.speed(attempt, config.probe.swd_speed_khz),
+            ..config.probe.clone()
+        };
+        backend = crate::probe::attach(&probe, Some(&config.hw_type.model().target))?;
+    }
+}
+
+/// Verbindet mit dem Gerät und prüft, ob es zum gewählten Hardware-Typ passt
+pub fn open_session(
+    config: &FlashConfig,
+) -> anyhow::Result<(Box<dyn ProgrammerBackend>, crate::device::DeviceIdentity)> {
+    let model = config.hw_type.model();
+    let mut backend = crate::probe::attach(&config.probe, Some(&model.target))?;
+    let identity = crate::device::identify(backend.as_mut())?;
+    if identity.contradicts(config.hw_type) && !config.allow_model_mismatch {
+        return Err(Unrelated(format!(
+            "Angeschlossenes Gerät passt nicht zu {}: {}",
+            config.hw_type,
+            identity.summary()
+        ))
+        .into());
     }
+    Ok((backend, identity))
 }
 
-/// Ruft die benötigten Daten für das Flashen ab
-pub fn fetch_flash_data() -> Result<String, String> {
-    // TODO: Implementierung des Datenabrufs
-    Ok("Daten abgerufen (Platzhalter)".to_string())
+/// Prüft die Firmware gegen die Freigabeliste. Die Liste wird bei jedem
+/// Flashen neu gelesen, damit eine Sperre sofort greift, auch mitten in einer Serie.
+fn check_policy(config: &FlashConfig) -> anyhow::Result<()> {
+    let policy = Policy::load(&config.settings);
+    let paths: Vec<String> = config.images.iter().map(|i| i.path.clone()).collect();
+    let result = match config.firmware_tag.as_deref() {
+        Some(tag) => policy.check_assets(config.hw_type, tag, &asset_names(&paths)),
+        None if policy.is_active() => Err(anyhow::anyhow!(
+            "Firmware ohne Release-Tag ist mit Freigabeliste nicht erlaubt"
+        )),
+        None => Ok(()),
+    };
+    result.map_err(|e| Unrelated(format!("{:#}", e)).into())
+}
+
+/// Flash-Vorgang mit probe-rs
+pub fn flash_with_probe_rs(
+    config: &FlashConfig,
+    record: &mut AuditRecord,
+) -> anyhow::Result<String> {
+    check_policy(config)?;
+    let images = config
+        .images
+        .iter()
+        .map(|file| FirmwareImage::load(Path::new(&file.path), file.base))
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(|e| Unrelated(format!("{:#}", e)))?;
+    if images.is_empty() {
+        return Err(Unrelated("Keine Firmware ausgewählt".to_string()).into());
+    }
+    let findings =
+        crate::sanity::check_images(&images, config.hw_type, config.allow_protected_overwrite);
+    if let Some(errors) = crate::sanity::error_summary(&findings) {
+        return Err(Unrelated(format!("Firmware-Prüfung fehlgeschlagen: {}", errors)).into());
+    }
+    let elf = images.iter().find(|i| i.is_elf).cloned();
+    let segments: Vec<_> = images.into_iter().flat_map(|i| i.segments).collect();
+    let (mut backend, identity) = open_session(config)?;
+    record.device(backend.as_ref(), &identity);
+    // Installierte Version erst hier lesen, die Anzeige kann veraltet sein
+    if !config.allow_version_change
+        && let Some(warning) = config
+            .firmware_tag
+            .as_deref()
+            .and_then(|tag| version_warning(&identity, tag))
+    {
+        return Err(Unrelated(format!(
+            "Nicht bestätigt: {} Das Gerät wurde nicht geflasht.",
+            warning
+        ))
+        .into());
+    }
+    record.serial =
+        crate::parameters::read(backend.as_mut(), config.hw_type, "serial", elf.as_ref())
+            .ok()
+            .flatten()
+            .map(|serial| serial.to_string());
+    let backup = match &config.backup_dir {
+        Some(dir) => {
+            let backup = crate::backup::create(backend.as_mut(), config.hw_type, &identity, dir)?;
+            crate::backup::prune(
+                dir,
+                config.settings.cache_size_mb * 1024 * 1024,
+                &backup.dir,
+            );
+            Some(backup)
+        }
+        None => None,
+    };
+    let ranges: Vec<MemoryRange> = segments.iter().map(|s| s.range()).collect();
+    let preserved = preserve_protected_regions(
+        backend.as_mut(),
+        config.hw_type,
+        &ranges,
+        config.allow_protected_overwrite,
+    )?;
+    let data = write_plan(config.hw_type, &segments, &preserved);
+    let mut backend = program_with_retry(backend, config, &data)?;
+    validate_preserved_regions(backend.as_mut(), &preserved)?;
+    let mut msg = "Flashen mit probe-rs erfolgreich!".to_string();
+    if let Some(backup) = backup {
+        msg.push_str(&format!(" Backup: {}", backup.dir.display()));
+    }
+    if let (Some(level), Some(kind)) = (config.final_rdp, config.hw_type.model().option_bytes) {
+        crate::option_bytes::set_rdp(backend.as_mut(), kind, level)?;
+        msg.push_str(&format!(" RDP: {} (wirksam nach Power-Cycle)", level));
+    }
+    crate::probe::after_flash(backend.as_mut(), config.after_flash)?;
+    Ok(msg)
 }
-use std::sync::mpsc::Sender;
 
 pub enum DownloadMsg {
     Progress(usize),
-    Done(String),
+    /// Pfade aller heruntergeladenen Assets
+    Done(Vec<String>),
     Error(String),
 }
 
@@ -269,27 +700,174 @@ pub struct FirmwareDownloadHandle {
 }
 
 impl FirmwareDownloadHandle {
-    pub fn start(repo: String, tag: String, asset: String) -> Self {
+    pub fn start(settings: Settings, repo: String, tag: String, assets: Vec<String>) -> Self {
         let (tx, rx) = std::sync::mpsc::channel();
         std::thread::spawn(move || {
-            let tx_progress = tx.clone();
-            let res = crate::flash::download_github_asset_progress_gui(
-                &repo,
-                &tag,
-                &asset,
-                move |percent| {
-                    let _ = tx_progress.send(DownloadMsg::Progress(percent));
-                },
-            );
-            match res {
-                Ok(path) => {
-                    let _ = tx.send(DownloadMsg::Done(path.display().to_string()));
-                }
-                Err(e) => {
-                    let _ = tx.send(DownloadMsg::Error(format!("Fehler: {}", e)));
+            let count = assets.len().max(1);
+            let mut paths = Vec::new();
+            for (i, asset) in assets.iter().enumerate() {
+                let tx_progress = tx.clone();
+                let res = crate::flash::download_github_asset_progress_gui(
+                    &settings,
+                    &repo,
+                    &tag,
+                    asset,
+                    move |percent| {
+                        let total = (i * 100 + percent) / count;
+                        let _ = tx_progress.send(DownloadMsg::Progress(total));
+                    },
+                );
+                let mut record = AuditRecord::new(Action::Download, None);
+                match res {
+                    Ok(path) => {
+                        record.firmware = vec![FirmwareRef::from_file(&path, Some(&tag))];
+                        record.success = true;
+                        record.details = repo.clone();
+                        crate::audit::append_logged(&settings, &record);
+                        paths.push(path.display().to_string());
+                    }
+                    Err(e) => {
+                        record.firmware = vec![FirmwareRef {
+                            tag: Some(tag.clone()),
+                            asset: asset.clone(),
+                            sha256: String::new(),
+                        }];
+                        record.details = format!("{}: {}", repo, e);
+                        crate::audit::append_logged(&settings, &record);
+                        let _ = tx.send(DownloadMsg::Error(format!("Fehler: {}", e)));
+                        return;
+                    }
                 }
             }
+            let _ = tx.send(DownloadMsg::Done(paths));
         });
         FirmwareDownloadHandle { rx }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parameters::ParameterValue;
+    use crate::testing::read;
+
+    fn serial(backend: &mut dyn ProgrammerBackend, hw_type: HardwareType) -> Option<String> {
+        crate::parameters::read(backend, hw_type, "serial", None)
+            .unwrap()
+            .map(|v| v.to_string())
+    }
+
+    #[test]
+    fn flash_keeps_protected_regions() {
+        let device = crate::testing::device();
+        let hw_type = device.hw_type;
+        let mut backend = device.connect();
+        let value = ParameterValue::String("SN-4711".to_string());
+        crate::parameters::write(backend.as_mut(), hw_type, "serial", &value, None).unwrap();
+        let calibration = device.region("calibration");
+        backend.flash(&[(calibration.base, &[0x42; 64])]).unwrap();
+        let before = read(backend.as_mut(), calibration).unwrap();
+        drop(backend);
+
+        let image = device.application_image();
+        flash_audited(&device.flash_config(std::slice::from_ref(&image))).unwrap();
+
+        let mut backend = device.connect();
+        assert_eq!(
+            serial(backend.as_mut(), hw_type).as_deref(),
+            Some("SN-4711")
+        );
+        assert_eq!(read(backend.as_mut(), calibration).unwrap(), before);
+        let application = device.region("application");
+        let written = std::fs::read(image).unwrap();
+        let range = MemoryRange {
+            base: application.base,
+            size: written.len() as u64,
+        };
+        assert_eq!(read(backend.as_mut(), range).unwrap(), written);
+    }
+
+    #[test]
+    fn flashing_leaves_readout_protection_alone() {
+        let device = crate::testing::device();
+        assert!(production_rdp(device.hw_type).is_some());
+        let config = device.flash_config(&[device.application_image()]);
+        flash_audited(&config).unwrap();
+        let kind = device.hw_type.model().option_bytes.unwrap();
+        let options = crate::option_bytes::read(device.connect().as_mut(), kind).unwrap();
+        assert_eq!(options.rdp(), RdpLevel::Level0);
+    }
+
+    #[test]
+    fn downgrade_needs_confirmation_at_flash_time() {
+        let device = crate::testing::device();
+        let application = device.region("application");
+        let before = read(device.connect().as_mut(), application).unwrap();
+        let mut config = device.flash_config(&[device.application_image()]);
+        config.firmware_tag = Some("v0.9.0".to_string());
+        assert!(flash_audited(&config).is_err());
+        assert_eq!(
+            read(device.connect().as_mut(), application).unwrap(),
+            before
+        );
+
+        config.allow_version_change = true;
+        flash_audited(&config).unwrap();
+        assert_ne!(
+            read(device.connect().as_mut(), application).unwrap(),
+            before
+        );
+    }
+
+    #[test]
+    fn flashing_checks_the_policy() {
+        let mut device = crate::testing::device();
+        let policy = device.dir.path().join("policy.toml");
+        std::fs::write(
+            &policy,
+            "[[allow]]\nhw_type = \"irock-424\"\ntags = [\"v1.*\"]\n\n\
+             [[block]]\ntag = \"v1.1.0\"\nreason = \"Fehlerhaft\"\n",
+        )
+        .unwrap();
+        device.settings.firmware_policy = Some(policy);
+        let mut config = device.flash_config(&[device.application_image()]);
+        for tag in [None, Some("v1.1.0"), Some("v2.0.0")] {
+            config.firmware_tag = tag.map(str::to_string);
+            let result = flash_hardware(&config);
+            assert!(!result.success, "{:?}", tag);
+            assert!(!result.hardware_error);
+        }
+        config.firmware_tag = Some("v1.2.0".to_string());
+        assert!(flash_hardware(&config).success);
+    }
+
+    #[test]
+    fn write_plan_leaves_protected_sectors_alone() {
+        let hw_type = HardwareType::from_id("irock-424").unwrap();
+        let model = hw_type.model();
+        let preserved: Vec<PreservedRegion> = model
+            .protected_regions()
+            .map(|region| PreservedRegion {
+                name: region.name.clone(),
+                range: region.range,
+                data: vec![0x42; region.range.size as usize],
+            })
+            .collect();
+        assert!(!preserved.is_empty());
+        let segments = [Segment {
+            address: model.application().base,
+            data: vec![0xA5; 0x800],
+        }];
+        let plan = write_plan(hw_type, &segments, &preserved);
+        assert_eq!(plan.len(), 1);
+        for (address, data) in plan {
+            let range = MemoryRange {
+                base: address,
+                size: data.len() as u64,
+            };
+            for sector in model.sectors(range) {
+                assert!(preserved.iter().all(|r| !sector.overlaps(&r.range)));
+            }
+        }
+    }
+}
@@ -1,42 +1,502 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum HardwareType {
-    IRock424,
-    IRock212,
-    IRock200,
-    IRock300,
-    IRock400,
+use crate::image::FirmwareImage;
+use crate::option_bytes::{OptionBytesKind, RdpLevel};
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Mitgelieferter Hardware-Katalog
+const BUNDLED_CATALOGUE: &str = include_str!("hardware.toml");
+
+/// Dateiname des benutzerdefinierten Katalogs im Konfigurationsverzeichnis
+pub const USER_CATALOGUE_FILE: &str = "hardware.toml";
+
+static CATALOGUE: OnceLock<Catalogue> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct MemoryRange {
+    pub base: u64,
+    pub size: u64,
 }
 
-impl HardwareType {
-    pub fn repo(&self) -> &'static str {
-        match self {
-            HardwareType::IRock424 => "Arvernus/iRock-424",
-            HardwareType::IRock212 => "Arvernus/iRock-212",
-            HardwareType::IRock200 | HardwareType::IRock300 | HardwareType::IRock400 => {
-                "Arvernus/iRock-200-300-400"
+impl MemoryRange {
+    pub fn end(&self) -> u64 {
+        self.base.saturating_add(self.size)
+    }
+
+    /// `true`, wenn der Bereich nicht leer ist und vollständig in `outer` liegt
+    pub fn within(&self, outer: &MemoryRange) -> bool {
+        self.size > 0
+            && self.base >= outer.base
+            && self
+                .base
+                .checked_add(self.size)
+                .is_some_and(|end| end <= outer.end())
+    }
+
+    pub fn contains(&self, address: u64) -> bool {
+        address >= self.base && address < self.end()
+    }
+
+    pub fn overlaps(&self, other: &MemoryRange) -> bool {
+        self.base < other.end() && other.base < self.end()
+    }
+}
+
+impl std::fmt::Display for MemoryRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "0x{:08X}..0x{:08X} ({} KiB)",
+            self.base,
+            self.end(),
+            self.size / 1024
+        )
+    }
+}
+
+/// Benannter Bereich im Flash, z.B. Bootloader oder Kalibrierdaten
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlashRegion {
+    pub name: String,
+    #[serde(flatten)]
+    pub range: MemoryRange,
+    /// Geschützte Bereiche bleiben beim Flashen erhalten
+    #[serde(default)]
+    pub protected: bool,
+    /// Asset-Namen (Platzhalter `*` und `?`), die in diesen Bereich geflasht werden
+    #[serde(default)]
+    pub images: Vec<String>,
+}
+
+impl std::fmt::Display for FlashRegion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.range)?;
+        if self.protected {
+            write!(f, " [protected]")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParameterKind {
+    U32,
+    String,
+}
+
+/// Geräteparameter im Flash, z.B. Seriennummer oder Kapazität
+#[derive(Debug, Clone, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    pub address: u64,
+    pub kind: ParameterKind,
+    /// Länge in Bytes (nur für Strings relevant)
+    #[serde(default = "default_parameter_length")]
+    pub length: usize,
+    /// Symbolname in der ELF-Datei; hat Vorrang vor `address`, wenn vorhanden
+    #[serde(default)]
+    pub symbol: Option<String>,
+}
+
+impl Parameter {
+    /// Adresse und Länge, bei ELF-Firmware über den Symbolnamen aufgelöst
+    pub fn resolve(&self, image: Option<&FirmwareImage>) -> (u64, usize) {
+        let symbol = self
+            .symbol
+            .as_deref()
+            .zip(image)
+            .and_then(|(name, image)| image.symbol(name));
+        match symbol {
+            Some(symbol) if symbol.size > 0 => (symbol.address, symbol.size as usize),
+            Some(symbol) => (symbol.address, self.length),
+            None => (self.address, self.length),
+        }
+    }
+}
+
+fn default_parameter_length() -> usize {
+    4
+}
+
+/// Beschreibung eines iRock-Modells aus dem Katalog
+#[derive(Debug, Clone, Deserialize)]
+pub struct HardwareModel {
+    pub id: String,
+    pub name: String,
+    /// GitHub-Repository mit den Firmware-Releases
+    pub repo: String,
+    /// probe-rs Target-Name, z.B. `STM32F401RCTx`
+    pub target: String,
+    pub flash: MemoryRange,
+    /// Sektorgrößen in KiB ab Flash-Anfang, die letzte gilt bis zum Ende
+    #[serde(deserialize_with = "sector_sizes")]
+    pub sectors: Vec<u64>,
+    pub ram: MemoryRange,
+    /// Aufteilung des Flash in Bereiche
+    #[serde(default)]
+    pub regions: Vec<FlashRegion>,
+    #[serde(default)]
+    pub parameters: Vec<Parameter>,
+    /// Device-ID des Mikrocontrollers (DEV_ID, untere 12 Bit von DBGMCU_IDCODE)
+    #[serde(default)]
+    pub device_id: Option<u16>,
+    #[serde(default = "default_idcode_address")]
+    pub idcode_address: u64,
+    /// Adresse der 96-Bit-Unique-ID des Mikrocontrollers
+    #[serde(default = "default_unique_id_address")]
+    pub unique_id_address: u64,
+    /// Adresse des Info-Blocks der Firmware
+    #[serde(default)]
+    pub info_block: Option<u64>,
+    /// Board-Kennung im Info-Block, unterscheidet Modelle mit gleichem Mikrocontroller
+    #[serde(default)]
+    pub board_id: Option<u32>,
+    /// Erlaubte Asset-Namen (Platzhalter `*` und `?`), bevorzugtes Format zuerst
+    #[serde(default = "default_firmware_patterns")]
+    pub firmware_patterns: Vec<String>,
+    /// Option-Byte-Layout, `None` = keine Verwaltung der Readout-Protection
+    #[serde(default)]
+    pub option_bytes: Option<OptionBytesKind>,
+    /// RDP-Stufe, mit der Produktionsgeräte nach dem Flashen ausgeliefert werden
+    #[serde(default)]
+    pub production_rdp: RdpLevel,
+}
+
+fn sector_sizes<'de, D>(deserializer: D) -> Result<Vec<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let sizes = Vec::<u64>::deserialize(deserializer)?;
+    if sizes.is_empty() || sizes.contains(&0) {
+        return Err(serde::de::Error::custom(
+            "sectors braucht mindestens eine Größe größer 0",
+        ));
+    }
+    Ok(sizes)
+}
+
+fn default_idcode_address() -> u64 {
+    // DBGMCU_IDCODE bei STM32F4
+    0xE004_2000
+}
+
+fn default_unique_id_address() -> u64 {
+    // U_ID bei STM32F4
+    0x1FFF_7A10
+}
+
+fn default_firmware_patterns() -> Vec<String> {
+    vec![
+        "*.bin".into(),
+        "*.hex".into(),
+        "*.dfu".into(),
+        "*.elf".into(),
+    ]
+}
+
+impl std::fmt::Display for Parameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ParameterKind::U32 => write!(f, "{} @ 0x{:08X} (u32)", self.name, self.address),
+            ParameterKind::String => write!(
+                f,
+                "{} @ 0x{:08X} (string, {} bytes)",
+                self.name, self.address, self.length
+            ),
+        }
+    }
+}
+
+impl HardwareModel {
+    pub fn region(&self, name: &str) -> Option<&FlashRegion> {
+        self.regions.iter().find(|r| r.name == name)
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&Parameter> {
+        self.parameters.iter().find(|p| p.name == name)
+    }
+
+    /// Bereich der Anwendung; ohne eigenen Eintrag der gesamte Flash
+    pub fn application(&self) -> MemoryRange {
+        self.region("application")
+            .map(|r| r.range)
+            .unwrap_or(self.flash)
+    }
+
+    /// Zielbereich für ein Asset anhand der Namensmuster der Bereiche
+    pub fn image_region(&self, asset_name: &str) -> Option<&FlashRegion> {
+        self.regions.iter().find(|r| {
+            r.images
+                .iter()
+                .any(|pattern| wildcard_match(pattern, asset_name))
+        })
+    }
+
+    /// Zieladresse für ein Asset; ohne passenden Bereich ein Komplett-Image ab Flash-Anfang
+    pub fn image_address(&self, asset_name: &str) -> u64 {
+        self.image_region(asset_name)
+            .map_or(self.flash.base, |r| r.range.base)
+    }
+
+    pub fn protected_regions(&self) -> impl Iterator<Item = &FlashRegion> {
+        self.regions.iter().filter(|r| r.protected)
+    }
+
+    /// Flash-Sektoren laut `sectors`, die `range` berühren; das Flashen
+    /// löscht immer ganze Sektoren
+    pub fn sectors(&self, range: MemoryRange) -> Vec<MemoryRange> {
+        let mut sectors = Vec::new();
+        let mut base = self.flash.base;
+        let mut index = 0;
+        while base < self.flash.end() {
+            let size = self
+                .sectors
+                .get(index)
+                .or(self.sectors.last())
+                .copied()
+                .unwrap_or(1)
+                * 1024;
+            let sector = MemoryRange {
+                base,
+                size: size.min(self.flash.end() - base),
+            };
+            if sector.overlaps(&range) {
+                sectors.push(sector);
+            }
+            base += size;
+            index += 1;
+        }
+        sectors
+    }
+
+    /// Assets im bevorzugten Format: die zum ersten Muster, zu dem es Assets
+    /// gibt. Releases enthalten dieselbe Firmware oft als .bin, .hex und .elf.
+    pub fn preferred_assets(&self, assets: &[String]) -> Vec<String> {
+        self.firmware_patterns
+            .iter()
+            .map(|pattern| {
+                assets
+                    .iter()
+                    .filter(|asset| wildcard_match(pattern, asset))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .find(|matching| !matching.is_empty())
+            .unwrap_or_default()
+    }
+
+    pub fn accepts_asset(&self, asset_name: &str) -> bool {
+        self.firmware_patterns
+            .iter()
+            .any(|pattern| wildcard_match(pattern, asset_name))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CatalogueFile {
+    #[serde(default)]
+    models: Vec<HardwareModel>,
+}
+
+#[derive(Debug)]
+pub struct Catalogue {
+    models: Vec<HardwareModel>,
+    types: Vec<HardwareType>,
+}
+
+impl Catalogue {
+    fn from_models(models: Vec<HardwareModel>) -> Self {
+        let types = (0..models.len()).map(HardwareType).collect();
+        Self { models, types }
+    }
+
+    fn bundled() -> Vec<HardwareModel> {
+        toml::from_str::<CatalogueFile>(BUNDLED_CATALOGUE)
+            .expect("Mitgelieferter Hardware-Katalog ist ungültig")
+            .models
+    }
+}
+
+/// Lädt den Katalog: mitgelieferte Modelle, ergänzt bzw. ersetzt durch die
+/// Einträge aus `user_file`. Muss vor dem ersten Zugriff auf `HardwareType`
+/// aufgerufen werden, sonst wird nur der mitgelieferte Katalog verwendet.
+pub fn init(user_file: Option<&Path>) -> Result<(), String> {
+    let mut models = Catalogue::bundled();
+    let mut result = Ok(());
+    if let Some(path) = user_file.filter(|p| p.exists()) {
+        match std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|s| toml::from_str::<CatalogueFile>(&s).map_err(|e| e.to_string()))
+        {
+            Ok(file) => {
+                for model in file.models {
+                    match models.iter_mut().find(|m| m.id == model.id) {
+                        Some(existing) => *existing = model,
+                        None => models.push(model),
+                    }
+                }
+            }
+            Err(e) => {
+                result = Err(format!(
+                    "Fehler im Hardware-Katalog {}: {}",
+                    path.display(),
+                    e
+                ))
             }
         }
     }
+    let _ = CATALOGUE.set(Catalogue::from_models(models));
+    result
+}
+
+fn catalogue() -> &'static Catalogue {
+    CATALOGUE.get_or_init(|| Catalogue::from_models(Catalogue::bundled()))
+}
+
+/// Verweis auf ein Modell im Hardware-Katalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HardwareType(usize);
+
+impl HardwareType {
+    pub fn model(&self) -> &'static HardwareModel {
+        &catalogue().models[self.0]
+    }
+    /// Stabiler Bezeichner für Konfigurationsdateien und Kommandozeile
+    pub fn id(&self) -> &'static str {
+        &self.model().id
+    }
+    pub fn from_id(id: &str) -> Option<HardwareType> {
+        HardwareType::all()
+            .iter()
+            .copied()
+            .find(|hw| hw.id().eq_ignore_ascii_case(id))
+    }
+    pub fn repo(&self) -> &'static str {
+        &self.model().repo
+    }
     pub fn all() -> &'static [HardwareType] {
-        &[
-            HardwareType::IRock424,
-            HardwareType::IRock212,
-            HardwareType::IRock200,
-            HardwareType::IRock300,
-            HardwareType::IRock400,
-        ]
+        &catalogue().types
     }
 }
 
 impl std::fmt::Display for HardwareType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
-            HardwareType::IRock424 => "iRock 424",
-            HardwareType::IRock212 => "iRock 212",
-            HardwareType::IRock200 => "iRock 200",
-            HardwareType::IRock300 => "iRock 300",
-            HardwareType::IRock400 => "iRock 400",
+        write!(f, "{}", self.model().name)
+    }
+}
+
+/// Einfacher Platzhalter-Vergleich: `*` beliebig viele, `?` genau ein Zeichen
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.to_ascii_lowercase().chars().collect();
+    let t: Vec<char> = text.to_ascii_lowercase().chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLASH: MemoryRange = MemoryRange {
+        base: 0x0800_0000,
+        size: 0x10_0000,
+    };
+
+    #[test]
+    fn within_rejects_overflow_and_empty_ranges() {
+        let range = |base, size| MemoryRange { base, size };
+        assert!(range(0x0800_0000, 0x4000).within(&FLASH));
+        assert!(FLASH.within(&FLASH));
+        assert!(!range(0x0800_0000, 0).within(&FLASH));
+        assert!(!range(0x07FF_FFFF, 2).within(&FLASH));
+        assert!(!range(0x080F_FFFF, 2).within(&FLASH));
+        assert!(!range(u64::MAX, 2).within(&FLASH));
+        assert_eq!(range(u64::MAX, 2).end(), u64::MAX);
+    }
+
+    #[test]
+    fn catalogue_defines_sector_layout() {
+        let file: CatalogueFile = toml::from_str(
+            r#"
+            [[models]]
+            id = "irock-f1"
+            name = "iRock F1"
+            repo = "Arvernus/iRock-F1"
+            target = "STM32F103RBTx"
+            flash = { base = 0x0800_0000, size = 0x2_0000 }
+            sectors = [1]
+            ram = { base = 0x2000_0000, size = 0x5000 }
+            "#,
+        )
+        .unwrap();
+        let model = &file.models[0];
+        let range = MemoryRange {
+            base: 0x0800_0300,
+            size: 0x500,
         };
-        write!(f, "{}", s)
+        let sectors = model.sectors(range);
+        assert_eq!(sectors.len(), 2);
+        assert_eq!(sectors[0].base, 0x0800_0000);
+        assert_eq!(sectors[1].base, 0x0800_0400);
+        assert!(sectors.iter().all(|s| s.size == 0x400));
+        assert_eq!(model.sectors(model.flash).len(), 128);
+
+        let bundled = HardwareType::from_id("irock-424").unwrap().model();
+        let sizes: Vec<u64> = bundled
+            .sectors(bundled.flash)
+            .iter()
+            .map(|s| s.size / 1024)
+            .collect();
+        assert_eq!(
+            sizes,
+            [16, 16, 16, 16, 64, 128, 128, 128, 128, 128, 128, 128]
+        );
+
+        let empty = r#"
+            [[models]]
+            id = "x"
+            name = "x"
+            repo = "x/x"
+            target = "x"
+            flash = { base = 0, size = 0x400 }
+            sectors = []
+            ram = { base = 0, size = 0x400 }
+            "#;
+        assert!(toml::from_str::<CatalogueFile>(empty).is_err());
+    }
+
+    #[test]
+    fn preferred_assets_pick_one_format() {
+        let model = HardwareType::from_id("irock-424").unwrap().model();
+        let assets: Vec<String> = ["app.elf", "app.hex", "boot.bin", "app.bin"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let preferred = model.preferred_assets(&assets);
+        assert_eq!(preferred.len(), 2);
+        assert!(
+            model
+                .firmware_patterns
+                .iter()
+                .any(|pattern| { preferred.iter().all(|asset| wildcard_match(pattern, asset)) })
+        );
+        assert!(model.preferred_assets(&[]).is_empty());
     }
 }
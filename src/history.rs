@@ -0,0 +1,225 @@
+use crate::audit::AuditRecord;
+use crate::backend::ProgrammerBackend;
+use crate::device::DeviceIdentity;
+use crate::hardware::HardwareType;
+use crate::image::FirmwareImage;
+use crate::serial::{Ledger, LedgerEntry};
+use crate::settings::Settings;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+const SNAPSHOT_FILE: &str = "snapshots.jsonl";
+
+/// Momentaufnahme der Systemwerte eines Geräts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub time: DateTime<Local>,
+    pub hw_type: String,
+    pub serial: Option<String>,
+    pub idcode: Option<u32>,
+    /// Angezeigte Werte nach Name: Firmware, Parameter und Option-Bytes
+    pub values: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+    /// Liest alle Systemwerte über eine bestehende Verbindung. Nicht lesbare
+    /// Werte (z.B. bei aktiver Readout-Protection) werden als solche vermerkt.
+    pub fn capture(
+        backend: &mut dyn ProgrammerBackend,
+        hw_type: HardwareType,
+        identity: &DeviceIdentity,
+        image: Option<&FirmwareImage>,
+    ) -> Snapshot {
+        let model = hw_type.model();
+        let mut values = BTreeMap::new();
+        values.insert(
+            "firmware".to_string(),
+            identity
+                .firmware
+                .as_ref()
+                .map_or("none".to_string(), |f| f.summary()),
+        );
+        if let Some(board_id) = identity.board_id {
+            values.insert("board_id".to_string(), format!("0x{:08X}", board_id));
+        }
+        let mut serial = None;
+        for parameter in &model.parameters {
+            let value = match crate::parameters::read(backend, hw_type, &parameter.name, image) {
+                Ok(Some(value)) => value.to_string(),
+                Ok(None) => "(empty)".to_string(),
+                Err(e) => format!("(unreadable: {})", e),
+            };
+            if parameter.name == "serial" && !value.starts_with('(') {
+                serial = Some(value.clone());
+            }
+            values.insert(parameter.name.clone(), value);
+        }
+        if let Some(kind) = model.option_bytes {
+            match crate::option_bytes::read(backend, kind) {
+                Ok(bytes) => {
+                    for (name, value) in bytes.fields() {
+                        values.insert(format!("option_bytes.{}", name), value);
+                    }
+                }
+                Err(e) => {
+                    values.insert("option_bytes".to_string(), format!("(unreadable: {})", e));
+                }
+            }
+        }
+        Snapshot {
+            time: Local::now(),
+            hw_type: hw_type.id().to_string(),
+            serial,
+            idcode: identity.idcode,
+            values,
+        }
+    }
+
+    /// Geänderte Werte gegenüber `previous` mit dem vorherigen Wert;
+    /// `None`, wenn der Wert dort fehlte
+    pub fn changes(&self, previous: &Snapshot) -> BTreeMap<String, Option<String>> {
+        let mut changes = BTreeMap::new();
+        for (name, value) in &self.values {
+            let before = previous.values.get(name);
+            if before != Some(value) {
+                changes.insert(name.clone(), before.cloned());
+            }
+        }
+        for (name, before) in &previous.values {
+            if !self.values.contains_key(name) {
+                changes.insert(name.clone(), Some(before.clone()));
+            }
+        }
+        changes
+    }
+}
+
+pub fn path(settings: &Settings) -> anyhow::Result<PathBuf> {
+    Ok(settings.data_dir()?.join(SNAPSHOT_FILE))
+}
+
+/// Hängt eine Momentaufnahme an die Gerätedatenbank an
+pub fn append(settings: &Settings, snapshot: &Snapshot) -> anyhow::Result<()> {
+    let path = path(settings)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(snapshot)?)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Alle Momentaufnahmen, älteste zuerst
+pub fn load(settings: &Settings) -> anyhow::Result<Vec<Snapshot>> {
+    let path = path(settings)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for (i, line) in std::fs::read_to_string(&path)?.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let snapshot = serde_json::from_str(line)
+            .map_err(|e| anyhow::anyhow!("{}, Zeile {}: {}", path.display(), i + 1, e))?;
+        snapshots.push(snapshot);
+    }
+    Ok(snapshots)
+}
+
+/// Letzte gespeicherte Momentaufnahme eines Geräts
+pub fn latest(settings: &Settings, serial: &str) -> Option<Snapshot> {
+    load(settings).ok()?.into_iter().rev().find(|s| {
+        s.serial
+            .as_deref()
+            .is_some_and(|s| s.eq_ignore_ascii_case(serial))
+    })
+}
+
+/// Eintrag im Verlauf eines Geräts
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// Vorgang aus dem Audit-Log (Flashen, Parameteränderung, …)
+    Operation(AuditRecord),
+    Snapshot {
+        snapshot: Snapshot,
+        /// Änderungen gegenüber der vorherigen Momentaufnahme
+        changes: BTreeMap<String, Option<String>>,
+    },
+}
+
+impl Event {
+    pub fn time(&self) -> DateTime<Local> {
+        match self {
+            Event::Operation(record) => record.time,
+            Event::Snapshot { snapshot, .. } => snapshot.time,
+        }
+    }
+}
+
+/// Alles, was über ein Gerät bekannt ist
+#[derive(Debug, Clone)]
+pub struct DeviceHistory {
+    pub serial: String,
+    /// Eintrag im Seriennummern-Register, falls die Nummer hier vergeben wurde
+    pub issued: Option<LedgerEntry>,
+    /// Chronologisch, älteste zuerst
+    pub events: Vec<Event>,
+}
+
+impl DeviceHistory {
+    /// Hardware-Typ laut Register oder letztem Eintrag
+    pub fn hw_type(&self) -> Option<&str> {
+        self.issued
+            .as_ref()
+            .map(|e| e.hw_type.as_str())
+            .or_else(|| {
+                self.events.iter().rev().find_map(|event| match event {
+                    Event::Operation(record) => record.hw_type.as_deref(),
+                    Event::Snapshot { snapshot, .. } => Some(snapshot.hw_type.as_str()),
+                })
+            })
+    }
+}
+
+/// Sucht Vorgänge und Momentaufnahmen zu einer Seriennummer
+pub fn lookup(settings: &Settings, serial: &str) -> anyhow::Result<DeviceHistory> {
+    let serial = serial.trim();
+    let matches = |s: Option<&str>| s.is_some_and(|s| s.eq_ignore_ascii_case(serial));
+    let mut events: Vec<Event> = crate::audit::load(settings)?
+        .into_iter()
+        .filter(|r| matches(r.serial.as_deref()))
+        .map(Event::Operation)
+        .collect();
+    let mut previous: Option<Snapshot> = None;
+    for snapshot in load(settings)?
+        .into_iter()
+        .filter(|s| matches(s.serial.as_deref()))
+    {
+        let changes = previous
+            .as_ref()
+            .map(|p| snapshot.changes(p))
+            .unwrap_or_default();
+        previous = Some(snapshot.clone());
+        events.push(Event::Snapshot { snapshot, changes });
+    }
+    events.sort_by_key(|e| e.time());
+    let issued = Ledger::open(settings).ok().and_then(|l| {
+        l.entries()
+            .iter()
+            .find(|e| matches(Some(&e.serial)))
+            .cloned()
+    });
+    Ok(DeviceHistory {
+        serial: serial.to_string(),
+        issued,
+        events,
+    })
+}
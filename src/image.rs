@@ -0,0 +1,364 @@
+use crate::hardware::MemoryRange;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Zusammenhängender Datenblock, der an `address` geflasht wird
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub address: u64,
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    pub fn range(&self) -> MemoryRange {
+        MemoryRange {
+            base: self.address,
+            size: self.data.len() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Symbol {
+    pub address: u64,
+    pub size: u64,
+}
+
+/// Geladene Firmware: Rohdaten (`.bin`), Intel-HEX, DFU/DfuSe oder ELF mit
+/// Segmenten und Symbolen
+#[derive(Debug, Clone)]
+pub struct FirmwareImage {
+    pub segments: Vec<Segment>,
+    /// Symboltabelle aus ELF-Dateien (für RTT/defmt und Parameter-Adressen)
+    pub symbols: BTreeMap<String, Symbol>,
+    pub is_elf: bool,
+}
+
+impl FirmwareImage {
+    /// Lädt eine Firmware-Datei. Rohdaten und DFU-Dateien ohne Adressen werden
+    /// an `base` platziert, ELF-, Intel-HEX- und DfuSe-Dateien an die Adressen
+    /// aus der Datei.
+    pub fn load(path: &Path, base: u64) -> anyhow::Result<FirmwareImage> {
+        let data = std::fs::read(path)?;
+        if is_elf_file(path, &data) {
+            let (segments, symbols) = parse_elf(&data)?;
+            return Ok(FirmwareImage {
+                segments,
+                symbols,
+                is_elf: true,
+            });
+        }
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase());
+        let segments = match extension.as_deref() {
+            Some("hex" | "ihex") => parse_intel_hex(&String::from_utf8(data)?)?,
+            Some("dfu") => parse_dfu(&data, base)?,
+            _ if data.starts_with(DFUSE_SIGNATURE) => parse_dfu(&data, base)?,
+            _ => vec![Segment {
+                address: base,
+                data,
+            }],
+        };
+        Ok(FirmwareImage {
+            segments,
+            symbols: BTreeMap::new(),
+            is_elf: false,
+        })
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Adresse des RTT-Kontrollblocks, falls die Firmware RTT verwendet
+    pub fn rtt_address(&self) -> Option<u64> {
+        self.symbol("_SEGGER_RTT").map(|s| s.address)
+    }
+
+    pub fn summary(&self) -> String {
+        let size: u64 = self.segments.iter().map(|s| s.data.len() as u64).sum();
+        let mut text = format!("{} segment(s), {} bytes", self.segments.len(), size);
+        if self.is_elf {
+            text.push_str(&format!(", {} symbols", self.symbols.len()));
+            if let Some(address) = self.rtt_address() {
+                text.push_str(&format!(", RTT @ 0x{:08X}", address));
+            }
+        }
+        text
+    }
+}
+
+fn is_elf_file(path: &Path, data: &[u8]) -> bool {
+    let by_extension = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("elf"));
+    by_extension || data.starts_with(b"\x7FELF")
+}
+
+type ElfContents = (Vec<Segment>, BTreeMap<String, Symbol>);
+
+/// Ladbare Segmente (PT_LOAD) an ihrer physischen Adresse sowie alle Symbole
+fn parse_elf(data: &[u8]) -> anyhow::Result<ElfContents> {
+    use object::read::elf::{ElfFile32, ProgramHeader};
+    use object::{Object, ObjectSymbol};
+
+    let elf = ElfFile32::<object::Endianness>::parse(data)
+        .map_err(|e| anyhow::anyhow!("Ungültige ELF-Datei: {}", e))?;
+    let endian = elf.endian();
+    let mut segments = Vec::new();
+    for header in elf.elf_program_headers() {
+        if header.p_type(endian) != object::elf::PT_LOAD || header.p_filesz(endian) == 0 {
+            continue;
+        }
+        let bytes = header
+            .data(endian, data)
+            .map_err(|_| anyhow::anyhow!("ELF-Segment kann nicht gelesen werden"))?;
+        segments.push(Segment {
+            // Physische Adresse: initialisierte Daten liegen im Flash, nicht im RAM
+            address: header.p_paddr(endian) as u64,
+            data: bytes.to_vec(),
+        });
+    }
+    if segments.is_empty() {
+        anyhow::bail!("ELF-Datei enthält keine ladbaren Segmente");
+    }
+    segments.sort_by_key(|s| s.address);
+
+    let mut symbols = BTreeMap::new();
+    for symbol in elf.symbols() {
+        let Ok(name) = symbol.name() else {
+            continue;
+        };
+        if name.is_empty() || symbol.is_undefined() {
+            continue;
+        }
+        symbols.insert(
+            name.to_string(),
+            Symbol {
+                address: symbol.address(),
+                size: symbol.size(),
+            },
+        );
+    }
+    Ok((segments, symbols))
+}
+
+/// Fasst aneinandergrenzende Blöcke zu Segmenten zusammen
+fn merge_segments(mut blocks: Vec<Segment>) -> anyhow::Result<Vec<Segment>> {
+    blocks.sort_by_key(|b| b.address);
+    let mut segments: Vec<Segment> = Vec::new();
+    for block in blocks {
+        match segments.last_mut() {
+            Some(last) if last.range().end() == block.address => last.data.extend(block.data),
+            Some(last) if last.range().overlaps(&block.range()) => {
+                anyhow::bail!("Überlappende Daten bei 0x{:08X}", block.address)
+            }
+            _ => segments.push(block),
+        }
+    }
+    if segments.is_empty() {
+        anyhow::bail!("Firmware-Datei enthält keine Daten");
+    }
+    Ok(segments)
+}
+
+/// Intel-HEX mit Extended-Segment- und Extended-Linear-Address-Records
+fn parse_intel_hex(text: &str) -> anyhow::Result<Vec<Segment>> {
+    let mut blocks = Vec::new();
+    let mut offset = 0u64;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |msg: &str| anyhow::anyhow!("Intel-HEX, Zeile {}: {}", i + 1, msg);
+        let hex = line
+            .strip_prefix(':')
+            .ok_or_else(|| error("Record beginnt nicht mit ':'"))?;
+        if hex.len() % 2 != 0 || !hex.is_ascii() {
+            return Err(error("ungültige Hex-Ziffern"));
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|j| u8::from_str_radix(&hex[j..j + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| error("ungültige Hex-Ziffern"))?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(error("falsche Record-Länge"));
+        }
+        if bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return Err(error("Prüfsumme stimmt nicht"));
+        }
+        let address = u16::from_be_bytes([bytes[1], bytes[2]]) as u64;
+        let payload = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            0x00 => blocks.push(Segment {
+                address: offset + address,
+                data: payload.to_vec(),
+            }),
+            0x01 => return merge_segments(blocks),
+            0x02 if payload.len() == 2 => {
+                offset = (u16::from_be_bytes([payload[0], payload[1]]) as u64) << 4
+            }
+            0x04 if payload.len() == 2 => {
+                offset = (u16::from_be_bytes([payload[0], payload[1]]) as u64) << 16
+            }
+            // Startadressen sind für das Flashen ohne Bedeutung
+            0x03 | 0x05 => {}
+            kind => return Err(error(&format!("unbekannter Record-Typ {:02X}", kind))),
+        }
+    }
+    anyhow::bail!("Intel-HEX: End-of-File-Record fehlt")
+}
+
+const DFUSE_SIGNATURE: &[u8] = b"DfuSe";
+const DFU_SUFFIX_LEN: usize = 16;
+const DFUSE_PREFIX_LEN: usize = 11;
+const DFUSE_TARGET_LEN: usize = 274;
+
+fn le32(data: &[u8], offset: usize) -> anyhow::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| anyhow::anyhow!("DFU-Datei ist unvollständig"))
+}
+
+/// DFU-Datei mit Suffix: DfuSe (ST) mit Adressen je Element, sonst Rohdaten an `base`
+fn parse_dfu(data: &[u8], base: u64) -> anyhow::Result<Vec<Segment>> {
+    let Some(body_len) = data.len().checked_sub(DFU_SUFFIX_LEN) else {
+        anyhow::bail!("DFU-Datei ist unvollständig");
+    };
+    let suffix = &data[body_len..];
+    if &suffix[8..11] != b"UFD" || suffix[11] as usize != DFU_SUFFIX_LEN {
+        anyhow::bail!("DFU-Suffix fehlt");
+    }
+    if dfu_crc(&data[..data.len() - 4]) != le32(suffix, 12)? {
+        anyhow::bail!("CRC der DFU-Datei stimmt nicht");
+    }
+    let body = &data[..body_len];
+    if !body.starts_with(DFUSE_SIGNATURE) {
+        return Ok(vec![Segment {
+            address: base,
+            data: body.to_vec(),
+        }]);
+    }
+
+    let targets = *body
+        .get(DFUSE_PREFIX_LEN - 1)
+        .ok_or_else(|| anyhow::anyhow!("DFU-Datei ist unvollständig"))?;
+    let mut offset = DFUSE_PREFIX_LEN;
+    let mut blocks = Vec::new();
+    for _ in 0..targets {
+        if body.get(offset..offset + 6) != Some(b"Target".as_slice()) {
+            anyhow::bail!("DfuSe: Target-Präfix fehlt");
+        }
+        let alternate = body[offset + 6];
+        let elements = le32(body, offset + 270)?;
+        offset += DFUSE_TARGET_LEN;
+        // Andere Alternate Settings sind z.B. Option-Bytes oder OTP
+        if alternate != 0 && elements > 0 {
+            anyhow::bail!(
+                "DfuSe: Alternate Setting {} wird nicht unterstützt",
+                alternate
+            );
+        }
+        for _ in 0..elements {
+            let address = le32(body, offset)? as u64;
+            let size = le32(body, offset + 4)? as usize;
+            let data = body
+                .get(offset + 8..offset + 8 + size)
+                .ok_or_else(|| anyhow::anyhow!("DFU-Datei ist unvollständig"))?;
+            blocks.push(Segment {
+                address,
+                data: data.to_vec(),
+            });
+            offset += 8 + size;
+        }
+    }
+    merge_segments(blocks)
+}
+
+/// CRC-32 der DFU-Spezifikation (ohne abschließende Invertierung)
+fn dfu_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0xFFFF_FFFF, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_suffix(mut body: Vec<u8>) -> Vec<u8> {
+        body.extend_from_slice(&[0xFF, 0xFF, 0x11, 0xDF, 0x83, 0x04, 0x1A, 0x01]);
+        body.extend_from_slice(b"UFD");
+        body.push(DFU_SUFFIX_LEN as u8);
+        let crc = dfu_crc(&body);
+        body.extend_from_slice(&crc.to_le_bytes());
+        body
+    }
+
+    #[test]
+    fn intel_hex_with_extended_linear_address() {
+        let text = ":020000040800F2\n\
+                    :0400000001020304F2\n\
+                    :0400040005060708DE\n\
+                    :020010001122BB\n\
+                    :00000001FF\n";
+        let segments = parse_intel_hex(text).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].address, 0x0800_0000);
+        assert_eq!(segments[0].data, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(segments[1].address, 0x0800_0010);
+        assert_eq!(segments[1].data, [0x11, 0x22]);
+    }
+
+    #[test]
+    fn intel_hex_errors() {
+        assert!(parse_intel_hex(":0400000001020304F3\n:00000001FF\n").is_err());
+        assert!(parse_intel_hex(":0400000001020304F2\n").is_err());
+        assert!(parse_intel_hex("0400000001020304F2\n:00000001FF\n").is_err());
+    }
+
+    #[test]
+    fn dfuse_elements_at_their_addresses() {
+        let mut body = b"DfuSe\x01\0\0\0\0\x01".to_vec();
+        let mut target = b"Target\0".to_vec();
+        target.resize(266, 0);
+        target.extend_from_slice(&24u32.to_le_bytes());
+        target.extend_from_slice(&2u32.to_le_bytes());
+        body.extend(target);
+        for (address, data) in [
+            (0x0800_C000u32, [1u8, 2, 3, 4]),
+            (0x0800_0000, [5, 6, 7, 8]),
+        ] {
+            body.extend_from_slice(&address.to_le_bytes());
+            body.extend_from_slice(&4u32.to_le_bytes());
+            body.extend_from_slice(&data);
+        }
+        let segments = parse_dfu(&with_suffix(body), 0).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].address, 0x0800_0000);
+        assert_eq!(segments[1].address, 0x0800_C000);
+        assert_eq!(segments[1].data, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn plain_dfu_at_base() {
+        let file = with_suffix(vec![0xAA; 8]);
+        let segments = parse_dfu(&file, 0x0800_C000).unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].address, 0x0800_C000);
+        assert_eq!(segments[0].data, [0xAA; 8]);
+
+        let mut corrupted = file.clone();
+        corrupted[0] = 0;
+        assert!(parse_dfu(&corrupted, 0).is_err());
+    }
+}
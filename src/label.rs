@@ -0,0 +1,515 @@
+use crate::device::DeviceIdentity;
+use crate::hardware::HardwareType;
+use crate::history::Snapshot;
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const PRINTER_TIMEOUT: Duration = Duration::from_secs(5);
+/// Rand des Etiketts in mm
+const MARGIN_MM: f32 = 2.0;
+
+/// 2D-Code auf dem Etikett
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeKind {
+    #[default]
+    Qr,
+    DataMatrix,
+    None,
+}
+
+impl CodeKind {
+    pub fn all() -> &'static [CodeKind] {
+        &[CodeKind::Qr, CodeKind::DataMatrix, CodeKind::None]
+    }
+}
+
+impl std::fmt::Display for CodeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            CodeKind::Qr => "QR code",
+            CodeKind::DataMatrix => "Data Matrix",
+            CodeKind::None => "No code",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Etikettenvorlage und Ausgabeziele
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LabelSettings {
+    pub width_mm: f32,
+    pub height_mm: f32,
+    /// Auflösung des Druckers, auch für PNG
+    pub dpi: u32,
+    /// Textzeilen mit Platzhaltern `{model}`, `{hw_type}`, `{serial}`,
+    /// `{capacity}`, `{firmware}` und `{date}`
+    pub lines: Vec<String>,
+    pub code: CodeKind,
+    /// Inhalt des 2D-Codes, ebenfalls mit Platzhaltern
+    pub code_content: String,
+    pub zpl: bool,
+    pub pdf: bool,
+    pub png: bool,
+    /// Verzeichnis für die erzeugten Dateien, `None` = nicht speichern
+    pub folder: Option<PathBuf>,
+    /// Etikettendrucker als `Host:Port` (Raw-Port, meist 9100); erhält ZPL
+    pub printer: Option<String>,
+    /// Im Produktionsmodus nach jedem bestandenen Gerät ein Etikett ausgeben
+    pub after_production: bool,
+}
+
+impl Default for LabelSettings {
+    fn default() -> Self {
+        Self {
+            width_mm: 50.0,
+            height_mm: 25.0,
+            dpi: 203,
+            lines: vec![
+                "{model}".to_string(),
+                "S/N {serial}".to_string(),
+                "Capacity {capacity}".to_string(),
+                "FW {firmware}  {date}".to_string(),
+            ],
+            code: CodeKind::default(),
+            code_content: "{serial}".to_string(),
+            zpl: true,
+            pdf: false,
+            png: false,
+            folder: None,
+            printer: None,
+            after_production: false,
+        }
+    }
+}
+
+/// Werte, die in die Vorlage eingesetzt werden
+#[derive(Debug, Clone)]
+pub struct LabelData {
+    pub model: String,
+    pub hw_type: String,
+    pub serial: String,
+    pub capacity: String,
+    pub firmware: String,
+    pub date: String,
+}
+
+impl LabelData {
+    /// Aus den gelesenen Systemwerten; nicht lesbare Werte bleiben leer
+    pub fn from_snapshot(
+        hw_type: HardwareType,
+        snapshot: &Snapshot,
+        identity: &DeviceIdentity,
+    ) -> Self {
+        let value = |name: &str| {
+            snapshot
+                .values
+                .get(name)
+                .filter(|v| !v.starts_with('('))
+                .cloned()
+                .unwrap_or_default()
+        };
+        Self {
+            model: hw_type.to_string(),
+            hw_type: hw_type.id().to_string(),
+            serial: snapshot.serial.clone().unwrap_or_default(),
+            capacity: value("capacity"),
+            firmware: identity
+                .firmware
+                .as_ref()
+                .and_then(|f| f.version.as_ref())
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            date: Local::now().format("%Y-%m-%d").to_string(),
+        }
+    }
+
+    /// Beispielwerte für ein Testetikett
+    pub fn sample(hw_type: HardwareType) -> Self {
+        Self {
+            model: hw_type.to_string(),
+            hw_type: hw_type.id().to_string(),
+            serial: crate::serial::SerialFormat::for_model(hw_type).example(),
+            capacity: "100".to_string(),
+            firmware: "1.0.0".to_string(),
+            date: Local::now().format("%Y-%m-%d").to_string(),
+        }
+    }
+
+    /// Setzt die Werte in die Platzhalter von `template` ein
+    pub fn fill(&self, template: &str) -> String {
+        template
+            .replace("{model}", &self.model)
+            .replace("{hw_type}", &self.hw_type)
+            .replace("{serial}", &self.serial)
+            .replace("{capacity}", &self.capacity)
+            .replace("{firmware}", &self.firmware)
+            .replace("{date}", &self.date)
+    }
+}
+
+/// Module eines 2D-Codes mit der nötigen Ruhezone
+struct Matrix {
+    size: usize,
+    quiet: usize,
+    modules: Vec<bool>,
+}
+
+impl Matrix {
+    fn encode(kind: CodeKind, content: &str) -> anyhow::Result<Option<Matrix>> {
+        match kind {
+            CodeKind::None => Ok(None),
+            CodeKind::Qr => {
+                let code = qrcode::QrCode::new(content.as_bytes())
+                    .map_err(|e| anyhow::anyhow!("QR-Code: {}", e))?;
+                Ok(Some(Matrix {
+                    size: code.width(),
+                    quiet: 4,
+                    modules: code
+                        .to_colors()
+                        .into_iter()
+                        .map(|c| c == qrcode::Color::Dark)
+                        .collect(),
+                }))
+            }
+            CodeKind::DataMatrix => {
+                let code = crate::datamatrix::encode(content)?;
+                let modules = (0..code.size * code.size)
+                    .map(|i| code.is_dark(i % code.size, i / code.size))
+                    .collect();
+                Ok(Some(Matrix {
+                    size: code.size,
+                    quiet: 1,
+                    modules,
+                }))
+            }
+        }
+    }
+
+    fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    /// Module einschließlich Ruhezone auf beiden Seiten
+    fn total(&self) -> usize {
+        self.size + 2 * self.quiet
+    }
+}
+
+/// Positionen in mm, Ursprung links oben
+struct Layout {
+    width: f32,
+    height: f32,
+    /// Linke obere Ecke und Kantenlänge des Codes einschließlich Ruhezone
+    code: Option<(f32, f32, f32)>,
+    text_x: f32,
+    line_height: f32,
+    /// Schriftgröße in mm
+    font_size: f32,
+    lines: Vec<String>,
+}
+
+impl Layout {
+    fn new(settings: &LabelSettings, data: &LabelData, has_code: bool) -> Layout {
+        let inner = (settings.height_mm - 2.0 * MARGIN_MM).max(1.0);
+        let code = has_code.then_some((MARGIN_MM, MARGIN_MM, inner));
+        let text_x = match code {
+            Some((x, _, size)) => x + size + MARGIN_MM,
+            None => MARGIN_MM,
+        };
+        let lines: Vec<String> = settings.lines.iter().map(|line| data.fill(line)).collect();
+        let line_height = inner / lines.len().max(1) as f32;
+        // Längste Zeile muss in die Breite passen; mittlere Zeichenbreite etwa 0,55 der Schriftgröße
+        let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let available = (settings.width_mm - text_x - MARGIN_MM).max(1.0);
+        let font_size = (line_height * 0.8).min(available / (longest.max(1) as f32 * 0.55));
+        Layout {
+            width: settings.width_mm,
+            height: settings.height_mm,
+            code,
+            text_x,
+            line_height,
+            font_size,
+            lines,
+        }
+    }
+
+    /// Oberkante der Zeile `i`
+    fn line_top(&self, i: usize) -> f32 {
+        MARGIN_MM + i as f32 * self.line_height
+    }
+}
+
+fn dots(mm: f32, dpi: u32) -> u32 {
+    (mm * dpi as f32 / 25.4).round() as u32
+}
+
+/// ZPL II für Thermodrucker; der Code wird vom Drucker selbst erzeugt
+pub fn render_zpl(settings: &LabelSettings, data: &LabelData) -> anyhow::Result<String> {
+    let content = data.fill(&settings.code_content);
+    let matrix = Matrix::encode(settings.code, &content)?;
+    let layout = Layout::new(settings, data, matrix.is_some());
+    let dpi = settings.dpi;
+    let mut zpl = String::from("^XA\n^CI28\n");
+    zpl.push_str(&format!(
+        "^PW{}\n^LL{}\n",
+        dots(layout.width, dpi),
+        dots(layout.height, dpi)
+    ));
+    if let (Some(matrix), Some((x, y, size))) = (&matrix, layout.code) {
+        // Ruhezone ist Teil der Fläche, der Code beginnt dahinter
+        let module = (dots(size, dpi) / matrix.total() as u32).max(1);
+        let offset = module * matrix.quiet as u32;
+        let (x, y) = (dots(x, dpi) + offset, dots(y, dpi) + offset);
+        match settings.code {
+            CodeKind::Qr => zpl.push_str(&format!(
+                "^FO{},{}^BQN,2,{}^FH_^FDQA,{}^FS\n",
+                x,
+                y,
+                module.min(10),
+                zpl_escape(&content)
+            )),
+            CodeKind::DataMatrix => zpl.push_str(&format!(
+                "^FO{},{}^BXN,{},200^FH_^FD{}^FS\n",
+                x,
+                y,
+                module,
+                zpl_escape(&content)
+            )),
+            CodeKind::None => {}
+        }
+    }
+    let font = dots(layout.font_size, dpi);
+    for (i, line) in layout.lines.iter().enumerate() {
+        zpl.push_str(&format!(
+            "^FO{},{}^A0N,{},{}^FH_^FD{}^FS\n",
+            dots(layout.text_x, dpi),
+            dots(layout.line_top(i), dpi),
+            font,
+            font,
+            zpl_escape(line)
+        ));
+    }
+    zpl.push_str("^XZ\n");
+    Ok(zpl)
+}
+
+/// Steuerzeichen als Hex-Escape für `^FH_`
+fn zpl_escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '^' | '~' | '_' => escaped.push_str(&format!("_{:02X}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Rastergrafik in der Auflösung des Druckers
+pub fn render_png(settings: &LabelSettings, data: &LabelData) -> anyhow::Result<Vec<u8>> {
+    let content = data.fill(&settings.code_content);
+    let matrix = Matrix::encode(settings.code, &content)?;
+    let layout = Layout::new(settings, data, matrix.is_some());
+    let dpi = settings.dpi;
+    let (width, height) = (dots(layout.width, dpi), dots(layout.height, dpi));
+    let mut image = image::GrayImage::from_pixel(width, height, image::Luma([255]));
+
+    if let (Some(matrix), Some((x, y, size))) = (&matrix, layout.code) {
+        let module = (dots(size, dpi) / matrix.total() as u32).max(1);
+        let x0 = dots(x, dpi) + module * matrix.quiet as u32;
+        let y0 = dots(y, dpi) + module * matrix.quiet as u32;
+        for my in 0..matrix.size {
+            for mx in 0..matrix.size {
+                if !matrix.is_dark(mx, my) {
+                    continue;
+                }
+                for py in 0..module {
+                    for px in 0..module {
+                        let (x, y) = (x0 + mx as u32 * module + px, y0 + my as u32 * module + py);
+                        if x < width && y < height {
+                            image.put_pixel(x, y, image::Luma([0]));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let font = FontRef::try_from_slice(epaint_default_fonts::UBUNTU_LIGHT)?;
+    let scale = PxScale::from(dots(layout.font_size, dpi) as f32);
+    let scaled = font.as_scaled(scale);
+    for (i, line) in layout.lines.iter().enumerate() {
+        let mut caret = dots(layout.text_x, dpi) as f32;
+        let baseline = dots(layout.line_top(i), dpi) as f32 + scaled.ascent();
+        let mut previous = None;
+        for c in line.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, id);
+            }
+            let glyph = id.with_scale_and_position(scale, ab_glyph::point(caret, baseline));
+            caret += scaled.h_advance(id);
+            previous = Some(id);
+            let Some(outline) = font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                let x = bounds.min.x as i64 + gx as i64;
+                let y = bounds.min.y as i64 + gy as i64;
+                if (0..width as i64).contains(&x) && (0..height as i64).contains(&y) {
+                    let pixel = image.get_pixel_mut(x as u32, y as u32);
+                    let value = pixel.0[0] as f32 * (1.0 - coverage.clamp(0.0, 1.0));
+                    pixel.0[0] = value as u8;
+                }
+            });
+        }
+    }
+
+    let mut png = Vec::new();
+    image.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// Vektor-PDF im Format des Etiketts
+pub fn render_pdf(settings: &LabelSettings, data: &LabelData) -> anyhow::Result<Vec<u8>> {
+    use printpdf::{BuiltinFont, Mm, PdfDocument, Rect};
+
+    let content = data.fill(&settings.code_content);
+    let matrix = Matrix::encode(settings.code, &content)?;
+    let layout = Layout::new(settings, data, matrix.is_some());
+    let (doc, page, layer) = PdfDocument::new(
+        format!("{} {}", data.model, data.serial),
+        Mm(layout.width),
+        Mm(layout.height),
+        "Label",
+    );
+    let layer = doc.get_page(page).get_layer(layer);
+    // PDF-Koordinaten beginnen links unten
+    let flip = |y: f32| layout.height - y;
+
+    if let (Some(matrix), Some((x, y, size))) = (&matrix, layout.code) {
+        let module = size / matrix.total() as f32;
+        let x0 = x + module * matrix.quiet as f32;
+        let y0 = y + module * matrix.quiet as f32;
+        for my in 0..matrix.size {
+            // Zusammenhängende dunkle Module einer Zeile als ein Rechteck
+            let mut mx = 0;
+            while mx < matrix.size {
+                if !matrix.is_dark(mx, my) {
+                    mx += 1;
+                    continue;
+                }
+                let start = mx;
+                while mx < matrix.size && matrix.is_dark(mx, my) {
+                    mx += 1;
+                }
+                let top = y0 + my as f32 * module;
+                layer.add_rect(Rect::new(
+                    Mm(x0 + start as f32 * module),
+                    Mm(flip(top + module)),
+                    Mm(x0 + mx as f32 * module),
+                    Mm(flip(top)),
+                ));
+            }
+        }
+    }
+
+    let font = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+    // Schriftgröße in pt; die Oberlänge von Helvetica beträgt etwa 0,75 der Größe
+    let points = layout.font_size * 72.0 / 25.4;
+    for (i, line) in layout.lines.iter().enumerate() {
+        let baseline = layout.line_top(i) + layout.font_size * 0.75;
+        layer.use_text(
+            line.as_str(),
+            points,
+            Mm(layout.text_x),
+            Mm(flip(baseline)),
+            &font,
+        );
+    }
+    Ok(doc.save_to_bytes()?)
+}
+
+/// Sendet ZPL an den Raw-Port eines Etikettendruckers
+pub fn send_to_printer(address: &str, zpl: &str) -> anyhow::Result<()> {
+    let address = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{}:9100", address)
+    };
+    let socket = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Drucker {} nicht gefunden", address))?;
+    let mut stream = TcpStream::connect_timeout(&socket, PRINTER_TIMEOUT)
+        .map_err(|e| anyhow::anyhow!("Drucker {} nicht erreichbar: {}", address, e))?;
+    stream.set_write_timeout(Some(PRINTER_TIMEOUT))?;
+    stream.write_all(zpl.as_bytes())?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Erzeugt das Etikett in den eingestellten Formaten, speichert es und sendet
+/// es an den Drucker. Liefert eine Beschreibung der Ausgaben.
+pub fn output(settings: &LabelSettings, data: &LabelData) -> anyhow::Result<String> {
+    if settings.folder.is_none() && settings.printer.is_none() {
+        anyhow::bail!("Kein Ausgabeziel für Etiketten eingestellt (Ordner oder Drucker)");
+    }
+    let mut outputs = Vec::new();
+    if let Some(folder) = &settings.folder {
+        if !settings.zpl && !settings.pdf && !settings.png {
+            anyhow::bail!("Kein Etikettenformat ausgewählt");
+        }
+        std::fs::create_dir_all(folder)?;
+        let name = file_name(data);
+        if settings.zpl {
+            outputs.push(save(
+                folder,
+                &name,
+                "zpl",
+                render_zpl(settings, data)?.as_bytes(),
+            )?);
+        }
+        if settings.pdf {
+            outputs.push(save(folder, &name, "pdf", &render_pdf(settings, data)?)?);
+        }
+        if settings.png {
+            outputs.push(save(folder, &name, "png", &render_png(settings, data)?)?);
+        }
+    }
+    if let Some(printer) = &settings.printer {
+        send_to_printer(printer, &render_zpl(settings, data)?)?;
+        outputs.push(format!("Drucker {}", printer));
+    }
+    let msg = format!("Etikett {} ausgegeben: {}", data.serial, outputs.join(", "));
+    crate::oplog::record(msg.clone());
+    Ok(msg)
+}
+
+fn file_name(data: &LabelData) -> String {
+    let serial: String = data
+        .serial
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let serial = if serial.is_empty() {
+        "label".to_string()
+    } else {
+        serial
+    };
+    format!("{}_{}", serial, Local::now().format("%Y%m%d-%H%M%S"))
+}
+
+fn save(folder: &Path, name: &str, extension: &str, data: &[u8]) -> anyhow::Result<String> {
+    let path = folder.join(format!("{}.{}", name, extension));
+    std::fs::write(&path, data)?;
+    Ok(path.display().to_string())
+}
@@ -1,48 +1,334 @@
+mod audit;
+mod backend;
+mod backup;
+mod batch;
+mod bundle;
+mod cli;
+mod datamatrix;
+mod device;
+mod diagnostics;
 mod hardware;
+mod history;
+mod image;
+mod label;
+mod operations;
+mod operator;
+mod oplog;
+mod option_bytes;
+mod parameters;
+mod policy;
+mod probe;
+mod recovery;
+mod sanity;
+mod scan;
+mod serial;
+mod settings;
+mod signing;
+mod simulator;
+#[cfg(test)]
+mod testing;
+mod version;
+mod views;
 use eframe::egui;
 use hardware::HardwareType;
-
-use std::sync::Arc;
+use settings::{Overrides, Settings};
 
 #[derive(Clone)]
 struct SelectedFirmware {
     tag: String,
-    asset: String,
+    /// Assets eines Releases, die gemeinsam geflasht werden (z.B. Bootloader und Anwendung)
+    assets: Vec<String>,
 }
 
 struct MyApp {
     active_view: View,
+    /// Gespeicherte Einstellungen (ohne Überschreibungen)
+    settings: Settings,
+    /// Überschreibungen aus Umgebung und Kommandozeile
+    overrides: Overrides,
+    /// Bearbeitungsstand im Settings-View
+    settings_draft: Settings,
+    settings_message: Option<String>,
+    /// Fehler beim Laden des benutzerdefinierten Hardware-Katalogs
+    catalogue_error: Option<String>,
     selected_hw_type: Option<HardwareType>,
+    /// Hardware-Typ wurde vom Benutzer gewählt (nicht voreingestellt oder erkannt)
+    hw_type_manual: bool,
+    detect_handle: Option<device::DetectHandle>,
+    detected_identity: Option<device::DeviceIdentity>,
+    detect_error: Option<String>,
+    /// Einordnung von Verbindungsfehlern mit Abhilfe
+    detect_diagnosis: Option<diagnostics::Diagnosis>,
+    /// Ergebnis von Reset/Halt/Run
+    target_control_message: Option<String>,
+    allow_model_mismatch: bool,
+    /// Backups des gewählten Hardware-Typs, `None` = neu laden
+    backups: Option<Vec<backup::Backup>>,
+    selected_backup: Option<usize>,
+    confirm_restore: bool,
+    /// Letzter Versuch scheiterte, weil das Backup von einem anderen Gerät stammt
+    restore_mismatch: bool,
+    restore_other_device: bool,
+    restore_message: Option<String>,
+    /// Bestätigung für Downgrade bzw. erneutes Flashen derselben Version
+    confirm_version_change: bool,
+    /// Geschützte Bereiche (Konfiguration, Kalibrierung) dürfen überschrieben werden
+    allow_protected_overwrite: bool,
     flash_release_service: flash::FlashReleaseService,
     selected_firmware: Option<SelectedFirmware>,
     download_handle: Option<flash::FirmwareDownloadHandle>,
     download_progress: Option<usize>,
     download_done: bool,
     download_error: Option<String>,
-    downloaded_path: Option<String>,
+    downloaded_paths: Vec<String>,
+    /// Inhalt der heruntergeladenen Dateien (Segmente, Symbole) oder Ladefehler
+    downloaded_images: Vec<Result<image::FirmwareImage, String>>,
+    /// Befunde der Image-Prüfung für Hardware-Typ und Freigabe geschützter Bereiche
+    image_findings: Option<(HardwareType, bool, Vec<sanity::Finding>)>,
     flash_result_message: Option<String>,
+    /// Letzter Flash-Vorgang ist fehlgeschlagen, Assistent anbieten
+    flash_failed: bool,
+    flash_diagnosis: Option<diagnostics::Diagnosis>,
+    /// Abschnitt, der in der Hilfe geöffnet wird
+    help_section: Option<diagnostics::ErrorKind>,
+    /// Eingaben für Auslesen und Löschen (Adresse und Größe als Text, dezimal oder `0x…`)
+    readout_address: String,
+    readout_size: String,
+    readout_path: String,
+    erase_address: String,
+    erase_size: String,
+    confirm_erase: bool,
+    erase_protected: bool,
+    operation_message: Option<String>,
+    /// Readout-Protection laut Katalog nach dem Flashen nicht setzen (Entwicklungsgeräte)
+    skip_rdp: bool,
+    /// Nach manuellem Flashen noch zu setzende Readout-Protection
+    pending_rdp: Option<(HardwareType, option_bytes::RdpLevel)>,
+    pending_rdp_message: Option<String>,
+    option_bytes: Option<option_bytes::OptionBytes>,
+    confirm_rdp_enable: bool,
+    confirm_rdp_regression: bool,
+    option_bytes_message: Option<String>,
+    /// Wiederherstellungs-Assistent, `None` = noch nicht gestartet
+    recovery: Option<recovery::Wizard>,
+    /// Laufende oder beendete Serie im Produktionsmodus
+    batch: Option<batch::Batch>,
+    production_write_serial: bool,
+    production_verify: bool,
+    /// Seriennummer: manuelle Eingabe statt Vergabe aus dem Register
+    serial_manual: bool,
+    serial_input: String,
+    /// Zuletzt vom Gerät gelesene Seriennummer
+    serial_current: Option<String>,
+    serial_message: Option<String>,
+    capacity_input: u32,
+    /// Zuletzt vom Gerät gelesene Kapazität
+    capacity_current: Option<String>,
+    capacity_message: Option<String>,
+    /// Geladenes Audit-Log, `None` = beim nächsten Anzeigen laden
+    audit_records: Option<Result<Vec<audit::AuditRecord>, String>>,
+    audit_filter: String,
+    audit_action: Option<audit::Action>,
+    audit_export_path: String,
+    audit_message: Option<String>,
+    /// Zuletzt gelesene Systemwerte mit Änderungen gegenüber der vorherigen Momentaufnahme
+    system_snapshot: Option<(
+        history::Snapshot,
+        std::collections::BTreeMap<String, Option<String>>,
+    )>,
+    system_message: Option<String>,
+    /// Etikettendaten zu den zuletzt gelesenen Systemwerten
+    system_label: Option<label::LabelData>,
+    history_serial: String,
+    history: Option<Result<history::DeviceHistory, String>>,
+    scan_input: String,
+    scan_message: Option<String>,
+    /// Gescannter Arbeitsauftrag, dessen Firmware noch gewählt werden muss
+    scan_work_order: Option<scan::ScanResult>,
+    /// Angemeldeter Bediener, `None` = Anmeldung anzeigen
+    operator: Option<operator::Operator>,
+    /// Bedienerprofile oder Ladefehler
+    operators: Result<operator::Operators, String>,
+    login_name: String,
+    login_pin: String,
+    login_message: Option<String>,
+    /// Fehlversuche in Folge; danach wird die Anmeldung kurz gesperrt
+    login_failures: u32,
+    login_blocked_until: Option<std::time::Instant>,
+    /// Eingaben im Formular für Bedienerprofile
+    operator_form_name: String,
+    operator_form_role: operator::Role,
+    operator_form_pin: String,
+    operator_form_pin_repeat: String,
+    operator_message: Option<String>,
+    /// Geladene Freigabeliste, `None` = beim nächsten Anzeigen laden
+    firmware_policy: Option<policy::Policy>,
+    /// Export von Firmware-Paketen: gewählte Hardware-Typen, geladene Releases und Auswahl
+    bundle_hw_types: Vec<HardwareType>,
+    bundle_releases: Option<Result<Vec<(HardwareType, flash::Release)>, String>>,
+    bundle_selection: Vec<(HardwareType, String)>,
+    bundle_handle: Option<bundle::BundleHandle>,
+    bundle_path: String,
+    bundle_message: Option<String>,
+    bundle_import_path: String,
+    bundle_import_message: Option<String>,
 }
 
-impl Default for MyApp {
-    fn default() -> Self {
+impl MyApp {
+    fn new(overrides: Overrides, catalogue_error: Option<String>) -> Self {
+        let (settings, settings_message) = match Settings::load() {
+            Ok(settings) => (settings, None),
+            Err(e) => (
+                Settings::default(),
+                Some(format!("Fehler beim Laden der Einstellungen: {}", e)),
+            ),
+        };
+        let effective = overrides.apply(&settings);
+        let selected_hw_type = effective.default_hw_type();
+        let operators = operator::Operators::load(&effective).map_err(|e| format!("{:#}", e));
         Self {
             active_view: View::default(),
-            selected_hw_type: None,
+            settings_draft: settings.clone(),
+            settings,
+            overrides,
+            settings_message,
+            catalogue_error,
+            selected_hw_type,
+            hw_type_manual: false,
+            detect_handle: None,
+            detected_identity: None,
+            detect_error: None,
+            detect_diagnosis: None,
+            target_control_message: None,
+            allow_model_mismatch: false,
+            confirm_version_change: false,
+            allow_protected_overwrite: false,
+            backups: None,
+            selected_backup: None,
+            confirm_restore: false,
+            restore_mismatch: false,
+            restore_other_device: false,
+            restore_message: None,
             flash_release_service: flash::FlashReleaseService::new(),
             selected_firmware: None,
             download_handle: None,
             download_progress: None,
             download_done: false,
             download_error: None,
-            downloaded_path: None,
+            downloaded_paths: Vec::new(),
+            downloaded_images: Vec::new(),
+            image_findings: None,
             flash_result_message: None,
+            flash_failed: false,
+            flash_diagnosis: None,
+            help_section: None,
+            readout_address: String::new(),
+            readout_size: String::new(),
+            readout_path: String::new(),
+            erase_address: String::new(),
+            erase_size: String::new(),
+            confirm_erase: false,
+            erase_protected: false,
+            operation_message: None,
+            skip_rdp: false,
+            pending_rdp: None,
+            pending_rdp_message: None,
+            option_bytes: None,
+            confirm_rdp_enable: false,
+            confirm_rdp_regression: false,
+            option_bytes_message: None,
+            recovery: None,
+            batch: None,
+            production_write_serial: true,
+            production_verify: true,
+            serial_manual: false,
+            serial_input: String::new(),
+            serial_current: None,
+            serial_message: None,
+            capacity_input: 0,
+            capacity_current: None,
+            capacity_message: None,
+            audit_records: None,
+            audit_filter: String::new(),
+            audit_action: None,
+            audit_export_path: String::new(),
+            audit_message: None,
+            system_snapshot: None,
+            system_message: None,
+            system_label: None,
+            history_serial: String::new(),
+            history: None,
+            scan_input: String::new(),
+            scan_message: None,
+            scan_work_order: None,
+            operator: None,
+            operators,
+            login_name: String::new(),
+            login_pin: String::new(),
+            login_message: None,
+            login_failures: 0,
+            login_blocked_until: None,
+            operator_form_name: String::new(),
+            operator_form_role: operator::Role::Operator,
+            operator_form_pin: String::new(),
+            operator_form_pin_repeat: String::new(),
+            operator_message: None,
+            firmware_policy: None,
+            bundle_hw_types: Vec::new(),
+            bundle_releases: None,
+            bundle_selection: Vec::new(),
+            bundle_handle: None,
+            bundle_path: String::new(),
+            bundle_message: None,
+            bundle_import_path: String::new(),
+            bundle_import_message: None,
+        }
+    }
+
+    /// Verwirft heruntergeladene Firmware, z.B. nach einer neuen Auswahl
+    fn reset_download(&mut self) {
+        self.download_progress = None;
+        self.download_done = false;
+        self.download_error = None;
+        self.downloaded_paths.clear();
+        self.downloaded_images.clear();
+        self.image_findings = None;
+        self.download_handle = None;
+        self.confirm_version_change = false;
+    }
+
+    /// Befunde der Image-Prüfung; werden nur nach einem neuen Download oder bei
+    /// geändertem Hardware-Typ bzw. geänderter Freigabe neu berechnet
+    fn image_findings(&mut self, hw: HardwareType, allow_protected: bool) -> Vec<sanity::Finding> {
+        match &self.image_findings {
+            Some((cached_hw, cached_allow, findings))
+                if *cached_hw == hw && *cached_allow == allow_protected =>
+            {
+                findings.clone()
+            }
+            _ => {
+                let images: Vec<image::FirmwareImage> = self
+                    .downloaded_images
+                    .iter()
+                    .filter_map(|i| i.as_ref().ok().cloned())
+                    .collect();
+                let findings = sanity::check_images(&images, hw, allow_protected);
+                self.image_findings = Some((hw, allow_protected, findings.clone()));
+                findings
+            }
         }
     }
+
+    /// Einstellungen inklusive Überschreibungen aus Umgebung und Kommandozeile
+    fn effective_settings(&self) -> Settings {
+        self.overrides.apply(&self.settings)
+    }
 }
 mod flash;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Default)]
 enum View {
+    #[default]
     Flash,
     SetSerial,
     SetCapacity,
@@ -50,18 +336,21 @@ enum View {
     AppUpdate,
     About,
     Settings,
+    Recovery,
+    Production,
+    Audit,
+    History,
+    Operators,
+    Bundles,
     Help,
 }
 
-// Standard‑View ist Flash
-impl Default for View {
-    fn default() -> Self {
-        View::Flash
-    }
-}
-
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.operator.is_none() {
+            egui::CentralPanel::default().show(ctx, |ui| self.login_view(ui));
+            return;
+        }
         // Menüleiste
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             egui::MenuBar::new().ui(ui, |ui| {
@@ -69,15 +358,56 @@ impl eframe::App for MyApp {
                     if ui.button("Flash").clicked() {
                         self.active_view = View::Flash;
                     }
-                    if ui.button("Set serial number").clicked() {
+                    if ui
+                        .add_enabled(
+                            self.allowed(operator::Permission::SetSerial),
+                            egui::Button::new("Set serial number"),
+                        )
+                        .clicked()
+                    {
                         self.active_view = View::SetSerial;
                     }
-                    if ui.button("Set capacity").clicked() {
+                    if ui
+                        .add_enabled(
+                            self.allowed(operator::Permission::SetCapacity),
+                            egui::Button::new("Set capacity"),
+                        )
+                        .clicked()
+                    {
                         self.active_view = View::SetCapacity;
                     }
                     if ui.button("Read system values").clicked() {
                         self.active_view = View::ReadSystem;
                     }
+                    if ui
+                        .add_enabled(
+                            self.allowed(operator::Permission::OptionBytes),
+                            egui::Button::new("Recovery wizard"),
+                        )
+                        .clicked()
+                    {
+                        self.active_view = View::Recovery;
+                    }
+                    if ui.button("Production mode").clicked() {
+                        self.active_view = View::Production;
+                    }
+                    if ui.button("Device history").clicked() {
+                        self.history = None;
+                        self.active_view = View::History;
+                    }
+                    if ui.button("Audit log").clicked() {
+                        self.audit_records = None;
+                        self.active_view = View::Audit;
+                    }
+                    if ui
+                        .add_enabled(
+                            self.allowed(operator::Permission::ExportBundle),
+                            egui::Button::new("Export firmware bundle"),
+                        )
+                        .clicked()
+                    {
+                        self.active_view = View::Bundles;
+                    }
                 });
                 ui.menu_button("App", |ui| {
                     if ui.button("Update app").clicked() {
@@ -88,23 +418,54 @@ impl eframe::App for MyApp {
                     }
                 });
                 ui.menu_button("Settings", |ui| {
-                    if ui.button("Settings").clicked() {
+                    let allowed = self.allowed(operator::Permission::Settings);
+                    if ui
+                        .add_enabled(allowed, egui::Button::new("Settings"))
+                        .clicked()
+                    {
                         self.active_view = View::Settings;
                     }
+                    if ui
+                        .add_enabled(allowed, egui::Button::new("Operator profiles"))
+                        .clicked()
+                    {
+                        self.operator_message = None;
+                        self.active_view = View::Operators;
+                    }
                 });
                 ui.menu_button("Help", |ui| {
                     if ui.button("Manual").clicked() {
                         self.active_view = View::Help;
                     }
                 });
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    // Nicht während einer laufenden Serie abmelden
+                    let running = self.batch.as_ref().is_some_and(|b| b.is_running());
+                    if ui
+                        .add_enabled(!running, egui::Button::new("Log out"))
+                        .clicked()
+                    {
+                        self.logout();
+                    }
+                    if let Some(operator) = &self.operator {
+                        ui.label(format!("{} ({})", operator.name, operator.role));
+                    }
+                });
             });
         });
 
+        let settings = self.effective_settings();
+
+        egui::TopBottomPanel::top("scan_bar").show(ctx, |ui| self.scan_bar(ui, &settings));
+
         // Zentraler Content
         egui::CentralPanel::default().show(ctx, |ui| match self.active_view {
             View::Flash => {
                 ui.heading("Flash device");
                 ui.separator();
+                if let Some(err) = &self.catalogue_error {
+                    ui.colored_label(egui::Color32::RED, err);
+                }
                 ui.label("1. Select hardware type:");
                 let mut hw_type_changed = false;
                 ui.horizontal(|ui| {
@@ -115,61 +476,269 @@ impl eframe::App for MyApp {
                                 self.selected_hw_type = Some(*hw_type);
                                 hw_type_changed = true;
                             }
+                            self.hw_type_manual = true;
+                        }
+                    }
+                });
+                if hw_type_changed {
+                    self.allow_model_mismatch = false;
+                    self.backups = None;
+                    self.selected_backup = None;
+                    self.restore_message = None;
+                    self.option_bytes = None;
+                    self.option_bytes_message = None;
+                }
+
+                // Angeschlossenes Gerät erkennen
+                ui.horizontal(|ui| {
+                    let detecting = self.detect_handle.is_some();
+                    if ui
+                        .add_enabled(!detecting, egui::Button::new("Read connected device"))
+                        .clicked()
+                    {
+                        self.detect_error = None;
+                        self.detect_diagnosis = None;
+                        self.detect_handle = Some(device::DetectHandle::start(
+                            probe::ProbeConfig::from_settings(&settings),
+                            self.selected_hw_type,
+                        ));
+                    }
+                    if detecting {
+                        ui.spinner();
+                        ui.label("Detecting...");
+                        ui.ctx()
+                            .request_repaint_after(std::time::Duration::from_millis(100));
+                    }
+                    ui.separator();
+                    for action in [
+                        probe::TargetAction::Reset,
+                        probe::TargetAction::Halt,
+                        probe::TargetAction::Run,
+                    ] {
+                        if ui
+                            .add_enabled(!detecting, egui::Button::new(action.to_string()))
+                            .clicked()
+                        {
+                            let target = self.selected_hw_type.map(|hw| hw.model().target.as_str());
+                            self.target_control_message = Some(
+                                match probe::control(
+                                    &probe::ProbeConfig::from_settings(&settings),
+                                    target,
+                                    action,
+                                ) {
+                                    Ok(msg) => msg,
+                                    Err(e) => format!("Fehler bei {}: {}", action, e),
+                                },
+                            );
                         }
                     }
                 });
+                if let Some(msg) = &self.target_control_message {
+                    ui.label(msg);
+                }
+                if let Some(result) = self.detect_handle.as_ref().and_then(|h| h.poll()) {
+                    self.detect_handle = None;
+                    match result {
+                        Ok(identity) => {
+                            // Erkanntes Modell nur vorwählen, wenn nicht manuell gewählt wurde
+                            if !self.hw_type_manual
+                                && let Some(hw) = identity.detected()
+                            {
+                                self.selected_hw_type = Some(hw);
+                            }
+                            self.detected_identity = Some(identity);
+                        }
+                        Err(e) => {
+                            self.detected_identity = None;
+                            self.detect_diagnosis = Some(diagnostics::diagnose(
+                                &e,
+                                &probe::ProbeConfig::from_settings(&settings),
+                            ));
+                            self.detect_error = Some(e);
+                        }
+                    }
+                }
+                if let Some(err) = &self.detect_error {
+                    ui.colored_label(egui::Color32::RED, err);
+                }
+                if let Some(diagnosis) = self.detect_diagnosis.clone() {
+                    self.diagnosis_ui(ui, &diagnosis, settings.language);
+                }
+                if let Some(identity) = &self.detected_identity {
+                    ui.label(format!("Detected: {}", identity.summary()));
+                    ui.weak(format!("Connection: {}", identity.connection));
+                    if let Some(hw) = self.selected_hw_type
+                        && identity.contradicts(hw)
+                    {
+                        ui.colored_label(
+                            egui::Color32::RED,
+                            egui::RichText::new(format!(
+                                "WARNING: The connected device does not match the selected hardware type {}!",
+                                hw
+                            ))
+                            .heading()
+                            .strong(),
+                        );
+                        if self.require(ui, operator::Permission::OverrideChecks) {
+                            ui.checkbox(
+                                &mut self.allow_model_mismatch,
+                                "Flash anyway (incompatible firmware may damage the device)",
+                            );
+                        }
+                    }
+                }
+
+                if let Some(hw) = self.selected_hw_type {
+                    let model = hw.model();
+                    ui.collapsing("Model details", |ui| {
+                        ui.label(format!("Target: {}", model.target));
+                        ui.label(format!("Flash: {}", model.flash));
+                        ui.label(format!("RAM: {}", model.ram));
+                        for region in &model.regions {
+                            ui.label(format!("Region {}", region));
+                        }
+                        for parameter in &model.parameters {
+                            ui.label(format!("Parameter: {}", parameter));
+                        }
+                    });
+                    ui.collapsing("Backups", |ui| self.backups_ui(ui, hw, &settings));
+                    ui.collapsing("Verify, read out, erase", |ui| {
+                        self.operations_ui(ui, hw, &settings)
+                    });
+                    if let Some(kind) = model.option_bytes {
+                        ui.collapsing("Option bytes / readout protection", |ui| {
+                            self.option_bytes_ui(ui, hw, kind, &settings)
+                        });
+                    }
+                }
+                ui.collapsing("Operation log", |ui| self.operation_log_ui(ui));
+                ui.collapsing("Offline firmware bundle", |ui| {
+                    self.bundle_import_ui(ui, &settings)
+                });
 
                 // Service informieren
                 self.flash_release_service
-                    .set_hw_type(self.selected_hw_type);
+                    .set_hw_type(self.selected_hw_type, &settings);
                 self.flash_release_service.poll();
 
-                if self.selected_hw_type.is_some() {
+                if let Some(hw) = self.selected_hw_type {
                     let (releases, releases_loading, releases_error) =
                         self.flash_release_service.get_state();
                     ui.add_space(16.0);
                     ui.label("2. Select firmware:");
+                    let policy = self
+                        .firmware_policy
+                        .get_or_insert_with(|| policy::Policy::load(&settings))
+                        .clone();
+                    if policy.is_active() {
+                        ui.horizontal(|ui| {
+                            if policy.error.is_some() {
+                                ui.colored_label(egui::Color32::RED, policy.summary());
+                            } else {
+                                ui.weak(policy.summary());
+                            }
+                            if ui.small_button("Reload").clicked() {
+                                self.firmware_policy = None;
+                            }
+                        });
+                    }
                     if releases_loading {
                         ui.label("Loading firmware...");
                     } else if let Some(err) = &releases_error {
                         ui.colored_label(egui::Color32::RED, err);
                     } else if let Some(releases) = &releases {
+                        self.resolve_work_order(releases, &settings);
                         if releases.is_empty() {
                             ui.label("No firmware found.");
                         } else {
-                            for release in releases.iter() {
+                            let prereleases = settings.show_prereleases
+                                && self.allowed(operator::Permission::PrereleaseFirmware);
+                            if settings.show_prereleases && !prereleases {
+                                ui.weak(format!(
+                                    "Pre-releases are hidden: {} requires the {} role.",
+                                    operator::Permission::PrereleaseFirmware,
+                                    operator::Permission::PrereleaseFirmware.required_role()
+                                ));
+                            }
+                            for release in releases
+                                .iter()
+                                .filter(|r| prereleases || !r.prerelease)
+                            {
+                                let title = format!(
+                                    "{}{}",
+                                    release.tag_name,
+                                    if release.prerelease {
+                                        " (Pre-release)"
+                                    } else {
+                                        ""
+                                    }
+                                );
+                                // Gesperrte Releases ausgegraut mit Begründung
+                                if let Err(reason) = policy.check_release(hw, &release.tag_name) {
+                                    ui.horizontal(|ui| {
+                                        ui.add_enabled(false, egui::Label::new(title));
+                                        ui.weak(reason);
+                                    });
+                                    continue;
+                                }
                                 ui.collapsing(
-                                    format!(
-                                        "{}{}",
-                                        release.tag_name,
-                                        if release.prerelease {
-                                            " (Pre-release)"
-                                        } else {
-                                            ""
-                                        }
-                                    ),
+                                    title,
                                     |ui| {
+                                        let model = hw.model();
                                         for asset in &release.stm32_assets {
+                                            let allowed =
+                                                policy.check_asset(hw, &release.tag_name, asset);
                                             let is_selected = if let Some(sel) =
                                                 &self.selected_firmware
                                             {
-                                                sel.tag == release.tag_name && sel.asset == *asset
+                                                sel.tag == release.tag_name
+                                                    && sel.assets.contains(asset)
                                             } else {
                                                 false
                                             };
-                                            if ui.selectable_label(is_selected, asset).clicked() {
+                                            let region = if asset.to_ascii_lowercase().ends_with(".elf") {
+                                                "ELF segments"
+                                            } else {
+                                                model
+                                                    .image_region(asset)
+                                                    .map_or("complete image", |r| r.name.as_str())
+                                            };
+                                            let label = format!("{}  →  {}", asset, region);
+                                            let response = ui
+                                                .add_enabled_ui(allowed.is_ok(), |ui| {
+                                                    ui.selectable_label(is_selected, label)
+                                                })
+                                                .inner;
+                                            if let Err(reason) = &allowed {
+                                                ui.weak(reason);
+                                            }
+                                            if response.clicked() {
+                                                // Assets desselben Releases an- bzw. abwählen
+                                                let mut assets = match &self.selected_firmware {
+                                                    Some(sel) if sel.tag == release.tag_name => {
+                                                        sel.assets.clone()
+                                                    }
+                                                    _ => Vec::new(),
+                                                };
+                                                if is_selected {
+                                                    assets.retain(|a| a != asset);
+                                                } else {
+                                                    assets.push(asset.clone());
+                                                }
                                                 // Wenn eine neue Firmware gewählt wird, alles zurücksetzen
-                                                self.selected_firmware = Some(SelectedFirmware {
-                                                    tag: release.tag_name.clone(),
-                                                    asset: asset.clone(),
-                                                });
-                                                self.download_progress = None;
-                                                self.download_done = false;
-                                                self.download_error = None;
-                                                self.downloaded_path = None;
-                                                self.download_handle = None;
+                                                self.selected_firmware =
+                                                    (!assets.is_empty()).then(|| SelectedFirmware {
+                                                        tag: release.tag_name.clone(),
+                                                        assets,
+                                                    });
+                                                self.reset_download();
                                             }
                                         }
+                                        if let Some(notes) = &release.notes {
+                                            egui::CollapsingHeader::new("Release notes")
+                                                .id_salt(("release_notes", &release.tag_name))
+                                                .show(ui, |ui| ui.label(notes));
+                                        }
                                     },
                                 );
                             }
@@ -179,16 +748,19 @@ impl eframe::App for MyApp {
                                 && self.download_progress.is_none()
                                 && self.download_error.is_none()
                                 && !self.download_done
+                                && let Some(hw) = self.selected_hw_type
                             {
-                                if let Some(hw) = self.selected_hw_type {
-                                    let repo = hw.repo().to_string();
-                                    let tag = sel.tag.clone();
-                                    let asset = sel.asset.clone();
-                                    self.download_progress = Some(0); // Progressbar sofort anzeigen
-                                    self.download_handle = Some(
-                                        flash::FirmwareDownloadHandle::start(repo, tag, asset),
-                                    );
-                                }
+                                let repo = settings.firmware_source(hw);
+                                let tag = sel.tag.clone();
+                                let assets = sel.assets.clone();
+                                self.download_progress = Some(0); // Progressbar sofort anzeigen
+                                self.download_handle =
+                                    Some(flash::FirmwareDownloadHandle::start(
+                                        settings.clone(),
+                                        repo,
+                                        tag,
+                                        assets,
+                                    ));
                             }
                             // Download-Progressbar und Flash-Button
                             if let Some(handle) = &mut self.download_handle {
@@ -197,9 +769,29 @@ impl eframe::App for MyApp {
                                         flash::DownloadMsg::Progress(p) => {
                                             self.download_progress = Some(p);
                                         }
-                                        flash::DownloadMsg::Done(path) => {
+                                        flash::DownloadMsg::Done(paths) => {
                                             self.download_done = true;
-                                            self.downloaded_path = Some(path);
+                                            self.downloaded_images = match self
+                                                .selected_hw_type
+                                            {
+                                                Some(hw) => paths
+                                                    .iter()
+                                                    .map(|path| {
+                                                        let file = flash::ImageFile::for_model(
+                                                            path.clone(),
+                                                            hw,
+                                                        );
+                                                        image::FirmwareImage::load(
+                                                            std::path::Path::new(path),
+                                                            file.base,
+                                                        )
+                                                        .map_err(|e| e.to_string())
+                                                    })
+                                                    .collect(),
+                                                None => Vec::new(),
+                                            };
+                                            self.image_findings = None;
+                                            self.downloaded_paths = paths;
                                         }
                                         flash::DownloadMsg::Error(e) => {
                                             self.download_error = Some(e);
@@ -216,21 +808,199 @@ impl eframe::App for MyApp {
                                     );
                                 } else if self.download_done {
                                     ui.label("Download complete.");
+                                    for (path, image) in
+                                        self.downloaded_paths.iter().zip(&self.downloaded_images)
+                                    {
+                                        let name = std::path::Path::new(path)
+                                            .file_name()
+                                            .map(|n| n.to_string_lossy().into_owned())
+                                            .unwrap_or_default();
+                                        match image {
+                                            Ok(image) => {
+                                                ui.label(format!("{}: {}", name, image.summary()));
+                                                if image.is_elf
+                                                    && let Some(hw) = self.selected_hw_type
+                                                {
+                                                    for parameter in &hw.model().parameters {
+                                                        let (address, length) =
+                                                            parameter.resolve(Some(image));
+                                                        ui.label(format!(
+                                                            "    {} @ 0x{:08X} ({} bytes)",
+                                                            parameter.name, address, length
+                                                        ));
+                                                    }
+                                                }
+                                            }
+                                            Err(e) => {
+                                                ui.colored_label(
+                                                    egui::Color32::RED,
+                                                    format!("{}: {}", name, e),
+                                                );
+                                            }
+                                        }
+                                    }
                                     ui.add_space(16.0);
                                     ui.label("3. Flash firmware:");
-                                    if ui.button("Firmware jetzt flashen").clicked() {
-                                        if let Some(path) = &self.downloaded_path {
-                                            let config = flash::FlashConfig {
-                                                firmware_path: path.clone(),
-                                            };
-                                            let result = flash::flash_hardware(&config);
-                                            self.flash_result_message = Some(result.message);
+                                    let installed = self
+                                        .detected_identity
+                                        .as_ref()
+                                        .and_then(|i| i.firmware.as_ref());
+                                    let mut needs_confirmation = false;
+                                    match installed {
+                                        Some(firmware) => {
+                                            ui.label(format!(
+                                                "Installed: {}  →  Selected: {}",
+                                                firmware.summary(),
+                                                sel.tag
+                                            ));
+                                            let warning = self
+                                                .detected_identity
+                                                .as_ref()
+                                                .and_then(|i| flash::version_warning(i, &sel.tag));
+                                            if let Some(warning) = warning {
+                                                needs_confirmation = true;
+                                                ui.colored_label(egui::Color32::YELLOW, warning);
+                                                ui.checkbox(
+                                                    &mut self.confirm_version_change,
+                                                    "Flash anyway",
+                                                );
+                                            }
                                         }
+                                        None if self.detected_identity.is_some() => {
+                                            ui.label("Installed: no firmware");
+                                        }
+                                        None => {
+                                            ui.label(
+                                                "Installed firmware unknown (read the connected device first).",
+                                            );
+                                            // Die Version wird beim Flashen geprüft
+                                            ui.checkbox(
+                                                &mut self.confirm_version_change,
+                                                "Allow a downgrade or re-flashing the installed version",
+                                            );
+                                        }
+                                    }
+                                    if self.require(ui, operator::Permission::OverrideChecks) {
+                                        ui.checkbox(
+                                            &mut self.allow_protected_overwrite,
+                                            "Allow overwriting protected regions (configuration, calibration)",
+                                        );
+                                    }
+                                    if let Some(level) =
+                                        self.selected_hw_type.and_then(flash::production_rdp)
+                                    {
+                                        let allowed =
+                                            self.allowed(operator::Permission::OptionBytes);
+                                        ui.add_enabled(
+                                            allowed,
+                                            egui::Checkbox::new(
+                                                &mut self.skip_rdp,
+                                                format!(
+                                                    "Skip readout protection after flashing (policy: {}; development units only)",
+                                                    level
+                                                ),
+                                            ),
+                                        );
+                                    }
+                                    // Prüfung der Images vor dem Flashen
+                                    let images_ok =
+                                        self.downloaded_images.iter().all(|i| i.is_ok());
+                                    let findings = match self.selected_hw_type {
+                                        Some(hw) => self
+                                            .image_findings(hw, self.allow_protected_overwrite),
+                                        None => Vec::new(),
+                                    };
+                                    if images_ok && findings.is_empty() {
+                                        ui.colored_label(
+                                            egui::Color32::GREEN,
+                                            "Image checks passed.",
+                                        );
+                                    }
+                                    for finding in &findings {
+                                        let color = match finding.severity {
+                                            sanity::Severity::Error => egui::Color32::RED,
+                                            sanity::Severity::Warning => egui::Color32::YELLOW,
+                                        };
+                                        ui.colored_label(color, &finding.message);
+                                    }
+                                    let policy_check = self
+                                        .selected_firmware
+                                        .as_ref()
+                                        .map_or(Ok(()), |sel| {
+                                            policy.check_assets(hw, &sel.tag, &sel.assets)
+                                        });
+                                    if let Err(e) = &policy_check {
+                                        ui.colored_label(egui::Color32::RED, e.to_string());
+                                    }
+                                    let can_flash = images_ok
+                                        && policy_check.is_ok()
+                                        && !sanity::has_errors(&findings)
+                                        && (!needs_confirmation || self.confirm_version_change);
+                                    if ui
+                                        .add_enabled(
+                                            can_flash,
+                                            egui::Button::new("Firmware jetzt flashen"),
+                                        )
+                                        .clicked()
+                                        && let Some(hw) = self.selected_hw_type
+                                    {
+                                        let mut config = flash::FlashConfig::new(
+                                            &self.downloaded_paths,
+                                            hw,
+                                            &settings,
+                                        );
+                                        let may_override = self.allowed(
+                                            operator::Permission::OverrideChecks,
+                                        );
+                                        config.allow_model_mismatch =
+                                            self.allow_model_mismatch && may_override;
+                                        config.firmware_tag = self
+                                            .selected_firmware
+                                            .as_ref()
+                                            .map(|sel| sel.tag.clone());
+                                        config.allow_protected_overwrite =
+                                            self.allow_protected_overwrite && may_override;
+                                        config.allow_version_change =
+                                            self.confirm_version_change;
+                                        // Readout-Protection erst nach Seriennummer und
+                                        // Parametern setzen, wie im Produktionsmodus
+                                        let deferred_rdp = flash::production_rdp(hw)
+                                            .filter(|_| !self.skip_rdp);
+                                        let result = flash::flash_hardware(&config);
+                                        self.flash_failed = !result.success;
+                                        self.pending_rdp = deferred_rdp
+                                            .filter(|_| result.success)
+                                            .map(|level| (hw, level));
+                                        self.pending_rdp_message = None;
+                                        self.flash_diagnosis = result.hardware_error.then(|| {
+                                            diagnostics::diagnose(
+                                                &result.message,
+                                                &config.probe,
+                                            )
+                                        });
+                                        self.flash_result_message = Some(result.message);
+                                        // Neues Backup in der Liste anzeigen
+                                        self.backups = None;
+                                        self.selected_backup = None;
                                     }
                                     if let Some(msg) = &self.flash_result_message {
                                         ui.add_space(8.0);
                                         ui.label(msg);
                                     }
+                                    if let Some(diagnosis) = self.flash_diagnosis.clone() {
+                                        self.diagnosis_ui(ui, &diagnosis, settings.language);
+                                    }
+                                    self.pending_rdp_ui(ui, &settings);
+                                    if self.flash_failed
+                                        && ui
+                                            .add_enabled(
+                                                self.allowed(operator::Permission::OptionBytes),
+                                                egui::Button::new("Open recovery wizard"),
+                                            )
+                                            .clicked()
+                                    {
+                                        self.active_view = View::Recovery;
+                                    }
                                 }
                             }
                             if let Some(err) = &self.download_error {
@@ -240,15 +1010,9 @@ impl eframe::App for MyApp {
                     }
                 }
             }
-            View::SetSerial => {
-                ui.heading("Set serial number");
-            }
-            View::SetCapacity => {
-                ui.heading("Set capacity");
-            }
-            View::ReadSystem => {
-                ui.heading("Read system values");
-            }
+            View::SetSerial => self.serial_view(ui, &settings),
+            View::SetCapacity => self.capacity_view(ui, &settings),
+            View::ReadSystem => self.system_view(ui, &settings),
             View::AppUpdate => {
                 ui.heading("App update");
             }
@@ -256,22 +1020,53 @@ impl eframe::App for MyApp {
                 ui.heading("About this app");
                 ui.label(format!("iRockProgrammer v{}", env!("CARGO_PKG_VERSION")));
             }
-            View::Settings => {
-                ui.heading("Settings");
-            }
-            View::Help => {
-                ui.heading("Help / Manual");
-            }
+            View::Settings => self.settings_view(ui),
+            View::Recovery => self.recovery_view(ui, &settings),
+            View::Production => self.production_view(ui, &settings),
+            View::Audit => self.audit_view(ui, &settings),
+            View::History => self.history_view(ui, &settings),
+            View::Operators => self.operators_view(ui),
+            View::Bundles => self.bundles_view(ui, &settings),
+            View::Help => self.help_view(ui, settings.language),
         });
     }
 }
 
 fn main() -> eframe::Result<()> {
+    // Katalog zuerst laden, da die Kommandozeile Hardware-Typen referenziert
+    let user_catalogue = Settings::config_dir().map(|d| d.join(hardware::USER_CATALOGUE_FILE));
+    let catalogue_error = hardware::init(user_catalogue.as_deref()).err();
+    let (options, command) = cli::split_args(std::env::args().skip(1).collect());
+    let overrides = match Overrides::from_env_and_args(options) {
+        Ok(overrides) => overrides,
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(if msg == settings::USAGE { 0 } else { 2 });
+        }
+    };
+    match cli::Command::parse(&command) {
+        Ok(Some(command)) => {
+            if let Some(e) = &catalogue_error {
+                eprintln!("{}", e);
+            }
+            let settings = overrides.apply(&Settings::load().unwrap_or_default());
+            std::process::exit(command.run(
+                &settings,
+                overrides.operator.as_deref(),
+                overrides.pin.as_deref(),
+            ));
+        }
+        Ok(None) => {}
+        Err(msg) => {
+            eprintln!("{}", msg);
+            std::process::exit(2);
+        }
+    }
     let options = eframe::NativeOptions::default();
     eframe::run_native(
         "iRock Programmer",
         options,
         // ★ Hier muss das Boxed-Closure ein Result zurückgeben! ★
-        Box::new(|_cc| Ok(Box::new(MyApp::default()))),
+        Box::new(|_cc| Ok(Box::new(MyApp::new(overrides, catalogue_error)))),
     )
 }
@@ -0,0 +1,265 @@
+use crate::audit::{Action, AuditRecord};
+use crate::flash::{FlashConfig, open_session};
+use crate::hardware::{FlashRegion, HardwareType, MemoryRange};
+use crate::image::FirmwareImage;
+use std::io::Write;
+use std::path::Path;
+
+/// Abweichung zwischen Gerät und Firmware-Datei
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub range: MemoryRange,
+    /// Erste abweichende Adresse
+    pub first_address: u64,
+    pub differing_bytes: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub checked_bytes: usize,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl VerifyReport {
+    pub fn is_match(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        if self.is_match() {
+            return format!("Verify OK: {} bytes match", self.checked_bytes);
+        }
+        let details: Vec<String> = self
+            .mismatches
+            .iter()
+            .map(|m| {
+                format!(
+                    "{}: {} bytes differ, first at 0x{:08X}",
+                    m.range, m.differing_bytes, m.first_address
+                )
+            })
+            .collect();
+        format!("Verify failed: {}", details.join("; "))
+    }
+}
+
+/// Vergleicht den Flash des Geräts mit den Firmware-Dateien, ohne zu schreiben
+pub fn verify(config: &FlashConfig) -> anyhow::Result<VerifyReport> {
+    let mut record = AuditRecord::new(Action::Verify, Some(config.hw_type));
+    record.firmware = config.firmware_refs();
+    crate::audit::run(&config.settings, record, |record| {
+        verify_images(config, record)
+    })
+}
+
+fn verify_images(config: &FlashConfig, record: &mut AuditRecord) -> anyhow::Result<VerifyReport> {
+    let images = config
+        .images
+        .iter()
+        .map(|file| FirmwareImage::load(Path::new(&file.path), file.base))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if images.is_empty() {
+        anyhow::bail!("Keine Firmware ausgewählt");
+    }
+    let (mut backend, identity) = open_session(config)?;
+    record.device(backend.as_ref(), &identity);
+    let mut report = VerifyReport::default();
+    for segment in images.iter().flat_map(|i| &i.segments) {
+        let mut data = vec![0u8; segment.data.len()];
+        backend.read(segment.address, &mut data)?;
+        report.checked_bytes += data.len();
+        let differing: Vec<usize> = data
+            .iter()
+            .zip(&segment.data)
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, _)| i)
+            .collect();
+        if let Some(first) = differing.first() {
+            report.mismatches.push(Mismatch {
+                range: segment.range(),
+                first_address: segment.address + *first as u64,
+                differing_bytes: differing.len(),
+            });
+        }
+    }
+    record.success = report.is_match();
+    record.details = report.summary();
+    Ok(report)
+}
+
+/// Lehnt Bereiche ab, die nicht vollständig im Flash liegen
+fn check_flash_range(hw_type: HardwareType, range: MemoryRange) -> anyhow::Result<()> {
+    let flash = hw_type.model().flash;
+    if !range.within(&flash) {
+        anyhow::bail!(
+            "0x{:08X} + 0x{:X} liegt nicht im Flash {}",
+            range.base,
+            range.size,
+            flash
+        );
+    }
+    Ok(())
+}
+
+/// Geschützte Bereiche, deren Sektoren beim Löschen von `range` (ohne: ganzer
+/// Chip) mitgelöscht würden
+pub fn protected_in_erase(
+    hw_type: HardwareType,
+    range: Option<MemoryRange>,
+) -> Vec<&'static FlashRegion> {
+    let model = hw_type.model();
+    let sectors = model.sectors(range.unwrap_or(model.flash));
+    model
+        .protected_regions()
+        .filter(|r| sectors.iter().any(|s| s.overlaps(&r.range)))
+        .collect()
+}
+
+/// Liest einen Speicherbereich aus und speichert ihn als `.bin` oder `.hex`
+pub fn read_out(config: &FlashConfig, range: MemoryRange, path: &Path) -> anyhow::Result<()> {
+    check_flash_range(config.hw_type, range)?;
+    let record = AuditRecord::new(Action::ReadOut, Some(config.hw_type));
+    let data = crate::audit::run(&config.settings, record, |record| {
+        let (mut backend, identity) = open_session(config)?;
+        record.device(backend.as_ref(), &identity);
+        record.details = format!("{} → {}", range, path.display());
+        let mut data = vec![0u8; range.size as usize];
+        backend.read(range.base, &mut data)?;
+        Ok(data)
+    })?;
+    let is_hex = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("hex") || e.eq_ignore_ascii_case("ihex"));
+    if is_hex {
+        let mut file = std::fs::File::create(path)?;
+        write_intel_hex(&mut file, range.base, &data)?;
+    } else {
+        std::fs::write(path, &data)?;
+    }
+    Ok(())
+}
+
+/// Löscht einen Flash-Bereich oder, ohne `range`, den gesamten Chip. Geschützte
+/// Bereiche nur mit `allow_protected_overwrite`.
+pub fn erase(config: &FlashConfig, range: Option<MemoryRange>) -> anyhow::Result<()> {
+    if let Some(range) = range {
+        check_flash_range(config.hw_type, range)?;
+    }
+    let protected = protected_in_erase(config.hw_type, range);
+    if !protected.is_empty() && !config.allow_protected_overwrite {
+        anyhow::bail!(
+            "Löschen würde geschützte Bereiche zerstören: {}",
+            protected
+                .iter()
+                .map(|r| r.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    let record = AuditRecord::new(Action::Erase, Some(config.hw_type));
+    crate::audit::run(&config.settings, record, |record| {
+        let (mut backend, identity) = open_session(config)?;
+        record.device(backend.as_ref(), &identity);
+        record.details = range.map_or("chip".to_string(), |r| r.to_string());
+        backend.erase(range)
+    })
+}
+
+/// Intel-HEX mit Extended-Linear-Address-Records für 32-Bit-Adressen
+fn write_intel_hex(out: &mut impl Write, base: u64, data: &[u8]) -> std::io::Result<()> {
+    let mut upper = None;
+    for (i, chunk) in data.chunks(16).enumerate() {
+        let address = base + (i * 16) as u64;
+        let high = (address >> 16) as u16;
+        if upper != Some(high) {
+            write_hex_record(out, 0, 0x04, &high.to_be_bytes())?;
+            upper = Some(high);
+        }
+        write_hex_record(out, address as u16, 0x00, chunk)?;
+    }
+    write_hex_record(out, 0, 0x01, &[])
+}
+
+fn write_hex_record(
+    out: &mut impl Write,
+    address: u16,
+    kind: u8,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut record = vec![data.len() as u8];
+    record.extend_from_slice(&address.to_be_bytes());
+    record.push(kind);
+    record.extend_from_slice(data);
+    let checksum = record
+        .iter()
+        .fold(0u8, |sum, b| sum.wrapping_add(*b))
+        .wrapping_neg();
+    record.push(checksum);
+    let hex: String = record.iter().map(|b| format!("{:02X}", b)).collect();
+    writeln!(out, ":{}", hex)
+}
+
+/// Adresse oder Größe, dezimal oder hexadezimal mit `0x`
+pub fn parse_number(text: &str) -> Option<u64> {
+    let text = text.trim().replace('_', "");
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::read;
+
+    #[test]
+    fn erase_needs_override_for_protected_regions() {
+        let device = crate::testing::device();
+        let calibration = device.region("calibration");
+        let mut backend = device.connect();
+        backend.flash(&[(calibration.base, &[0x42; 64])]).unwrap();
+        drop(backend);
+
+        let mut config = device.flash_config(&[]);
+        assert!(erase(&config, Some(calibration)).is_err());
+        assert!(erase(&config, None).is_err());
+        let data = read(device.connect().as_mut(), calibration).unwrap();
+        assert_eq!(&data[..64], &[0x42; 64]);
+
+        erase(&config, Some(device.region("application"))).unwrap();
+        config.allow_protected_overwrite = true;
+        erase(&config, Some(calibration)).unwrap();
+        let data = read(device.connect().as_mut(), calibration).unwrap();
+        assert!(data.iter().all(|b| *b == 0xFF));
+    }
+
+    #[test]
+    fn read_out_rejects_ranges_outside_flash() {
+        let device = crate::testing::device();
+        let config = device.flash_config(&[]);
+        let path = device.dir.path().join("dump.bin");
+        let flash = device.hw_type.model().flash;
+        for range in [
+            MemoryRange {
+                base: u64::MAX - 4,
+                size: 0x100,
+            },
+            MemoryRange {
+                base: flash.base,
+                size: flash.size + 1,
+            },
+            MemoryRange {
+                base: flash.base,
+                size: 0,
+            },
+        ] {
+            assert!(read_out(&config, range, &path).is_err());
+        }
+        assert!(!path.exists());
+        let config_region = device.region("config");
+        read_out(&config, config_region, &path).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), config_region.size);
+    }
+}
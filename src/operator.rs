@@ -0,0 +1,301 @@
+//! Lokale Bedienerprofile mit Rolle und PIN
+
+use crate::settings::Settings;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const OPERATORS_FILE: &str = "operators.toml";
+/// Zulässige PIN-Länge (nur Ziffern)
+const PIN_LENGTH: std::ops::RangeInclusive<usize> = 4..=12;
+/// PBKDF2-HMAC-SHA256-Runden für neue PINs
+const PIN_ITERATIONS: u32 = 600_000;
+
+/// Angemeldeter Bediener; auch aus Hintergrund-Threads für das Audit-Log lesbar
+static CURRENT: Mutex<Option<String>> = Mutex::new(None);
+
+/// Rollen, aufsteigend nach Berechtigungen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Flashen und Produktionsmodus mit freigegebener Firmware
+    Operator,
+    /// Zusätzlich Seriennummer und Kapazität ändern, Flash auslesen und löschen,
+    /// Backups wiederherstellen, Sicherheitsprüfungen übergehen, Firmware-Pakete
+    /// importieren
+    Technician,
+    /// Alles, inklusive Pre-releases, Option-Bytes, Einstellungen und Bedienerverwaltung
+    Engineer,
+}
+
+impl Role {
+    pub fn all() -> &'static [Role] {
+        &[Role::Operator, Role::Technician, Role::Engineer]
+    }
+
+    pub fn allows(self, permission: Permission) -> bool {
+        self >= permission.required_role()
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Role::Operator => "Operator",
+            Role::Technician => "Technician",
+            Role::Engineer => "Engineer",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Vorgänge, die eine bestimmte Rolle erfordern. Vergabe von Seriennummern im
+/// Produktionsmodus gehört zum normalen Ablauf und ist nicht eingeschränkt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    PrereleaseFirmware,
+    SetSerial,
+    SetCapacity,
+    OptionBytes,
+    /// Flash auslesen und löschen
+    ReadOutErase,
+    /// Backup auf ein Gerät zurückschreiben
+    RestoreBackup,
+    /// Trotz abweichendem Modell oder über geschützte Bereiche flashen
+    OverrideChecks,
+    /// Offline-Firmware-Paket als Firmware-Quelle eintragen
+    ImportBundle,
+    ExportBundle,
+    /// Einstellungen und Bedienerprofile
+    Settings,
+}
+
+impl Permission {
+    pub fn required_role(self) -> Role {
+        match self {
+            Permission::SetSerial
+            | Permission::SetCapacity
+            | Permission::ReadOutErase
+            | Permission::RestoreBackup
+            | Permission::OverrideChecks
+            | Permission::ImportBundle => Role::Technician,
+            Permission::PrereleaseFirmware
+            | Permission::OptionBytes
+            | Permission::ExportBundle
+            | Permission::Settings => Role::Engineer,
+        }
+    }
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Permission::PrereleaseFirmware => "Pre-release firmware",
+            Permission::SetSerial => "Changing serial numbers",
+            Permission::SetCapacity => "Changing the capacity",
+            Permission::OptionBytes => "Changing option bytes",
+            Permission::ReadOutErase => "Reading out and erasing flash",
+            Permission::RestoreBackup => "Restoring backups",
+            Permission::OverrideChecks => "Overriding the model and protected region checks",
+            Permission::ImportBundle => "Importing firmware bundles",
+            Permission::ExportBundle => "Exporting firmware bundles",
+            Permission::Settings => "Changing settings and operator profiles",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Bedienerprofil; die PIN wird nur als PBKDF2-Hash mit zufälligem Salt
+/// gespeichert. Die kurze Ziffern-PIN schützt trotzdem nur gegen Gelegenheitszugriff,
+/// nicht gegen jemanden, der `operators.toml` kopieren kann.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Operator {
+    pub name: String,
+    pub role: Role,
+    salt: String,
+    pin_hash: String,
+    /// PBKDF2-Runden
+    iterations: u32,
+}
+
+impl Operator {
+    pub fn new(name: &str, role: Role, pin: &str) -> anyhow::Result<Operator> {
+        let name = name.trim();
+        if name.is_empty() {
+            anyhow::bail!("Name fehlt");
+        }
+        let mut operator = Operator {
+            name: name.to_string(),
+            role,
+            salt: String::new(),
+            pin_hash: String::new(),
+            iterations: PIN_ITERATIONS,
+        };
+        operator.set_pin(pin)?;
+        Ok(operator)
+    }
+
+    pub fn set_pin(&mut self, pin: &str) -> anyhow::Result<()> {
+        if !PIN_LENGTH.contains(&pin.len()) || !pin.chars().all(|c| c.is_ascii_digit()) {
+            anyhow::bail!(
+                "Die PIN muss aus {} bis {} Ziffern bestehen",
+                PIN_LENGTH.start(),
+                PIN_LENGTH.end()
+            );
+        }
+        let mut salt = [0u8; 16];
+        getrandom::fill(&mut salt)
+            .map_err(|e| anyhow::anyhow!("Keine Zufallszahlen verfügbar: {}", e))?;
+        self.salt = hex(&salt);
+        self.iterations = PIN_ITERATIONS;
+        self.pin_hash = pin_hash(&self.salt, pin, self.iterations);
+        Ok(())
+    }
+
+    pub fn check_pin(&self, pin: &str) -> bool {
+        self.iterations > 0 && pin_hash(&self.salt, pin, self.iterations) == self.pin_hash
+    }
+
+    pub fn allows(&self, permission: Permission) -> bool {
+        self.role.allows(permission)
+    }
+}
+
+fn pin_hash(salt: &str, pin: &str, iterations: u32) -> String {
+    let mut hash = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(pin.as_bytes(), salt.as_bytes(), iterations, &mut hash);
+    hex(&hash)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Alle Bedienerprofile, gespeichert als TOML im Konfigurationsverzeichnis
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Operators {
+    #[serde(default)]
+    pub operators: Vec<Operator>,
+}
+
+impl Operators {
+    pub fn path(settings: &Settings) -> anyhow::Result<PathBuf> {
+        Ok(settings.data_dir()?.join(OPERATORS_FILE))
+    }
+
+    /// Lädt die Profile; fehlt die Datei, gibt es noch keine
+    pub fn load(settings: &Settings) -> anyhow::Result<Operators> {
+        let path = Self::path(settings)?;
+        if !path.exists() {
+            return Ok(Operators::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        toml::from_str(&content).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, settings: &Settings) -> anyhow::Result<()> {
+        let path = Self::path(settings)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Operator> {
+        self.operators
+            .iter()
+            .find(|o| o.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Prüft Name und PIN
+    pub fn login(&self, name: &str, pin: &str) -> anyhow::Result<Operator> {
+        self.get(name)
+            .filter(|o| o.check_pin(pin))
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unbekannter Bediener oder falsche PIN"))
+    }
+
+    /// Legt ein Profil an oder ersetzt das gleichnamige
+    pub fn upsert(&mut self, operator: Operator) -> anyhow::Result<()> {
+        let mut operators = self.operators.clone();
+        match operators
+            .iter_mut()
+            .find(|o| o.name.eq_ignore_ascii_case(&operator.name))
+        {
+            Some(existing) => *existing = operator,
+            None => operators.push(operator),
+        }
+        Self::check_engineer(&operators)?;
+        self.operators = operators;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> anyhow::Result<()> {
+        let mut operators = self.operators.clone();
+        operators.retain(|o| !o.name.eq_ignore_ascii_case(name));
+        Self::check_engineer(&operators)?;
+        self.operators = operators;
+        Ok(())
+    }
+
+    /// Ohne Engineer könnte niemand mehr Profile und Einstellungen ändern
+    fn check_engineer(operators: &[Operator]) -> anyhow::Result<()> {
+        if !operators.iter().any(|o| o.role == Role::Engineer) {
+            anyhow::bail!("Es muss mindestens ein Engineer-Profil bestehen bleiben");
+        }
+        Ok(())
+    }
+}
+
+/// Setzt den angemeldeten Bediener
+pub fn set_current(name: Option<&str>) {
+    *CURRENT.lock().unwrap_or_else(|e| e.into_inner()) = name.map(str::to_string);
+}
+
+/// Angemeldeter Bediener, sonst der Benutzer des Betriebssystems (Kommandozeile)
+pub fn current() -> Option<String> {
+    CURRENT
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .or_else(|| {
+            std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pin_is_salted_and_checked() {
+        let a = Operator::new("Anna", Role::Operator, "1234").unwrap();
+        let b = Operator::new("Anna", Role::Operator, "1234").unwrap();
+        assert_ne!(a.salt, b.salt);
+        assert_ne!(a.pin_hash, b.pin_hash);
+        assert!(a.check_pin("1234"));
+        assert!(!a.check_pin("4321"));
+    }
+
+    #[test]
+    fn invalid_pins_are_rejected() {
+        assert!(Operator::new("Anna", Role::Operator, "12").is_err());
+        assert!(Operator::new("Anna", Role::Operator, "12ab").is_err());
+        assert!(Operator::new(" ", Role::Operator, "1234").is_err());
+    }
+
+    #[test]
+    fn roles_grant_permissions() {
+        assert!(!Role::Operator.allows(Permission::ReadOutErase));
+        assert!(Role::Technician.allows(Permission::ReadOutErase));
+        assert!(!Role::Technician.allows(Permission::OptionBytes));
+        assert!(Role::Engineer.allows(Permission::OptionBytes));
+        assert!(!Role::Operator.allows(Permission::RestoreBackup));
+        assert!(!Role::Operator.allows(Permission::OverrideChecks));
+        assert!(Role::Technician.allows(Permission::OverrideChecks));
+    }
+}
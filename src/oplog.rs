@@ -0,0 +1,39 @@
+use chrono::{DateTime, Local};
+use std::sync::Mutex;
+
+/// Ältere Einträge werden verworfen
+const MAX_ENTRIES: usize = 1000;
+
+static LOG: Mutex<Vec<LogEntry>> = Mutex::new(Vec::new());
+
+/// Eintrag im Vorgangsprotokoll (Verbindungsversuche, Wiederholungen, Ergebnisse)
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: DateTime<Local>,
+    pub message: String,
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}  {}", self.time.format("%H:%M:%S"), self.message)
+    }
+}
+
+pub fn record(message: impl Into<String>) {
+    let mut log = LOG.lock().unwrap_or_else(|e| e.into_inner());
+    if log.len() >= MAX_ENTRIES {
+        log.remove(0);
+    }
+    log.push(LogEntry {
+        time: Local::now(),
+        message: message.into(),
+    });
+}
+
+pub fn entries() -> Vec<LogEntry> {
+    LOG.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn clear() {
+    LOG.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
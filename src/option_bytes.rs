@@ -0,0 +1,260 @@
+use crate::backend::ProgrammerBackend;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+// Flash-Interface der STM32F4 (RM0090/RM0368)
+pub(crate) const FLASH_OPTKEYR: u64 = 0x4002_3C08;
+pub(crate) const FLASH_SR: u64 = 0x4002_3C0C;
+pub(crate) const FLASH_OPTCR: u64 = 0x4002_3C14;
+pub(crate) const OPTKEY1: u32 = 0x0819_2A3B;
+pub(crate) const OPTKEY2: u32 = 0x4C5D_6E7F;
+pub(crate) const OPTCR_OPTLOCK: u32 = 1 << 0;
+pub(crate) const OPTCR_OPTSTRT: u32 = 1 << 1;
+const SR_BSY: u32 = 1 << 16;
+/// Eine RDP-Regression löscht den gesamten Flash und dauert entsprechend lange
+const OPTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Option-Byte-Layout des Mikrocontrollers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionBytesKind {
+    Stm32f4,
+}
+
+/// Readout-Protection-Stufe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[serde(try_from = "u8")]
+pub enum RdpLevel {
+    #[default]
+    Level0,
+    Level1,
+    /// Dauerhaft, das Debug-Interface ist danach abgeschaltet
+    Level2,
+}
+
+impl RdpLevel {
+    pub(crate) fn from_byte(byte: u8) -> Self {
+        match byte {
+            0xAA => RdpLevel::Level0,
+            0xCC => RdpLevel::Level2,
+            _ => RdpLevel::Level1,
+        }
+    }
+
+    fn byte(self) -> u8 {
+        match self {
+            RdpLevel::Level0 => 0xAA,
+            RdpLevel::Level1 => 0x55,
+            RdpLevel::Level2 => 0xCC,
+        }
+    }
+}
+
+impl TryFrom<u8> for RdpLevel {
+    type Error = String;
+
+    fn try_from(level: u8) -> Result<Self, Self::Error> {
+        match level {
+            0 => Ok(RdpLevel::Level0),
+            1 => Ok(RdpLevel::Level1),
+            // Stufe 2 ist irreversibel und wird deshalb nicht automatisch gesetzt
+            2 => Err("RDP Level 2 wird nicht unterstützt (irreversibel)".to_string()),
+            _ => Err(format!("Ungültige RDP-Stufe: {}", level)),
+        }
+    }
+}
+
+impl std::fmt::Display for RdpLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            RdpLevel::Level0 => "Level 0 (no protection)",
+            RdpLevel::Level1 => "Level 1 (readout protection)",
+            RdpLevel::Level2 => "Level 2 (permanent, debug disabled)",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Inhalt von FLASH_OPTCR
+#[derive(Debug, Clone, Copy)]
+pub struct OptionBytes {
+    pub raw: u32,
+}
+
+impl OptionBytes {
+    pub fn rdp(&self) -> RdpLevel {
+        RdpLevel::from_byte((self.raw >> 8) as u8)
+    }
+
+    pub fn bor_level(&self) -> u32 {
+        (self.raw >> 2) & 0b11
+    }
+
+    /// Bits für den Schreibschutz je Sektor (1 = nicht geschützt)
+    pub fn nwrp(&self) -> u32 {
+        (self.raw >> 16) & 0xFFF
+    }
+
+    /// Anzeige der einzelnen Felder
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let flag = |bit: u32| (self.raw >> bit & 1).to_string();
+        vec![
+            ("FLASH_OPTCR", format!("0x{:08X}", self.raw)),
+            ("RDP", self.rdp().to_string()),
+            ("BOR_LEV", self.bor_level().to_string()),
+            ("WDG_SW", flag(5)),
+            ("nRST_STOP", flag(6)),
+            ("nRST_STDBY", flag(7)),
+            ("nWRP", format!("0b{:012b}", self.nwrp())),
+        ]
+    }
+}
+
+/// Liest die Option-Bytes; funktioniert auch bei aktiver Readout-Protection
+pub fn read(
+    backend: &mut dyn ProgrammerBackend,
+    kind: OptionBytesKind,
+) -> anyhow::Result<OptionBytes> {
+    match kind {
+        OptionBytesKind::Stm32f4 => Ok(OptionBytes {
+            raw: backend.read_word_32(FLASH_OPTCR)?,
+        }),
+    }
+}
+
+/// Setzt die RDP-Stufe. Eine Regression auf Level 0 löscht den gesamten Flash.
+/// Die neue Stufe wird erst nach einem Power-Cycle wirksam.
+pub fn set_rdp(
+    backend: &mut dyn ProgrammerBackend,
+    kind: OptionBytesKind,
+    level: RdpLevel,
+) -> anyhow::Result<()> {
+    if level == RdpLevel::Level2 {
+        anyhow::bail!("RDP Level 2 wird nicht unterstützt (irreversibel)");
+    }
+    write_optcr(backend, kind, |optcr| {
+        (optcr & !0xFF00) | (level.byte() as u32) << 8
+    })
+}
+
+/// nWRP-Bits der vorhandenen Sektoren; die übrigen sind bei kleineren
+/// Bausteinen (z.B. STM32F401) reserviert und bleiben unverändert
+fn nwrp_mask(sectors: usize) -> u32 {
+    (1u32 << sectors.min(12)) - 1
+}
+
+/// Setzt RDP auf Level 0 und hebt den Schreibschutz der `sectors` Sektoren auf
+pub fn recover(
+    backend: &mut dyn ProgrammerBackend,
+    kind: OptionBytesKind,
+    sectors: usize,
+) -> anyhow::Result<()> {
+    write_optcr(backend, kind, |optcr| {
+        (optcr & !0xFF00) | (RdpLevel::Level0.byte() as u32) << 8 | nwrp_mask(sectors) << 16
+    })
+}
+
+/// Entsperrt die Option-Bytes, schreibt den geänderten Wert und sperrt wieder
+fn write_optcr(
+    backend: &mut dyn ProgrammerBackend,
+    kind: OptionBytesKind,
+    change: impl FnOnce(u32) -> u32,
+) -> anyhow::Result<()> {
+    match kind {
+        OptionBytesKind::Stm32f4 => {
+            backend.halt()?;
+            wait_not_busy(backend)?;
+            if backend.read_word_32(FLASH_OPTCR)? & OPTCR_OPTLOCK != 0 {
+                backend.write_word_32(FLASH_OPTKEYR, OPTKEY1)?;
+                backend.write_word_32(FLASH_OPTKEYR, OPTKEY2)?;
+            }
+            let optcr = backend.read_word_32(FLASH_OPTCR)?;
+            if optcr & OPTCR_OPTLOCK != 0 {
+                anyhow::bail!("Option-Bytes konnten nicht entsperrt werden");
+            }
+            let optcr = change(optcr) & !(OPTCR_OPTLOCK | OPTCR_OPTSTRT);
+            backend.write_word_32(FLASH_OPTCR, optcr)?;
+            backend.write_word_32(FLASH_OPTCR, optcr | OPTCR_OPTSTRT)?;
+            wait_not_busy(backend)?;
+            backend.write_word_32(FLASH_OPTCR, optcr | OPTCR_OPTLOCK)?;
+            Ok(())
+        }
+    }
+}
+
+fn wait_not_busy(backend: &mut dyn ProgrammerBackend) -> anyhow::Result<()> {
+    let start = Instant::now();
+    while backend.read_word_32(FLASH_SR)? & SR_BSY != 0 {
+        if start.elapsed() > OPTION_TIMEOUT {
+            anyhow::bail!("Zeitüberschreitung beim Schreiben der Option-Bytes");
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::SimulatedDevice;
+
+    #[test]
+    fn rdp1_blocks_reads_after_power_cycle() {
+        let device = crate::testing::device();
+        let kind = device.hw_type.model().option_bytes.unwrap();
+        let mut backend = device.connect();
+        set_rdp(backend.as_mut(), kind, RdpLevel::Level1).unwrap();
+        assert_eq!(
+            read(backend.as_mut(), kind).unwrap().rdp(),
+            RdpLevel::Level1
+        );
+        // Bis zum Power-Cycle bleibt der Flash lesbar
+        assert!(crate::testing::read(backend.as_mut(), device.region("config")).is_ok());
+
+        SimulatedDevice::power_cycle(device.hw_type);
+        let mut backend = device.connect();
+        assert!(crate::testing::read(backend.as_mut(), device.region("config")).is_err());
+        assert!(
+            backend
+                .flash(&[(device.region("application").base, &[0; 4])])
+                .is_err()
+        );
+        // Option-Bytes bleiben auch mit Readout-Protection lesbar
+        assert!(read(backend.as_mut(), kind).is_ok());
+    }
+
+    #[test]
+    fn nwrp_mask_covers_existing_sectors() {
+        let sectors = |id: &str| {
+            let model = crate::hardware::HardwareType::from_id(id).unwrap().model();
+            model.sectors(model.flash).len()
+        };
+        // STM32F405 mit 1 MiB: 12 Sektoren, STM32F401RC mit 256 KiB: 6 Sektoren
+        assert_eq!(nwrp_mask(sectors("irock-424")), 0xFFF);
+        assert_eq!(nwrp_mask(sectors("irock-212")), 0x3F);
+    }
+
+    #[test]
+    fn rdp_regression_mass_erases() {
+        let device = crate::testing::device();
+        let kind = device.hw_type.model().option_bytes.unwrap();
+        let mut backend = device.connect();
+        set_rdp(backend.as_mut(), kind, RdpLevel::Level1).unwrap();
+        SimulatedDevice::power_cycle(device.hw_type);
+
+        let mut backend = device.connect();
+        let flash = device.hw_type.model().flash;
+        recover(
+            backend.as_mut(),
+            kind,
+            device.hw_type.model().sectors(flash).len(),
+        )
+        .unwrap();
+        let flash = crate::testing::read(backend.as_mut(), device.hw_type.model().flash).unwrap();
+        assert!(flash.iter().all(|b| *b == 0xFF));
+        assert_eq!(
+            read(backend.as_mut(), kind).unwrap().rdp(),
+            RdpLevel::Level0
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use crate::backend::ProgrammerBackend;
+use crate::hardware::{HardwareType, MemoryRange, Parameter, ParameterKind};
+use crate::image::FirmwareImage;
+
+/// Wert eines Geräteparameters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParameterValue {
+    U32(u32),
+    String(String),
+}
+
+impl std::fmt::Display for ParameterValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParameterValue::U32(v) => write!(f, "{}", v),
+            ParameterValue::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+fn find(hw_type: HardwareType, name: &str) -> anyhow::Result<&'static Parameter> {
+    hw_type
+        .model()
+        .parameter(name)
+        .ok_or_else(|| anyhow::anyhow!("{} hat keinen Parameter \"{}\"", hw_type, name))
+}
+
+fn encode(parameter: &Parameter, length: usize, value: &ParameterValue) -> anyhow::Result<Vec<u8>> {
+    match (parameter.kind, value) {
+        (ParameterKind::U32, ParameterValue::U32(v)) => Ok(v.to_le_bytes().to_vec()),
+        (ParameterKind::String, ParameterValue::String(s)) => {
+            if !s.is_ascii() || s.contains('\0') {
+                anyhow::bail!("{} darf nur ASCII-Zeichen enthalten", parameter.name);
+            }
+            if s.len() > length {
+                anyhow::bail!("{} ist zu lang (max. {} Zeichen)", parameter.name, length);
+            }
+            let mut raw = vec![0u8; length];
+            raw[..s.len()].copy_from_slice(s.as_bytes());
+            Ok(raw)
+        }
+        _ => anyhow::bail!("Falscher Typ für Parameter {}", parameter.name),
+    }
+}
+
+/// Liest einen Parameter; `None` bei gelöschtem Flash.
+/// Bei ELF-Firmware wird die Adresse über das Symbol aufgelöst.
+pub fn read(
+    backend: &mut dyn ProgrammerBackend,
+    hw_type: HardwareType,
+    name: &str,
+    image: Option<&FirmwareImage>,
+) -> anyhow::Result<Option<ParameterValue>> {
+    let parameter = find(hw_type, name)?;
+    let (address, length) = parameter.resolve(image);
+    Ok(match parameter.kind {
+        ParameterKind::U32 => {
+            let value = backend.read_word_32(address)?;
+            (value != u32::MAX).then_some(ParameterValue::U32(value))
+        }
+        ParameterKind::String => {
+            let mut raw = vec![0u8; length];
+            backend.read(address, &mut raw)?;
+            crate::device::decode_string_parameter(&raw).map(ParameterValue::String)
+        }
+    })
+}
+
+/// Schreibt einen Parameter und prüft ihn durch Zurücklesen. Da das Flashen den
+/// Sektor löscht, wird der gesamte umgebende Bereich gelesen und neu geschrieben.
+pub fn write(
+    backend: &mut dyn ProgrammerBackend,
+    hw_type: HardwareType,
+    name: &str,
+    value: &ParameterValue,
+    image: Option<&FirmwareImage>,
+) -> anyhow::Result<()> {
+    let model = hw_type.model();
+    let parameter = find(hw_type, name)?;
+    let (address, length) = parameter.resolve(image);
+    let raw = encode(parameter, length, value)?;
+    let field = MemoryRange {
+        base: address,
+        size: raw.len() as u64,
+    };
+    let block = model
+        .regions
+        .iter()
+        .map(|r| r.range)
+        .find(|r| r.contains(field.base) && field.end() <= r.end())
+        .unwrap_or(field);
+    let mut data = vec![0u8; block.size as usize];
+    backend.read(block.base, &mut data)?;
+    let offset = (field.base - block.base) as usize;
+    data[offset..offset + raw.len()].copy_from_slice(&raw);
+    backend.flash(&[(block.base, &data)])?;
+
+    let mut check = vec![0u8; raw.len()];
+    backend.read(field.base, &mut check)?;
+    if check != raw {
+        anyhow::bail!("Parameter {} konnte nicht geschrieben werden", name);
+    }
+    Ok(())
+}
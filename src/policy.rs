@@ -0,0 +1,198 @@
+//! Freigabeliste für Firmware in der Produktion: erlaubte Releases und Assets
+//! je Hardware-Typ sowie gesperrte Versionen, optional mit Ed25519 signiert
+//!
+//! ```toml
+//! [[allow]]
+//! hw_type = "irock-424"
+//! tags = ["v2.*"]
+//! assets = ["*.elf"]
+//!
+//! [[block]]
+//! tag = "v2.3.0"
+//! reason = "Akku-Kalibrierung fehlerhaft"
+//! ```
+
+use crate::hardware::{HardwareType, wildcard_match};
+use crate::settings::Settings;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Endung der Signaturdatei neben der Freigabeliste
+pub const SIGNATURE_EXTENSION: &str = "sig";
+
+/// Erlaubte Releases eines Hardware-Typs (Platzhalter `*` und `?` erlaubt)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllowRule {
+    pub hw_type: String,
+    pub tags: Vec<String>,
+    /// Leer = alle Assets der erlaubten Releases
+    #[serde(default)]
+    pub assets: Vec<String>,
+}
+
+/// Gesperrte Version, hat Vorrang vor den Freigaben
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRule {
+    #[serde(default = "any")]
+    pub hw_type: String,
+    pub tag: String,
+    /// Nur dieses Asset sperren, sonst das ganze Release
+    #[serde(default)]
+    pub asset: Option<String>,
+    pub reason: String,
+}
+
+fn any() -> String {
+    "*".to_string()
+}
+
+/// Geladene Freigabeliste. Ohne `allow`-Einträge ist jede nicht gesperrte
+/// Firmware erlaubt; gibt es Einträge, nur die aufgeführte.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Policy {
+    #[serde(default)]
+    pub allow: Vec<AllowRule>,
+    #[serde(default)]
+    pub block: Vec<BlockRule>,
+    /// Datei, aus der die Liste stammt; `None` = keine Freigabeliste eingestellt
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+    #[serde(skip)]
+    pub signed: bool,
+    /// Liste nicht lesbar oder Signatur ungültig: dann ist jede Firmware gesperrt
+    #[serde(skip)]
+    pub error: Option<String>,
+}
+
+impl Policy {
+    /// Freigabeliste laut Einstellungen. Fehler werden nicht gemeldet, sondern
+    /// sperren jede Firmware, damit die Produktion nicht unbemerkt ungeschützt läuft.
+    pub fn load(settings: &Settings) -> Policy {
+        let Some(path) = settings.firmware_policy.clone() else {
+            return Policy::default();
+        };
+        match read(&path, settings.policy_public_key.as_deref()) {
+            Ok(policy) => policy,
+            Err(e) => Policy {
+                source: Some(path.clone()),
+                error: Some(format!("Freigabeliste {}: {:#}", path.display(), e)),
+                ..Policy::default()
+            },
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.source.is_some()
+    }
+
+    /// Kurzbeschreibung für die Anzeige
+    pub fn summary(&self) -> String {
+        match (&self.source, &self.error) {
+            (None, _) => "No firmware policy".to_string(),
+            (Some(_), Some(e)) => e.clone(),
+            (Some(path), None) => format!(
+                "Firmware policy {} ({}, {} allowed, {} blocked)",
+                path.display(),
+                if self.signed { "signed" } else { "unsigned" },
+                self.allow.len(),
+                self.block.len()
+            ),
+        }
+    }
+
+    /// Prüft ein Release; `Err` mit dem Grund, wenn es gesperrt oder nicht freigegeben ist
+    pub fn check_release(&self, hw_type: HardwareType, tag: &str) -> Result<(), String> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+        if let Some(rule) = self.block.iter().find(|b| {
+            b.asset.is_none() && matches(&b.hw_type, hw_type) && wildcard_match(&b.tag, tag)
+        }) {
+            return Err(format!("Gesperrt: {}", rule.reason));
+        }
+        if self.allow.is_empty() || self.allowing(hw_type, tag).next().is_some() {
+            Ok(())
+        } else {
+            Err(format!("{} ist für {} nicht freigegeben", tag, hw_type))
+        }
+    }
+
+    /// Prüft ein einzelnes Asset eines Releases
+    pub fn check_asset(&self, hw_type: HardwareType, tag: &str, asset: &str) -> Result<(), String> {
+        self.check_release(hw_type, tag)?;
+        if let Some(rule) = self.block.iter().find(|b| {
+            matches(&b.hw_type, hw_type)
+                && wildcard_match(&b.tag, tag)
+                && b.asset.as_deref().is_some_and(|a| wildcard_match(a, asset))
+        }) {
+            return Err(format!("Gesperrt: {}", rule.reason));
+        }
+        let allowed = self.allow.is_empty()
+            || self.allowing(hw_type, tag).any(|rule| {
+                rule.assets.is_empty() || rule.assets.iter().any(|a| wildcard_match(a, asset))
+            });
+        if allowed {
+            Ok(())
+        } else {
+            Err(format!("{} aus {} ist nicht freigegeben", asset, tag))
+        }
+    }
+
+    /// Prüft alle Assets, die gemeinsam geflasht werden
+    pub fn check_assets(
+        &self,
+        hw_type: HardwareType,
+        tag: &str,
+        assets: &[String],
+    ) -> anyhow::Result<()> {
+        self.check_release(hw_type, tag)
+            .map_err(anyhow::Error::msg)?;
+        for asset in assets {
+            self.check_asset(hw_type, tag, asset)
+                .map_err(anyhow::Error::msg)?;
+        }
+        Ok(())
+    }
+
+    fn allowing<'a>(
+        &'a self,
+        hw_type: HardwareType,
+        tag: &'a str,
+    ) -> impl Iterator<Item = &'a AllowRule> {
+        self.allow.iter().filter(move |rule| {
+            matches(&rule.hw_type, hw_type) && rule.tags.iter().any(|t| wildcard_match(t, tag))
+        })
+    }
+}
+
+fn matches(pattern: &str, hw_type: HardwareType) -> bool {
+    wildcard_match(pattern, hw_type.id())
+}
+
+/// Signaturdatei zur Freigabeliste, z.B. `policy.toml.sig`
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(SIGNATURE_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Liest die Liste; mit `public_key` muss eine gültige Signatur über die
+/// unveränderten Dateibytes daneben liegen
+fn read(path: &Path, public_key: Option<&str>) -> anyhow::Result<Policy> {
+    let content = std::fs::read(path)?;
+    let signed = match public_key.filter(|k| !k.trim().is_empty()) {
+        Some(key) => {
+            let signature = std::fs::read_to_string(signature_path(path)).map_err(|e| {
+                anyhow::anyhow!("Signatur {}: {}", signature_path(path).display(), e)
+            })?;
+            crate::signing::verify(&content, signature.trim(), key)?;
+            true
+        }
+        None => false,
+    };
+    let mut policy: Policy = toml::from_str(std::str::from_utf8(&content)?)?;
+    policy.source = Some(path.to_path_buf());
+    policy.signed = signed;
+    Ok(policy)
+}
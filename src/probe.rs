@@ -0,0 +1,322 @@
+use crate::backend::{ProbeRsBackend, ProgrammerBackend};
+use crate::hardware::HardwareType;
+use crate::settings::Settings;
+use crate::simulator::SimulatedDevice;
+use probe_rs::config::TargetSelector;
+use probe_rs::probe::{DebugProbeSelector, Probe, list::Lister};
+use probe_rs::{Permissions, Session, SessionConfig};
+use serde::{Deserialize, Serialize};
+
+/// Zustand des Geräts nach dem Flashen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AfterFlash {
+    /// Reset und Firmware starten
+    #[default]
+    Run,
+    /// Reset und am Reset-Vektor anhalten
+    Halt,
+    /// Zustand so lassen, wie probe-rs ihn hinterlässt
+    Leave,
+}
+
+impl AfterFlash {
+    pub fn all() -> &'static [AfterFlash] {
+        &[AfterFlash::Run, AfterFlash::Halt, AfterFlash::Leave]
+    }
+}
+
+impl std::fmt::Display for AfterFlash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            AfterFlash::Run => "Reset and run",
+            AfterFlash::Halt => "Reset and halt",
+            AfterFlash::Leave => "Leave as is",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Versorgungsspannung, ab der ein Gerät als angeschlossen gilt (V)
+const MIN_TARGET_VOLTAGE: f32 = 1.5;
+
+/// SWD-Geschwindigkeiten für Wiederholungen nach Verbindungsfehlern (kHz)
+pub const FALLBACK_SPEEDS: &[u32] = &[1000, 400, 100];
+
+/// Wiederholungen bei Verbindungs- und Programmierfehlern
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub attach_attempts: u32,
+    pub flash_attempts: u32,
+    /// Bei Wiederholungen die SWD-Geschwindigkeit schrittweise senken
+    pub lower_speed_on_retry: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attach_attempts: 3,
+            flash_attempts: 2,
+            lower_speed_on_retry: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Genau ein Versuch, z.B. wenn der Aufrufer selbst wiederholt
+    pub fn once() -> Self {
+        Self {
+            attach_attempts: 1,
+            flash_attempts: 1,
+            lower_speed_on_retry: false,
+        }
+    }
+
+    /// SWD-Geschwindigkeit für den Versuch `attempt` (ab 0)
+    pub fn speed(&self, attempt: u32, configured: Option<u32>) -> Option<u32> {
+        if attempt == 0 || !self.lower_speed_on_retry {
+            return configured;
+        }
+        let index = (attempt as usize - 1).min(FALLBACK_SPEEDS.len() - 1);
+        let fallback = FALLBACK_SPEEDS[index];
+        Some(configured.map_or(fallback, |speed| speed.min(fallback)))
+    }
+}
+
+/// Manuelle Steuerung des Kerns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetAction {
+    Reset,
+    Halt,
+    Run,
+}
+
+impl std::fmt::Display for TargetAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            TargetAction::Reset => "Reset",
+            TargetAction::Halt => "Halt",
+            TargetAction::Run => "Run",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Probe-Auswahl und Verbindungsparameter für eine probe-rs-Session
+#[derive(Debug, Clone, Default)]
+pub struct ProbeConfig {
+    /// Bevorzugte Probe (`VID:PID[:Seriennummer]`), sonst die erste gefundene
+    pub probe: Option<String>,
+    pub swd_speed_khz: Option<u32>,
+    /// Reset-Leitung während des Verbindens halten (Firmware schläft oder nutzt die SWD-Pins)
+    pub connect_under_reset: bool,
+    /// Erlaubt probe-rs das Löschen des gesamten Chips, z.B. zum Entsperren
+    pub allow_erase_all: bool,
+    pub retry: RetryPolicy,
+    /// Simuliertes Gerät statt einer echten Probe
+    pub demo_device: Option<HardwareType>,
+}
+
+impl ProbeConfig {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            probe: settings.preferred_probe.clone(),
+            swd_speed_khz: settings.swd_speed_khz,
+            connect_under_reset: settings.connect_under_reset,
+            allow_erase_all: false,
+            retry: settings.retry,
+            demo_device: settings
+                .demo_device
+                .as_deref()
+                .and_then(HardwareType::from_id),
+        }
+    }
+}
+
+fn target_selector(target: Option<&str>) -> TargetSelector {
+    match target {
+        Some(target) if !target.is_empty() => TargetSelector::from(target),
+        _ => TargetSelector::Auto,
+    }
+}
+
+/// Öffnet eine probe-rs-Session mit der bevorzugten Probe und SWD-Geschwindigkeit,
+/// im Demo-Modus das simulierte Gerät. Ohne `target` erkennt probe-rs den Chip
+/// selbst. Fehlgeschlagene Versuche werden laut `RetryPolicy` wiederholt und im
+/// Vorgangsprotokoll festgehalten.
+pub fn attach(
+    config: &ProbeConfig,
+    target: Option<&str>,
+) -> anyhow::Result<Box<dyn ProgrammerBackend>> {
+    if let Some(hw_type) = config.demo_device {
+        return Ok(Box::new(SimulatedDevice::connect(hw_type, target)?));
+    }
+    let attempts = config.retry.attach_attempts.max(1);
+    let mut attempt = 0;
+    loop {
+        let speed = config.retry.speed(attempt, config.swd_speed_khz);
+        let current = ProbeConfig {
+            swd_speed_khz: speed,
+            ..config.clone()
+        };
+        let speed_text = speed.map_or("default".to_string(), |s| format!("{} kHz", s));
+        match attach_once(&current, target) {
+            Ok(session) => {
+                let backend = ProbeRsBackend::new(session, probe_identifier(&current));
+                if attempt > 0 {
+                    crate::oplog::record(format!(
+                        "Verbindung im Versuch {}/{} ({}) hergestellt",
+                        attempt + 1,
+                        attempts,
+                        speed_text
+                    ));
+                }
+                return Ok(Box::new(backend));
+            }
+            Err(e) => {
+                crate::oplog::record(format!(
+                    "Verbindungsversuch {}/{} ({}) fehlgeschlagen: {:#}",
+                    attempt + 1,
+                    attempts,
+                    speed_text,
+                    e
+                ));
+                attempt += 1;
+                if attempt >= attempts {
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+/// Prüft ohne Reset, Wiederholung und Protokolleintrag, ob ein Gerät
+/// angeschlossen ist (Erkennung von Anstecken und Abziehen im Produktionsmodus).
+/// Kann die Probe die Versorgungsspannung messen, genügt diese; sonst eine
+/// normale Verbindung, auch wenn `connect_under_reset` eingestellt ist.
+pub fn is_connected(config: &ProbeConfig, target: Option<&str>) -> bool {
+    if let Some(hw_type) = config.demo_device {
+        return SimulatedDevice::connect(hw_type, target).is_ok();
+    }
+    let Ok(mut probe) = open_probe(config) else {
+        return false;
+    };
+    if let Ok(Some(voltage)) = probe.get_target_voltage() {
+        return voltage >= MIN_TARGET_VOLTAGE;
+    }
+    if let Some(speed) = config.swd_speed_khz
+        && probe.set_speed(speed).is_err()
+    {
+        return false;
+    }
+    probe
+        .attach(target_selector(target), Permissions::default())
+        .is_ok()
+}
+
+fn attach_once(config: &ProbeConfig, target: Option<&str>) -> anyhow::Result<Session> {
+    let target = target_selector(target);
+    if config.probe.is_none() && !config.connect_under_reset && !config.allow_erase_all {
+        let session_config = SessionConfig {
+            speed: config.swd_speed_khz,
+            ..SessionConfig::default()
+        };
+        return Ok(Session::auto_attach(target, session_config)?);
+    }
+    let mut probe = open_probe(config)?;
+    if let Some(speed) = config.swd_speed_khz {
+        probe.set_speed(speed)?;
+    }
+    let permissions = if config.allow_erase_all {
+        Permissions::new().allow_erase_all()
+    } else {
+        Permissions::default()
+    };
+    if config.connect_under_reset {
+        Ok(probe.attach_under_reset(target, permissions)?)
+    } else {
+        Ok(probe.attach(target, permissions)?)
+    }
+}
+
+/// Prüft, ob die bevorzugte bzw. überhaupt eine Debug-Probe vorhanden ist,
+/// ohne eine Verbindung zum Gerät aufzubauen
+pub fn ensure_probe(config: &ProbeConfig) -> anyhow::Result<()> {
+    if config.demo_device.is_some() {
+        return Ok(());
+    }
+    open_probe(config).map(drop)
+}
+
+/// Bevorzugte Probe oder die erste gefundene
+fn open_probe(config: &ProbeConfig) -> anyhow::Result<Probe> {
+    let lister = Lister::new();
+    match &config.probe {
+        Some(selector) => {
+            let selector: DebugProbeSelector = selector.parse()?;
+            Ok(lister.open(selector)?)
+        }
+        None => {
+            let probes = lister.list_all();
+            let info = probes
+                .first()
+                .ok_or_else(|| anyhow::anyhow!("Keine Debug-Probe gefunden"))?;
+            Ok(info.open()?)
+        }
+    }
+}
+
+/// Kennung der verwendeten Probe für das Audit-Log
+fn probe_identifier(config: &ProbeConfig) -> Option<String> {
+    if let Some(selector) = &config.probe {
+        return Some(selector.clone());
+    }
+    let probes = Lister::new().list_all();
+    let info = probes.first()?;
+    Some(match &info.serial_number {
+        Some(serial) => format!("{:04x}:{:04x}:{}", info.vendor_id, info.product_id, serial),
+        None => format!("{:04x}:{:04x}", info.vendor_id, info.product_id),
+    })
+}
+
+/// Versorgungsspannung des Geräts, sofern die Probe sie messen kann
+pub fn target_voltage(config: &ProbeConfig) -> Option<f32> {
+    if config.demo_device.is_some() {
+        return Some(3.3);
+    }
+    open_probe(config).ok()?.get_target_voltage().ok().flatten()
+}
+
+/// Setzt den Kern nach dem Flashen in den gewünschten Zustand
+pub fn after_flash(backend: &mut dyn ProgrammerBackend, mode: AfterFlash) -> anyhow::Result<()> {
+    match mode {
+        AfterFlash::Run => backend.reset()?,
+        AfterFlash::Halt => backend.reset_and_halt()?,
+        AfterFlash::Leave => {}
+    }
+    Ok(())
+}
+
+/// Reset, Halt oder Run auf dem angeschlossenen Gerät
+pub fn control(
+    config: &ProbeConfig,
+    target: Option<&str>,
+    action: TargetAction,
+) -> anyhow::Result<String> {
+    let mut backend = attach(config, target)?;
+    Ok(match action {
+        TargetAction::Reset => {
+            backend.reset()?;
+            "Gerät zurückgesetzt.".to_string()
+        }
+        TargetAction::Halt => {
+            let pc = backend.halt()?;
+            format!("Kern angehalten, PC = 0x{:08X}", pc)
+        }
+        TargetAction::Run => {
+            backend.run()?;
+            "Kern läuft.".to_string()
+        }
+    })
+}
@@ -0,0 +1,284 @@
+use crate::audit::{Action, AuditRecord};
+use crate::flash::FlashConfig;
+use crate::hardware::HardwareType;
+use crate::probe::{FALLBACK_SPEEDS, ProbeConfig, RetryPolicy};
+use crate::settings::Settings;
+
+/// Schritte der Wiederherstellung, von harmlos bis destruktiv
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    LowerSpeed,
+    ConnectUnderReset,
+    MassErase,
+    OptionBytes,
+    FlashFirmware,
+}
+
+impl Step {
+    pub fn all() -> &'static [Step] {
+        &[
+            Step::LowerSpeed,
+            Step::ConnectUnderReset,
+            Step::MassErase,
+            Step::OptionBytes,
+            Step::FlashFirmware,
+        ]
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Step::LowerSpeed => "Lower SWD speed",
+            Step::ConnectUnderReset => "Connect under reset",
+            Step::MassErase => "Mass erase",
+            Step::OptionBytes => "Option byte recovery",
+            Step::FlashFirmware => "Flash known-good firmware",
+        }
+    }
+
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Step::LowerSpeed => {
+                "Long cables, weak supplies or a noisy environment can make the connection fail \
+                 at high SWD speeds. Tries to connect at 1 MHz, 400 kHz and 100 kHz."
+            }
+            Step::ConnectUnderReset => {
+                "If the firmware sleeps, disables the debug port or uses the SWD pins for \
+                 something else, the probe can only connect while the reset line is held. \
+                 Requires the reset line (NRST) to be wired to the probe."
+            }
+            Step::MassErase => {
+                "Erases the complete flash so that no firmware can interfere with the \
+                 connection anymore."
+            }
+            Step::OptionBytes => {
+                "Removes readout protection (RDP Level 1) and the write protection of all \
+                 sectors. Removing readout protection mass-erases the flash."
+            }
+            Step::FlashFirmware => {
+                "Flashes the downloaded firmware with the connection settings that worked."
+            }
+        }
+    }
+
+    /// Beschreibung des Datenverlusts, `None` = Schritt ist harmlos
+    pub fn data_loss(&self) -> Option<&'static str> {
+        match self {
+            Step::LowerSpeed | Step::ConnectUnderReset => None,
+            Step::MassErase | Step::OptionBytes => Some(
+                "The complete flash including firmware, serial number, configuration and \
+                 calibration data will be lost.",
+            ),
+            Step::FlashFirmware => {
+                Some("The installed firmware is replaced. Protected regions are preserved.")
+            }
+        }
+    }
+}
+
+/// Zustand des Wiederherstellungs-Assistenten
+pub struct Wizard {
+    pub step: usize,
+    /// Verbindungsparameter, die sich bisher bewährt haben
+    pub probe: ProbeConfig,
+    pub log: Vec<String>,
+    pub confirm_data_loss: bool,
+    /// Downgrade bzw. erneutes Flashen der installierten Version bestätigt
+    pub allow_version_change: bool,
+}
+
+impl Wizard {
+    pub fn new(probe: ProbeConfig) -> Self {
+        Self {
+            step: 0,
+            // Der Assistent probiert die Varianten selbst der Reihe nach durch
+            probe: ProbeConfig {
+                retry: RetryPolicy::once(),
+                ..probe
+            },
+            log: Vec::new(),
+            confirm_data_loss: false,
+            allow_version_change: false,
+        }
+    }
+
+    pub fn current(&self) -> Step {
+        Step::all()[self.step]
+    }
+
+    pub fn go_to(&mut self, step: usize) {
+        self.step = step.min(Step::all().len() - 1);
+        self.confirm_data_loss = false;
+    }
+
+    /// Führt den aktuellen Schritt aus. Nach einer erfolgreichen Verbindung
+    /// geht es direkt zum Flashen, sonst zum nächsten Schritt.
+    pub fn run(
+        &mut self,
+        hw_type: HardwareType,
+        firmware: &[String],
+        tag: Option<&str>,
+        settings: &Settings,
+    ) {
+        let step = self.current();
+        let target = hw_type.model().target.clone();
+        let result = match step {
+            Step::LowerSpeed => self.lower_speed(&target),
+            Step::ConnectUnderReset => self.connect_under_reset(&target),
+            Step::MassErase => self.mass_erase(hw_type, settings),
+            Step::OptionBytes => self.option_bytes(hw_type, settings),
+            Step::FlashFirmware => self.flash(hw_type, firmware, tag, settings),
+        };
+        match result {
+            Ok(msg) => {
+                self.log.push(format!("{}: {}", step.title(), msg));
+                if step != Step::FlashFirmware {
+                    self.go_to(Step::all().len() - 1);
+                }
+            }
+            Err(e) => {
+                self.log.push(format!("{}: Fehler: {}", step.title(), e));
+                if step != Step::FlashFirmware {
+                    self.go_to(self.step + 1);
+                }
+            }
+        }
+    }
+
+    /// Probiert die Geschwindigkeiten und übernimmt die erste, die funktioniert
+    fn try_speeds(&mut self, target: &str, base: ProbeConfig) -> anyhow::Result<String> {
+        let mut last_error = None;
+        for speed in FALLBACK_SPEEDS {
+            let config = ProbeConfig {
+                swd_speed_khz: Some(*speed),
+                ..base.clone()
+            };
+            match crate::probe::attach(&config, Some(target)) {
+                Ok(_) => {
+                    self.probe = config;
+                    return Ok(format!("Verbindung mit {} kHz hergestellt", speed));
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("Keine Verbindung")))
+    }
+
+    fn lower_speed(&mut self, target: &str) -> anyhow::Result<String> {
+        self.try_speeds(target, self.probe.clone())
+    }
+
+    fn connect_under_reset(&mut self, target: &str) -> anyhow::Result<String> {
+        let base = ProbeConfig {
+            connect_under_reset: true,
+            ..self.probe.clone()
+        };
+        if crate::probe::attach(&base, Some(target)).is_ok() {
+            self.probe = base;
+            return Ok("Verbindung unter Reset hergestellt".to_string());
+        }
+        self.try_speeds(target, base)
+    }
+
+    fn mass_erase(&mut self, hw_type: HardwareType, settings: &Settings) -> anyhow::Result<String> {
+        let config = ProbeConfig {
+            connect_under_reset: true,
+            allow_erase_all: true,
+            ..self.probe.clone()
+        };
+        let mut record = AuditRecord::new(Action::Erase, Some(hw_type));
+        record.details = "Recovery: mass erase".to_string();
+        crate::audit::run(settings, record, |record| {
+            let mut backend = crate::probe::attach(&config, Some(&hw_type.model().target))?;
+            record.probe = backend.probe();
+            backend.erase(None)
+        })?;
+        self.probe.connect_under_reset = true;
+        Ok("Chip gelöscht".to_string())
+    }
+
+    fn option_bytes(
+        &mut self,
+        hw_type: HardwareType,
+        settings: &Settings,
+    ) -> anyhow::Result<String> {
+        let model = hw_type.model();
+        let kind = model
+            .option_bytes
+            .ok_or_else(|| anyhow::anyhow!("{} hat keine Option-Bytes im Katalog", hw_type))?;
+        let config = ProbeConfig {
+            connect_under_reset: true,
+            ..self.probe.clone()
+        };
+        let record = AuditRecord::new(Action::OptionBytes, Some(hw_type));
+        let (before, after) = crate::audit::run(settings, record, |record| {
+            let mut backend = crate::probe::attach(&config, Some(&model.target))?;
+            record.probe = backend.probe();
+            let before = crate::option_bytes::read(backend.as_mut(), kind)?;
+            let sectors = model.sectors(model.flash).len();
+            crate::option_bytes::recover(backend.as_mut(), kind, sectors)?;
+            let after = crate::option_bytes::read(backend.as_mut(), kind)?;
+            record.details = format!("Recovery: {} → {}", before.rdp(), after.rdp());
+            Ok((before, after))
+        })?;
+        self.probe.connect_under_reset = true;
+        Ok(format!(
+            "{} → {}. Bitte das Gerät aus- und wieder einschalten.",
+            before.rdp(),
+            after.rdp()
+        ))
+    }
+
+    fn flash(
+        &mut self,
+        hw_type: HardwareType,
+        firmware: &[String],
+        tag: Option<&str>,
+        settings: &Settings,
+    ) -> anyhow::Result<String> {
+        let Some(tag) = tag.filter(|_| !firmware.is_empty()) else {
+            anyhow::bail!("Keine Firmware heruntergeladen (Flash-Ansicht)");
+        };
+        let mut config = FlashConfig::new(firmware, hw_type, settings);
+        config.probe = self.probe.clone();
+        // Nach dem Löschen gibt es nichts mehr zu sichern
+        config.backup_dir = None;
+        config.firmware_tag = Some(tag.to_string());
+        config.allow_version_change = self.allow_version_change;
+        let result = crate::flash::flash_hardware(&config);
+        if !result.success {
+            anyhow::bail!(result.message);
+        }
+        Ok(result.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flash_step_checks_the_policy() {
+        let mut device = crate::testing::device();
+        let policy = device.dir.path().join("policy.toml");
+        std::fs::write(
+            &policy,
+            "[[block]]\ntag = \"v1.1.0\"\nreason = \"Fehlerhaft\"\n",
+        )
+        .unwrap();
+        device.settings.firmware_policy = Some(policy);
+        let firmware = [device.application_image()];
+        let mut wizard = Wizard::new(device.probe());
+        assert!(
+            wizard
+                .flash(device.hw_type, &firmware, None, &device.settings)
+                .is_err()
+        );
+        let error = wizard
+            .flash(device.hw_type, &firmware, Some("v1.1.0"), &device.settings)
+            .unwrap_err();
+        assert!(error.to_string().contains("Fehlerhaft"));
+        wizard
+            .flash(device.hw_type, &firmware, Some("v1.2.0"), &device.settings)
+            .unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+use crate::hardware::{HardwareModel, HardwareType};
+use crate::image::{FirmwareImage, Segment};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// Ergebnis einer Prüfung vor dem Flashen
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Finding {
+    fn error(message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+        }
+    }
+
+    fn warning(message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+        }
+    }
+}
+
+pub fn has_errors(findings: &[Finding]) -> bool {
+    findings.iter().any(|f| f.severity == Severity::Error)
+}
+
+/// Fasst alle Fehler zu einer Meldung zusammen
+pub fn error_summary(findings: &[Finding]) -> Option<String> {
+    let errors: Vec<&str> = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .map(|f| f.message.as_str())
+        .collect();
+    (!errors.is_empty()).then(|| errors.join("; "))
+}
+
+/// Prüft Firmware-Images vor dem Flashen: Größe, Lage im Flash, Vektortabelle,
+/// geschützte Bereiche und offensichtlich falsche Dateien
+pub fn check_images(
+    images: &[FirmwareImage],
+    hw_type: HardwareType,
+    allow_protected_overwrite: bool,
+) -> Vec<Finding> {
+    let model = hw_type.model();
+    let mut findings = Vec::new();
+    for image in images {
+        check_image(image, model, allow_protected_overwrite, &mut findings);
+    }
+
+    let segments: Vec<&Segment> = images.iter().flat_map(|i| &i.segments).collect();
+    for (i, a) in segments.iter().enumerate() {
+        for b in &segments[i + 1..] {
+            if a.range().overlaps(&b.range()) {
+                findings.push(Finding::error(format!(
+                    "Images overlap: {} and {}",
+                    a.range(),
+                    b.range()
+                )));
+            }
+        }
+    }
+    let total: u64 = segments.iter().map(|s| s.data.len() as u64).sum();
+    if total > model.flash.size {
+        findings.push(Finding::error(format!(
+            "Image size {} bytes exceeds the flash size of {} bytes",
+            total, model.flash.size
+        )));
+    }
+    findings
+}
+
+fn check_image(
+    image: &FirmwareImage,
+    model: &HardwareModel,
+    allow_protected_overwrite: bool,
+    findings: &mut Vec<Finding>,
+) {
+    let Some(first) = image.segments.first() else {
+        findings.push(Finding::error("Image contains no data".to_string()));
+        return;
+    };
+    if image.segments.iter().all(|s| s.data.is_empty()) {
+        findings.push(Finding::error(
+            "File is empty (download incomplete?)".to_string(),
+        ));
+        return;
+    }
+    if let Some(kind) = text_file_kind(&first.data) {
+        findings.push(Finding::error(format!(
+            "File is {} instead of firmware (download error?)",
+            kind
+        )));
+        return;
+    }
+
+    for segment in &image.segments {
+        let range = segment.range();
+        if range.base < model.flash.base || range.end() > model.flash.end() {
+            findings.push(Finding::error(format!(
+                "{} lies outside the flash {}",
+                range, model.flash
+            )));
+            continue;
+        }
+        for region in model.protected_regions() {
+            if region.range.overlaps(&range) {
+                let message = format!("Image {} overlaps protected region {}", range, region);
+                findings.push(if allow_protected_overwrite {
+                    Finding::warning(message)
+                } else {
+                    Finding::error(message)
+                });
+            }
+        }
+    }
+
+    check_vector_table(first, model, findings);
+}
+
+/// Plausibilität der Cortex-M-Vektortabelle am Anfang des Images
+fn check_vector_table(segment: &Segment, model: &HardwareModel, findings: &mut Vec<Finding>) {
+    if segment.data.len() < 8 {
+        findings.push(Finding::error(format!(
+            "Image at 0x{:08X} is too small for a vector table",
+            segment.address
+        )));
+        return;
+    }
+    let word = |offset: usize| {
+        u32::from_le_bytes(segment.data[offset..offset + 4].try_into().unwrap()) as u64
+    };
+    let initial_sp = word(0);
+    let reset_vector = word(4);
+    if !(model.ram.contains(initial_sp) || initial_sp == model.ram.end()) {
+        findings.push(Finding::error(format!(
+            "Initial stack pointer 0x{:08X} is not in RAM {}",
+            initial_sp, model.ram
+        )));
+    } else if initial_sp % 4 != 0 {
+        findings.push(Finding::warning(format!(
+            "Initial stack pointer 0x{:08X} is not word aligned",
+            initial_sp
+        )));
+    }
+    if reset_vector & 1 == 0 {
+        findings.push(Finding::error(format!(
+            "Reset vector 0x{:08X} has no thumb bit set",
+            reset_vector
+        )));
+    }
+    let reset_address = reset_vector & !1;
+    if !model.flash.contains(reset_address) {
+        findings.push(Finding::error(format!(
+            "Reset vector 0x{:08X} does not point into flash {}",
+            reset_vector, model.flash
+        )));
+    } else if !segment.range().contains(reset_address) {
+        findings.push(Finding::warning(format!(
+            "Reset vector 0x{:08X} points outside of this image",
+            reset_vector
+        )));
+    }
+}
+
+/// Erkennt Textdateien, die statt der Firmware heruntergeladen wurden
+fn text_file_kind(data: &[u8]) -> Option<&'static str> {
+    let start = data
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(data.len());
+    let head: Vec<u8> = data[start..]
+        .iter()
+        .take(64)
+        .map(|b| b.to_ascii_lowercase())
+        .collect();
+    if head.starts_with(b"<!doctype html") || head.starts_with(b"<html") {
+        Some("an HTML page")
+    } else if head.starts_with(b"<?xml") {
+        Some("an XML document")
+    } else if head.starts_with(b"{\"") || head.starts_with(b"{\n") {
+        Some("a JSON document")
+    } else if head.starts_with(b"not found") || head.starts_with(b"404") {
+        Some("an error message")
+    } else {
+        None
+    }
+}
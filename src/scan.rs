@@ -0,0 +1,190 @@
+//! Eingaben von Barcode-Scannern, die sich als Tastatur anmelden: Geräteetiketten
+//! und Arbeitsaufträge werden über konfigurierbare reguläre Ausdrücke zerlegt
+
+use crate::hardware::HardwareType;
+use crate::serial::Ledger;
+use crate::settings::Settings;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Platzhalter in Mustern für Seriennummern in den konfigurierten Formaten
+pub const SERIAL_PLACEHOLDER: &str = "{serial}";
+
+/// Muster für gescannte Codes. Ausgewertet werden die benannten Gruppen
+/// `hw`, `serial`, `tag`, `asset` (Platzhalter `*` erlaubt) und `order`.
+/// `{serial}` steht für eine Seriennummer in einem der konfigurierten Formate.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanPattern {
+    pub name: String,
+    pub pattern: String,
+}
+
+impl ScanPattern {
+    /// Arbeitsauftrag `WO<Nummer>/<Hardware-Typ>/<Release>[/<Asset>]` und
+    /// Geräteetikett mit der Seriennummer (Inhalt des Etikettencodes)
+    pub fn defaults() -> Vec<ScanPattern> {
+        vec![
+            ScanPattern {
+                name: "Work order".to_string(),
+                pattern: r"^(?i:WO)(?P<order>\d+)/(?P<hw>[\w-]+)/(?P<tag>[^/\s]+)(?:/(?P<asset>[^/\s]+))?$"
+                    .to_string(),
+            },
+            ScanPattern {
+                name: "Device label".to_string(),
+                pattern: format!("^(?P<serial>{})$", SERIAL_PLACEHOLDER),
+            },
+        ]
+    }
+
+    /// Übersetzt das Muster; `serial_pattern` ersetzt den Platzhalter `{serial}`
+    pub fn regex(&self, serial_pattern: &str) -> Result<Regex, regex::Error> {
+        Regex::new(&self.pattern.replace(SERIAL_PLACEHOLDER, serial_pattern))
+    }
+}
+
+/// Seriennummern aller Hardware-Typen laut Seriennummern-Format
+pub fn serial_pattern(settings: &Settings) -> String {
+    let mut patterns: Vec<String> = HardwareType::all()
+        .iter()
+        .map(|hw| settings.serial_format(*hw).pattern())
+        .collect();
+    patterns.sort();
+    patterns.dedup();
+    format!("(?:{})", patterns.join("|"))
+}
+
+/// Zerlegter Scan
+#[derive(Debug, Clone, Default)]
+pub struct ScanResult {
+    /// Name des passenden Musters
+    pub pattern: String,
+    pub hw_type: Option<HardwareType>,
+    pub serial: Option<String>,
+    /// Vom Arbeitsauftrag verlangtes Release
+    pub tag: Option<String>,
+    pub asset: Option<String>,
+    pub order: Option<String>,
+}
+
+impl ScanResult {
+    pub fn summary(&self) -> String {
+        let mut parts = vec![self.pattern.clone()];
+        if let Some(order) = &self.order {
+            parts.push(format!("order {}", order));
+        }
+        if let Some(hw) = self.hw_type {
+            parts.push(hw.to_string());
+        }
+        if let Some(serial) = &self.serial {
+            parts.push(format!("serial {}", serial));
+        }
+        if let Some(tag) = &self.tag {
+            parts.push(format!("firmware {}", tag));
+        }
+        if let Some(asset) = &self.asset {
+            parts.push(asset.clone());
+        }
+        parts.join(", ")
+    }
+}
+
+/// Probiert die Muster der Reihe nach; ungültige Muster werden übersprungen
+/// (der Settings-View zeigt den Fehler an)
+pub fn parse(settings: &Settings, text: &str) -> anyhow::Result<ScanResult> {
+    let text = text.trim();
+    let serial_pattern = serial_pattern(settings);
+    for pattern in &settings.scan_patterns {
+        let Ok(regex) = pattern.regex(&serial_pattern) else {
+            continue;
+        };
+        let Some(captures) = regex.captures(text) else {
+            continue;
+        };
+        let group = |name: &str| {
+            captures
+                .name(name)
+                .map(|m| m.as_str().trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let mut result = ScanResult {
+            pattern: pattern.name.clone(),
+            hw_type: None,
+            serial: group("serial"),
+            tag: group("tag"),
+            asset: group("asset"),
+            order: group("order"),
+        };
+        if let Some(hw) = group("hw") {
+            result.hw_type = Some(
+                hardware_type(&hw)
+                    .ok_or_else(|| anyhow::anyhow!("Unbekannter Hardware-Typ: {}", hw))?,
+            );
+        } else if let Some(serial) = &result.serial {
+            result.hw_type = hardware_type_for_serial(settings, serial);
+        }
+        return Ok(result);
+    }
+    anyhow::bail!("Kein Scan-Muster passt zu \"{}\"", text)
+}
+
+/// Hardware-Typ nach ID oder Modellname
+fn hardware_type(name: &str) -> Option<HardwareType> {
+    HardwareType::from_id(name).or_else(|| {
+        HardwareType::all()
+            .iter()
+            .copied()
+            .find(|hw| hw.model().name.eq_ignore_ascii_case(name))
+    })
+}
+
+/// Hardware-Typ laut Seriennummern-Register, sonst nach dem längsten
+/// passenden Präfix der Seriennummern-Formate
+fn hardware_type_for_serial(settings: &Settings, serial: &str) -> Option<HardwareType> {
+    let issued = Ledger::open(settings).ok().and_then(|ledger| {
+        ledger
+            .entries()
+            .iter()
+            .find(|e| e.serial.eq_ignore_ascii_case(serial))
+            .and_then(|e| HardwareType::from_id(&e.hw_type))
+    });
+    issued.or_else(|| {
+        let serial = serial.to_ascii_uppercase();
+        HardwareType::all()
+            .iter()
+            .copied()
+            .map(|hw| (hw, settings.serial_format(hw).prefix.to_ascii_uppercase()))
+            .filter(|(_, prefix)| !prefix.is_empty() && serial.starts_with(prefix.as_str()))
+            .max_by_key(|(_, prefix)| prefix.len())
+            .map(|(hw, _)| hw)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_label_needs_serial_format() {
+        let (_dir, settings) = crate::testing::environment();
+        let hw_type = HardwareType::from_id("irock-424").unwrap();
+        let serial = settings.serial_format(hw_type).example();
+        let result = parse(&settings, &serial).unwrap();
+        assert_eq!(result.pattern, "Device label");
+        assert_eq!(result.serial.as_deref(), Some(serial.as_str()));
+        assert_eq!(result.hw_type, Some(hw_type));
+        assert!(parse(&settings, &serial.to_ascii_lowercase()).is_ok());
+        assert!(parse(&settings, "ABCD-1234").is_err());
+        assert!(parse(&settings, &format!("{}0", serial)).is_err());
+    }
+
+    #[test]
+    fn work_order_without_asset() {
+        let (_dir, settings) = crate::testing::environment();
+        let hw_type = HardwareType::from_id("irock-424").unwrap();
+        let result = parse(&settings, &format!("WO17/{}/v1.2.0", hw_type.id())).unwrap();
+        assert_eq!(result.order.as_deref(), Some("17"));
+        assert_eq!(result.hw_type, Some(hw_type));
+        assert_eq!(result.tag.as_deref(), Some("v1.2.0"));
+        assert_eq!(result.asset, None);
+    }
+}
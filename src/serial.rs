@@ -0,0 +1,383 @@
+use crate::hardware::HardwareType;
+use crate::settings::Settings;
+use chrono::{DateTime, Datelike, Local};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const LEDGER_FILE: &str = "serials.jsonl";
+
+/// Aufbau der Seriennummern eines Hardware-Typs:
+/// Präfix, optional Jahr/Kalenderwoche (`YYWW`), laufende Nummer, optional Prüfziffer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SerialFormat {
+    pub prefix: String,
+    pub year_week: bool,
+    /// Stellen der laufenden Nummer
+    pub digits: usize,
+    /// Prüfziffer nach Luhn über alle Ziffern
+    pub check_digit: bool,
+}
+
+impl Default for SerialFormat {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            year_week: true,
+            digits: 5,
+            check_digit: true,
+        }
+    }
+}
+
+impl SerialFormat {
+    /// Standardformat mit Präfix aus der Modellnummer, z.B. `IR424`
+    pub fn for_model(hw_type: HardwareType) -> Self {
+        let number: String = hw_type
+            .id()
+            .chars()
+            .filter(|c| c.is_ascii_digit())
+            .collect();
+        Self {
+            prefix: format!("IR{}", number),
+            ..Self::default()
+        }
+    }
+
+    /// Fester Teil vor der laufenden Nummer
+    fn base(&self, date: DateTime<Local>) -> String {
+        let mut base = self.prefix.clone();
+        if self.year_week {
+            let week = date.iso_week();
+            base.push_str(&format!("{:02}{:02}", week.year() % 100, week.week()));
+        }
+        base
+    }
+
+    fn len(&self) -> usize {
+        self.prefix.len()
+            + if self.year_week { 4 } else { 0 }
+            + self.digits
+            + self.check_digit as usize
+    }
+
+    fn build(&self, base: &str, number: u64) -> String {
+        let mut serial = format!("{}{:0width$}", base, number, width = self.digits);
+        if self.check_digit {
+            serial.push(luhn_digit(&serial));
+        }
+        serial
+    }
+
+    /// Beispiel für die Anzeige in den Einstellungen
+    pub fn example(&self) -> String {
+        self.build(&self.base(Local::now()), 1)
+    }
+
+    /// Prüft Format und Länge gegen den Parameter im Katalog
+    pub fn validate(&self, hw_type: HardwareType) -> Result<(), String> {
+        if !self.prefix.is_ascii() || self.prefix.chars().any(|c| c.is_whitespace()) {
+            return Err("Präfix darf nur ASCII-Zeichen ohne Leerzeichen enthalten".to_string());
+        }
+        if !(1..=9).contains(&self.digits) {
+            return Err("Laufende Nummer muss 1 bis 9 Stellen haben".to_string());
+        }
+        if let Some(parameter) = hw_type.model().parameter("serial")
+            && self.len() > parameter.length
+        {
+            return Err(format!(
+                "Seriennummer ({} Zeichen) ist länger als der Parameter ({} Zeichen)",
+                self.len(),
+                parameter.length
+            ));
+        }
+        Ok(())
+    }
+
+    /// Regulärer Ausdruck für Seriennummern dieses Formats (Präfix ohne
+    /// Beachtung der Groß-/Kleinschreibung)
+    pub fn pattern(&self) -> String {
+        format!(
+            "(?i:{})[0-9]{{{}}}",
+            regex::escape(&self.prefix),
+            self.len() - self.prefix.len()
+        )
+    }
+
+    /// `false`, wenn die Prüfziffer einer Seriennummer nicht stimmt
+    pub fn check(&self, serial: &str) -> bool {
+        if !self.check_digit {
+            return true;
+        }
+        let mut chars = serial.chars();
+        match chars.next_back() {
+            Some(last) => luhn_digit(chars.as_str()) == last,
+            None => false,
+        }
+    }
+}
+
+/// Prüfziffer nach Luhn über die Ziffern in `text`; Buchstaben werden übersprungen
+fn luhn_digit(text: &str) -> char {
+    let sum: u32 = text
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| {
+            if i % 2 == 0 {
+                let d = d * 2;
+                if d > 9 { d - 9 } else { d }
+            } else {
+                d
+            }
+        })
+        .sum();
+    char::from_digit((10 - sum % 10) % 10, 10).unwrap_or('0')
+}
+
+/// Eintrag im Seriennummern-Register
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub serial: String,
+    pub hw_type: String,
+    pub issued: DateTime<Local>,
+    /// 96-Bit-Unique-ID des Geräts, falls bekannt
+    #[serde(default)]
+    pub unique_id: Option<String>,
+}
+
+/// Register aller vergebenen Seriennummern. Wird nur angehängt, damit eine
+/// Nummer auch nach einem Neustart oder fehlgeschlagenem Schreiben nie erneut
+/// vergeben wird.
+pub struct Ledger {
+    path: PathBuf,
+    entries: Vec<LedgerEntry>,
+}
+
+impl Ledger {
+    pub fn path(settings: &Settings) -> anyhow::Result<PathBuf> {
+        Ok(settings.data_dir()?.join(LEDGER_FILE))
+    }
+
+    /// Lädt das Register aus dem Datenverzeichnis
+    pub fn open(settings: &Settings) -> anyhow::Result<Ledger> {
+        Self::load(&Self::path(settings)?)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Ledger> {
+        let mut entries = Vec::new();
+        if path.exists() {
+            for (i, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let entry = serde_json::from_str(line)
+                    .map_err(|e| anyhow::anyhow!("{}, Zeile {}: {}", path.display(), i + 1, e))?;
+                entries.push(entry);
+            }
+        }
+        Ok(Ledger {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    pub fn contains(&self, serial: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|e| e.serial.eq_ignore_ascii_case(serial))
+    }
+
+    /// Zuletzt an das Gerät mit `unique_id` vergebene Nummer dieses Hardware-Typs
+    pub fn issued_to(&self, hw_type: HardwareType, unique_id: &str) -> Option<&LedgerEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.hw_type == hw_type.id() && e.unique_id.as_deref() == Some(unique_id))
+    }
+
+    /// Nächste freie Nummer im aktuellen Nummernkreis, ohne sie zu vergeben
+    pub fn peek(&self, format: &SerialFormat) -> anyhow::Result<String> {
+        let base = format.base(Local::now());
+        let highest = self
+            .entries
+            .iter()
+            .filter(|e| e.serial.len() == format.len())
+            .filter_map(|e| e.serial.strip_prefix(&base))
+            .filter_map(|rest| rest.get(..format.digits)?.parse::<u64>().ok())
+            .max()
+            .unwrap_or(0);
+        let number = highest + 1;
+        if number >= 10u64.pow(format.digits as u32) {
+            anyhow::bail!("Nummernkreis {} ist erschöpft", base);
+        }
+        Ok(format.build(&base, number))
+    }
+
+    /// Vergibt die nächste Seriennummer und trägt sie sofort ein
+    pub fn allocate(
+        &mut self,
+        hw_type: HardwareType,
+        format: &SerialFormat,
+        unique_id: Option<&str>,
+    ) -> anyhow::Result<String> {
+        format.validate(hw_type).map_err(anyhow::Error::msg)?;
+        // Andere Instanzen könnten inzwischen Nummern vergeben haben
+        let _lock = self.lock()?;
+        self.entries = Self::load(&self.path)?.entries;
+        let serial = self.peek(format)?;
+        self.append(hw_type, &serial, unique_id)?;
+        Ok(serial)
+    }
+
+    /// Trägt eine (auch manuell eingegebene) Seriennummer ein; bereits vergebene
+    /// Nummern werden abgelehnt
+    pub fn register(
+        &mut self,
+        hw_type: HardwareType,
+        serial: &str,
+        unique_id: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let _lock = self.lock()?;
+        self.entries = Self::load(&self.path)?.entries;
+        if self.contains(serial) {
+            anyhow::bail!("Seriennummer {} wurde bereits vergeben", serial);
+        }
+        self.append(hw_type, serial, unique_id)
+    }
+
+    /// Exklusive Sperre für Neuladen, Prüfen und Anhängen, auch über
+    /// Prozessgrenzen; gilt, bis die zurückgegebene Datei geschlossen wird.
+    /// Eigene Sperrdatei, da eine gesperrte Datei unter Windows nicht lesbar ist.
+    fn lock(&self) -> anyhow::Result<std::fs::File> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path.with_extension("lock"))?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    fn append(
+        &mut self,
+        hw_type: HardwareType,
+        serial: &str,
+        unique_id: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let entry = LedgerEntry {
+            serial: serial.to_string(),
+            hw_type: hw_type.id().to_string(),
+            issued: Local::now(),
+            unique_id: unique_id.map(str::to_string),
+        };
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        file.sync_all()?;
+        self.entries.push(entry);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hw_type() -> HardwareType {
+        HardwareType::from_id("irock-424").unwrap()
+    }
+
+    #[test]
+    fn issued_to_finds_registered_units() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ledger = Ledger::load(&dir.path().join(LEDGER_FILE)).unwrap();
+        ledger.register(hw_type(), "IR424A", Some("UID1")).unwrap();
+        ledger.register(hw_type(), "IR424B", None).unwrap();
+        assert_eq!(
+            ledger
+                .issued_to(hw_type(), "UID1")
+                .map(|e| e.serial.as_str()),
+            Some("IR424A")
+        );
+        assert!(ledger.issued_to(hw_type(), "UID2").is_none());
+    }
+
+    #[test]
+    fn luhn_check_digit() {
+        assert_eq!(luhn_digit("7992739871"), '3');
+        assert_eq!(luhn_digit("0"), '0');
+        // Buchstaben zählen nicht mit
+        assert_eq!(luhn_digit("IR42426"), luhn_digit("42426"));
+        let format = SerialFormat::for_model(hw_type());
+        let serial = format.build("IR4242607", 12);
+        assert!(format.check(&serial));
+        let mut wrong = serial.clone();
+        let last = wrong.pop().unwrap();
+        wrong.push(if last == '0' { '1' } else { '0' });
+        assert!(!format.check(&wrong));
+    }
+
+    #[test]
+    fn peek_continues_after_highest_number() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut ledger = Ledger::load(&dir.path().join(LEDGER_FILE)).unwrap();
+        let format = SerialFormat {
+            prefix: "T".to_string(),
+            year_week: false,
+            digits: 2,
+            check_digit: false,
+        };
+        assert_eq!(ledger.peek(&format).unwrap(), "T01");
+        ledger.register(hw_type(), "T07", None).unwrap();
+        ledger.register(hw_type(), "T03", None).unwrap();
+        // Andere Länge oder anderes Präfix gehört nicht zum Nummernkreis
+        ledger.register(hw_type(), "T123", None).unwrap();
+        ledger.register(hw_type(), "X50", None).unwrap();
+        assert_eq!(ledger.peek(&format).unwrap(), "T08");
+        ledger.register(hw_type(), "T99", None).unwrap();
+        assert!(ledger.peek(&format).is_err());
+    }
+
+    #[test]
+    fn concurrent_allocations_are_unique() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LEDGER_FILE);
+        let format = SerialFormat::for_model(hw_type());
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let path = path.clone();
+                let format = format.clone();
+                std::thread::spawn(move || {
+                    (0..5)
+                        .map(|_| {
+                            Ledger::load(&path)
+                                .unwrap()
+                                .allocate(hw_type(), &format, None)
+                                .unwrap()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut serials: Vec<String> = threads
+            .into_iter()
+            .flat_map(|t| t.join().unwrap())
+            .collect();
+        serials.sort();
+        serials.dedup();
+        assert_eq!(serials.len(), 20);
+        assert_eq!(Ledger::load(&path).unwrap().entries().len(), 20);
+    }
+}
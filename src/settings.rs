@@ -0,0 +1,390 @@
+use crate::hardware::HardwareType;
+use crate::label::LabelSettings;
+use crate::probe::{AfterFlash, RetryPolicy};
+use crate::scan::ScanPattern;
+use crate::serial::SerialFormat;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+const APP_DIR: &str = "iRockProgrammer";
+const SETTINGS_FILE: &str = "settings.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    De,
+    #[default]
+    En,
+}
+
+impl Language {
+    pub fn all() -> &'static [Language] {
+        &[Language::De, Language::En]
+    }
+
+    pub fn from_code(code: &str) -> Option<Language> {
+        match code.to_ascii_lowercase().as_str() {
+            "de" => Some(Language::De),
+            "en" => Some(Language::En),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Language::De => "Deutsch",
+            Language::En => "English",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Persistente Einstellungen, gespeichert als TOML im Konfigurationsverzeichnis
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Probe-Selektor im Format `VID:PID[:Seriennummer]`
+    pub preferred_probe: Option<String>,
+    pub swd_speed_khz: Option<u32>,
+    /// Firmware-Repository je Hardware-Typ (Schlüssel: `HardwareType::id`)
+    pub firmware_sources: BTreeMap<String, String>,
+    pub cache_dir: Option<PathBuf>,
+    /// Höchstgröße des Firmware-Caches und, getrennt davon, der Backups
+    pub cache_size_mb: u64,
+    /// Verzeichnis für Audit-Log, Seriennummern-Register, Geräteverlauf,
+    /// Bedienerprofile und importierte Pakete, `None` = Konfigurationsverzeichnis
+    pub data_dir: Option<PathBuf>,
+    pub language: Language,
+    pub github_token: Option<String>,
+    pub show_prereleases: bool,
+    pub default_hw_type: Option<String>,
+    /// Flash vor dem Programmieren im Cache sichern
+    pub backup_before_flash: bool,
+    /// Unter Reset verbinden (für schlafende Firmware oder umgewidmete SWD-Pins)
+    pub connect_under_reset: bool,
+    pub after_flash: AfterFlash,
+    pub retry: RetryPolicy,
+    /// Simuliertes Gerät statt Debug-Probe (Schlüssel: `HardwareType::id`)
+    pub demo_device: Option<String>,
+    /// Seriennummern-Format je Hardware-Typ (Schlüssel: `HardwareType::id`)
+    pub serial_formats: BTreeMap<String, SerialFormat>,
+    /// Etikettenvorlage, Formate und Drucker
+    pub label: LabelSettings,
+    /// Muster für Barcode-Scans, in dieser Reihenfolge geprüft
+    pub scan_patterns: Vec<ScanPattern>,
+    /// Freigabeliste für Firmware, `None` = keine Einschränkung
+    pub firmware_policy: Option<PathBuf>,
+    /// Öffentlicher Ed25519-Schlüssel (Base64 oder Hex); gesetzt = Freigabeliste muss signiert sein
+    pub policy_public_key: Option<String>,
+    /// Datei mit dem privaten Ed25519-Schlüssel zum Signieren exportierter Firmware-Pakete
+    pub bundle_signing_key: Option<PathBuf>,
+    /// Öffentlicher Schlüssel (Base64 oder Hex); gesetzt = importierte Pakete müssen damit signiert sein
+    pub bundle_public_key: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            preferred_probe: None,
+            swd_speed_khz: None,
+            firmware_sources: BTreeMap::new(),
+            cache_dir: None,
+            cache_size_mb: 512,
+            data_dir: None,
+            language: Language::default(),
+            github_token: None,
+            show_prereleases: false,
+            default_hw_type: None,
+            backup_before_flash: true,
+            connect_under_reset: false,
+            after_flash: AfterFlash::default(),
+            retry: RetryPolicy::default(),
+            demo_device: None,
+            serial_formats: BTreeMap::new(),
+            label: LabelSettings::default(),
+            scan_patterns: ScanPattern::defaults(),
+            firmware_policy: None,
+            policy_public_key: None,
+            bundle_signing_key: None,
+            bundle_public_key: None,
+        }
+    }
+}
+
+impl Settings {
+    /// Verzeichnis für Konfigurationsdateien, z.B. `~/.config/iRockProgrammer`
+    pub fn config_dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join(APP_DIR))
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        Self::config_dir().map(|d| d.join(SETTINGS_FILE))
+    }
+
+    /// Lädt die Einstellungen; fehlt die Datei, werden Standardwerte verwendet
+    pub fn load() -> Result<Settings, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(path) = Self::path() else {
+            return Ok(Settings::default());
+        };
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let path = Self::path().ok_or("Kein Konfigurationsverzeichnis gefunden")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Repository, aus dem die Firmware für den Hardware-Typ geladen wird
+    pub fn firmware_source(&self, hw_type: HardwareType) -> String {
+        self.firmware_sources
+            .get(hw_type.id())
+            .filter(|repo| !repo.trim().is_empty())
+            .cloned()
+            .unwrap_or_else(|| hw_type.repo().to_string())
+    }
+
+    /// Seriennummern-Format für den Hardware-Typ
+    pub fn serial_format(&self, hw_type: HardwareType) -> SerialFormat {
+        self.serial_formats
+            .get(hw_type.id())
+            .cloned()
+            .unwrap_or_else(|| SerialFormat::for_model(hw_type))
+    }
+
+    /// Cache-Verzeichnis für heruntergeladene Firmware
+    pub fn cache_dir(&self) -> PathBuf {
+        self.cache_dir
+            .clone()
+            .or_else(|| dirs::cache_dir().map(|d| d.join(APP_DIR)))
+            .unwrap_or_else(|| std::env::temp_dir().join(APP_DIR))
+    }
+
+    /// Verzeichnis für Audit-Log, Register und Bedienerprofile
+    pub fn data_dir(&self) -> anyhow::Result<PathBuf> {
+        self.data_dir
+            .clone()
+            .or_else(Self::config_dir)
+            .ok_or_else(|| anyhow::anyhow!("Kein Konfigurationsverzeichnis"))
+    }
+
+    /// Verzeichnis für Flash-Backups
+    pub fn backup_dir(&self) -> PathBuf {
+        self.cache_dir().join("backups")
+    }
+
+    pub fn default_hw_type(&self) -> Option<HardwareType> {
+        self.default_hw_type
+            .as_deref()
+            .and_then(HardwareType::from_id)
+    }
+}
+
+/// Überschreibungen aus Umgebungsvariablen und Kommandozeile.
+/// Werden nicht in die Konfigurationsdatei geschrieben.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub preferred_probe: Option<String>,
+    pub swd_speed_khz: Option<u32>,
+    pub cache_dir: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    pub language: Option<Language>,
+    pub github_token: Option<String>,
+    pub show_prereleases: Option<bool>,
+    pub default_hw_type: Option<String>,
+    pub connect_under_reset: Option<bool>,
+    pub demo_device: Option<String>,
+    /// Bediener für Befehle ohne Oberfläche
+    pub operator: Option<String>,
+    /// PIN dazu, nur aus der Umgebung
+    pub pin: Option<String>,
+}
+
+pub const USAGE: &str = "\
+Usage: iRockProgrammer [OPTIONS] [COMMAND]
+
+Options:
+  --probe <VID:PID[:SERIAL]>   Preferred debug probe       (IROCK_PROBE)
+  --swd-speed <kHz>            SWD speed                   (IROCK_SWD_SPEED)
+  --cache-dir <PATH>           Firmware cache directory    (IROCK_CACHE_DIR)
+  --data-dir <PATH>            Audit log, ledger, operators (IROCK_DATA_DIR)
+  --language <de|en>           User interface language     (IROCK_LANGUAGE)
+  --github-token <TOKEN>       GitHub access token         (IROCK_GITHUB_TOKEN)
+  --prereleases                Show pre-releases           (IROCK_PRERELEASES=1)
+  --no-prereleases             Hide pre-releases           (IROCK_PRERELEASES=0)
+  --hw-type <ID>               Default hardware type       (IROCK_HW_TYPE)
+  --connect-under-reset        Connect under reset         (IROCK_CONNECT_UNDER_RESET=1)
+  --demo <ID>                  Use a simulated device      (IROCK_DEMO)
+  --operator <NAME>            Operator for commands       (IROCK_OPERATOR)
+                               The PIN is only read from IROCK_PIN
+  -h, --help                   Show this help
+
+Commands (run without user interface, require --operator and IROCK_PIN):
+  flash <TAG> <ASSET>...       Download release assets and flash them
+                               (checked against the firmware policy)
+  verify <FILE>...             Compare the device flash with firmware files
+  read <FILE> [<ADDR> <SIZE>]  Read flash (default: all) to a .bin or .hex file
+  erase <ADDR> <SIZE>          Erase a flash range
+  erase chip                   Erase the complete chip";
+
+impl Overrides {
+    /// Liest zuerst die Umgebungsvariablen, Kommandozeilenargumente haben Vorrang
+    pub fn from_env_and_args<I>(args: I) -> Result<Overrides, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut overrides = Overrides::default();
+        overrides.apply_env(|key| std::env::var(key).ok())?;
+        overrides.apply_args(args)?;
+        Ok(overrides)
+    }
+
+    fn apply_env<F>(&mut self, var: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(v) = var("IROCK_PROBE") {
+            self.preferred_probe = Some(v);
+        }
+        if let Some(v) = var("IROCK_SWD_SPEED") {
+            self.swd_speed_khz = Some(parse_speed(&v)?);
+        }
+        if let Some(v) = var("IROCK_CACHE_DIR") {
+            self.cache_dir = Some(PathBuf::from(v));
+        }
+        if let Some(v) = var("IROCK_DATA_DIR") {
+            self.data_dir = Some(PathBuf::from(v));
+        }
+        if let Some(v) = var("IROCK_LANGUAGE") {
+            self.language = Some(parse_language(&v)?);
+        }
+        if let Some(v) = var("IROCK_GITHUB_TOKEN").or_else(|| var("GITHUB_TOKEN")) {
+            self.github_token = Some(v);
+        }
+        if let Some(v) = var("IROCK_PRERELEASES") {
+            self.show_prereleases = Some(matches!(v.as_str(), "1" | "true" | "yes"));
+        }
+        if let Some(v) = var("IROCK_HW_TYPE") {
+            self.default_hw_type = Some(parse_hw_type(&v)?);
+        }
+        if let Some(v) = var("IROCK_CONNECT_UNDER_RESET") {
+            self.connect_under_reset = Some(matches!(v.as_str(), "1" | "true" | "yes"));
+        }
+        if let Some(v) = var("IROCK_DEMO") {
+            self.demo_device = Some(parse_hw_type(&v)?);
+        }
+        if let Some(v) = var("IROCK_OPERATOR") {
+            self.operator = Some(v);
+        }
+        if let Some(v) = var("IROCK_PIN") {
+            self.pin = Some(v);
+        }
+        Ok(())
+    }
+
+    fn apply_args<I>(&mut self, args: I) -> Result<(), String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("Fehlender Wert für {}", name))
+            };
+            match arg.as_str() {
+                "--probe" => self.preferred_probe = Some(value("--probe")?),
+                "--swd-speed" => self.swd_speed_khz = Some(parse_speed(&value("--swd-speed")?)?),
+                "--cache-dir" => self.cache_dir = Some(PathBuf::from(value("--cache-dir")?)),
+                "--data-dir" => self.data_dir = Some(PathBuf::from(value("--data-dir")?)),
+                "--language" => self.language = Some(parse_language(&value("--language")?)?),
+                "--github-token" => self.github_token = Some(value("--github-token")?),
+                "--prereleases" => self.show_prereleases = Some(true),
+                "--no-prereleases" => self.show_prereleases = Some(false),
+                "--hw-type" => self.default_hw_type = Some(parse_hw_type(&value("--hw-type")?)?),
+                "--connect-under-reset" => self.connect_under_reset = Some(true),
+                "--demo" => self.demo_device = Some(parse_hw_type(&value("--demo")?)?),
+                "--operator" => self.operator = Some(value("--operator")?),
+                "-h" | "--help" => return Err(USAGE.to_string()),
+                other => return Err(format!("Unbekannte Option: {}\n\n{}", other, USAGE)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Einstellungen mit angewendeten Überschreibungen
+    pub fn apply(&self, settings: &Settings) -> Settings {
+        let mut effective = settings.clone();
+        if let Some(v) = &self.preferred_probe {
+            effective.preferred_probe = Some(v.clone());
+        }
+        if let Some(v) = self.swd_speed_khz {
+            effective.swd_speed_khz = Some(v);
+        }
+        if let Some(v) = &self.cache_dir {
+            effective.cache_dir = Some(v.clone());
+        }
+        if let Some(v) = &self.data_dir {
+            effective.data_dir = Some(v.clone());
+        }
+        if let Some(v) = self.language {
+            effective.language = v;
+        }
+        if let Some(v) = &self.github_token {
+            effective.github_token = Some(v.clone());
+        }
+        if let Some(v) = self.show_prereleases {
+            effective.show_prereleases = v;
+        }
+        if let Some(v) = &self.default_hw_type {
+            effective.default_hw_type = Some(v.clone());
+        }
+        if let Some(v) = self.connect_under_reset {
+            effective.connect_under_reset = v;
+        }
+        if let Some(v) = &self.demo_device {
+            effective.demo_device = Some(v.clone());
+        }
+        effective
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.preferred_probe.is_none()
+            && self.swd_speed_khz.is_none()
+            && self.cache_dir.is_none()
+            && self.data_dir.is_none()
+            && self.language.is_none()
+            && self.github_token.is_none()
+            && self.show_prereleases.is_none()
+            && self.default_hw_type.is_none()
+            && self.connect_under_reset.is_none()
+            && self.demo_device.is_none()
+    }
+}
+
+fn parse_speed(value: &str) -> Result<u32, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Ungültige SWD-Geschwindigkeit: {}", value))
+}
+
+fn parse_language(value: &str) -> Result<Language, String> {
+    Language::from_code(value.trim()).ok_or_else(|| format!("Unbekannte Sprache: {}", value))
+}
+
+fn parse_hw_type(value: &str) -> Result<String, String> {
+    HardwareType::from_id(value.trim())
+        .map(|hw| hw.id().to_string())
+        .ok_or_else(|| format!("Unbekannter Hardware-Typ: {}", value))
+}
@@ -0,0 +1,55 @@
+//! Ed25519-Signaturen für Freigabelisten und Firmware-Pakete. Schlüssel und
+//! Signaturen werden als Base64 oder Hex angegeben.
+
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::path::Path;
+
+/// Prüft eine Signatur über `data`
+pub fn verify(data: &[u8], signature: &str, public_key: &str) -> anyhow::Result<()> {
+    let key: [u8; 32] = decode(public_key)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Öffentlicher Schlüssel muss 32 Bytes lang sein"))?;
+    let key = VerifyingKey::from_bytes(&key)
+        .map_err(|e| anyhow::anyhow!("Ungültiger öffentlicher Schlüssel: {}", e))?;
+    let signature: [u8; 64] = decode(signature)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Signatur muss 64 Bytes lang sein"))?;
+    key.verify(data, &Signature::from_bytes(&signature))
+        .map_err(|_| anyhow::anyhow!("Signatur ungültig"))
+}
+
+/// Signiert `data` mit dem privaten Schlüssel aus `key_file`; Signatur als Base64
+pub fn sign(data: &[u8], key_file: &Path) -> anyhow::Result<String> {
+    let signature = signing_key(key_file)?.sign(data);
+    Ok(base64::engine::general_purpose::STANDARD.encode(signature.to_bytes()))
+}
+
+/// Öffentlicher Schlüssel zum privaten Schlüssel in `key_file`, zur Weitergabe als Base64
+pub fn public_key(key_file: &Path) -> anyhow::Result<String> {
+    let key = signing_key(key_file)?.verifying_key();
+    Ok(base64::engine::general_purpose::STANDARD.encode(key.to_bytes()))
+}
+
+fn signing_key(key_file: &Path) -> anyhow::Result<SigningKey> {
+    let text = std::fs::read_to_string(key_file)
+        .map_err(|e| anyhow::anyhow!("Schlüssel {}: {}", key_file.display(), e))?;
+    let seed: [u8; 32] = decode(&text)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Privater Schlüssel muss 32 Bytes lang sein"))?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+fn decode(text: &str) -> anyhow::Result<Vec<u8>> {
+    let text = text.trim();
+    let is_hex = text.len().is_multiple_of(2) && text.chars().all(|c| c.is_ascii_hexdigit());
+    if is_hex {
+        return (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).map_err(anyhow::Error::from))
+            .collect();
+    }
+    base64::engine::general_purpose::STANDARD
+        .decode(text)
+        .map_err(|e| anyhow::anyhow!("Weder Hex noch Base64: {}", e))
+}
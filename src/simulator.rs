@@ -0,0 +1,328 @@
+use crate::backend::ProgrammerBackend;
+use crate::hardware::{HardwareType, MemoryRange, ParameterKind};
+use crate::option_bytes::{
+    FLASH_OPTCR, FLASH_OPTKEYR, FLASH_SR, OPTCR_OPTLOCK, OPTCR_OPTSTRT, OPTKEY1, OPTKEY2,
+    OptionBytesKind, RdpLevel,
+};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// Zustand aller simulierten Geräte, bleibt zwischen Verbindungen erhalten
+static DEVICES: Mutex<BTreeMap<String, SimulatedState>> = Mutex::new(BTreeMap::new());
+
+/// Reset-Wert von FLASH_OPTCR bei STM32F4 (RDP Level 0, gesperrt)
+const OPTCR_RESET: u32 = 0x0FFF_AAED;
+const DEMO_VERSION: &str = "1.0.0";
+const DEMO_SERIAL: &str = "DEMO0001";
+/// Unique-ID des Demo-Geräts (12 Bytes, wie bei STM32F4)
+const DEMO_UNIQUE_ID: [u8; 12] = [
+    0x31, 0x00, 0x2A, 0x00, 0x0F, 0x47, 0x30, 0x35, 0x33, 0x37, 0x34, 0x32,
+];
+
+struct SimulatedState {
+    flash_base: u64,
+    flash: Vec<u8>,
+    ram: Vec<u8>,
+    optcr: u32,
+    /// Wirksame RDP-Stufe. Eine höhere Stufe aus FLASH_OPTCR gilt erst nach
+    /// einem Power-Cycle, eine Regression sofort (mit Löschen des Flash).
+    rdp: RdpLevel,
+    /// Fortschritt der Schlüsselfolge in FLASH_OPTKEYR
+    optkey_stage: u8,
+    halted: bool,
+    pc: u64,
+}
+
+impl SimulatedState {
+    /// Gerät mit vorinstallierter Demo-Firmware, Info-Block und Parametern
+    fn new(hw_type: HardwareType) -> Self {
+        let model = hw_type.model();
+        let mut state = Self {
+            flash_base: model.flash.base,
+            flash: vec![0xFF; model.flash.size as usize],
+            ram: vec![0; model.ram.size as usize],
+            optcr: OPTCR_RESET,
+            rdp: RdpLevel::Level0,
+            optkey_stage: 0,
+            halted: false,
+            pc: 0,
+        };
+        let application = model.application();
+        let reset_vector = application.base + 0x201;
+        state.put(application.base, &(model.ram.end() as u32).to_le_bytes());
+        state.put(application.base + 4, &(reset_vector as u32).to_le_bytes());
+        if let Some(info) = model.info_block {
+            state.put(info, &0x4B43_5249u32.to_le_bytes());
+            state.put(info + 4, &model.board_id.unwrap_or(0).to_le_bytes());
+            state.put(info + 8, DEMO_VERSION.as_bytes());
+            state.put(info + 8 + DEMO_VERSION.len() as u64, &[0]);
+        }
+        for parameter in &model.parameters {
+            match parameter.kind {
+                ParameterKind::String if parameter.name == "serial" => {
+                    let mut raw = vec![0u8; parameter.length];
+                    let len = DEMO_SERIAL.len().min(raw.len());
+                    raw[..len].copy_from_slice(&DEMO_SERIAL.as_bytes()[..len]);
+                    state.put(parameter.address, &raw);
+                }
+                ParameterKind::U32 => state.put(parameter.address, &100u32.to_le_bytes()),
+                ParameterKind::String => {}
+            }
+        }
+        state.pc = reset_vector & !1;
+        state
+    }
+
+    /// Schreibt direkt in den simulierten Flash (nur zum Vorbelegen)
+    fn put(&mut self, address: u64, data: &[u8]) {
+        let Some(offset) = address.checked_sub(self.flash_base) else {
+            return;
+        };
+        let offset = offset as usize;
+        if let Some(target) = self.flash.get_mut(offset..offset + data.len()) {
+            target.copy_from_slice(data);
+        }
+    }
+}
+
+/// Simuliertes iRock-Gerät mit Flash, RAM, Option-Bytes und Parameterblock
+pub struct SimulatedDevice {
+    hw_type: HardwareType,
+}
+
+impl SimulatedDevice {
+    /// Verbindet mit dem simulierten Gerät; ein abweichendes Target schlägt fehl
+    pub fn connect(hw_type: HardwareType, target: Option<&str>) -> anyhow::Result<Self> {
+        let model = hw_type.model();
+        if let Some(target) = target
+            && !target.is_empty()
+            && !target.eq_ignore_ascii_case(&model.target)
+        {
+            anyhow::bail!(
+                "Target {} not found on the demo device ({})",
+                target,
+                model.target
+            );
+        }
+        Ok(Self { hw_type })
+    }
+
+    /// Setzt das simulierte Gerät auf den Auslieferungszustand zurück
+    pub fn reset_state(hw_type: HardwareType) {
+        lock().remove(hw_type.id());
+    }
+
+    /// Spannung aus und wieder an: Option-Bytes werden neu geladen, eine
+    /// gesetzte Readout-Protection wird damit wirksam
+    pub fn power_cycle(hw_type: HardwareType) {
+        if let Some(state) = lock().get_mut(hw_type.id()) {
+            state.rdp = RdpLevel::from_byte((state.optcr >> 8) as u8);
+            state.optcr |= OPTCR_OPTLOCK;
+            state.optkey_stage = 0;
+            state.halted = false;
+        }
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut SimulatedState) -> T) -> T {
+        let mut devices = lock();
+        let state = devices
+            .entry(self.hw_type.id().to_string())
+            .or_insert_with(|| SimulatedState::new(self.hw_type));
+        f(state)
+    }
+
+    fn has_option_bytes(&self) -> bool {
+        self.hw_type.model().option_bytes == Some(OptionBytesKind::Stm32f4)
+    }
+
+    fn erase_sectors(&self, state: &mut SimulatedState, range: MemoryRange) {
+        let model = self.hw_type.model();
+        for sector in model.sectors(range) {
+            let start = (sector.base - model.flash.base) as usize;
+            state.flash[start..start + sector.size as usize].fill(0xFF);
+        }
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, BTreeMap<String, SimulatedState>> {
+    DEVICES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl ProgrammerBackend for SimulatedDevice {
+    fn info(&self) -> String {
+        format!("Demo device (simulated {})", self.hw_type)
+    }
+
+    fn probe(&self) -> Option<String> {
+        Some("demo".to_string())
+    }
+
+    fn read(&mut self, address: u64, data: &mut [u8]) -> anyhow::Result<()> {
+        let model = self.hw_type.model();
+        let range = MemoryRange {
+            base: address,
+            size: data.len() as u64,
+        };
+        let option_bytes = self.has_option_bytes();
+        self.with_state(|state| {
+            if model.flash.contains(address) && range.end() <= model.flash.end() {
+                if state.rdp != RdpLevel::Level0 {
+                    anyhow::bail!("Flash is protected by readout protection (RDP Level 1)");
+                }
+                let offset = (address - model.flash.base) as usize;
+                data.copy_from_slice(&state.flash[offset..offset + data.len()]);
+                return Ok(());
+            }
+            if model.ram.contains(address) && range.end() <= model.ram.end() {
+                let offset = (address - model.ram.base) as usize;
+                data.copy_from_slice(&state.ram[offset..offset + data.len()]);
+                return Ok(());
+            }
+            let unique_id = MemoryRange {
+                base: model.unique_id_address,
+                size: DEMO_UNIQUE_ID.len() as u64,
+            };
+            if unique_id.contains(address) && range.end() <= unique_id.end() {
+                let offset = (address - unique_id.base) as usize;
+                data.copy_from_slice(&DEMO_UNIQUE_ID[offset..offset + data.len()]);
+                return Ok(());
+            }
+            let word = match address {
+                a if a == model.idcode_address => {
+                    // Revision in den oberen 16 Bit
+                    Some(0x1000_0000 | model.device_id.unwrap_or(0) as u32)
+                }
+                FLASH_OPTCR if option_bytes => Some(state.optcr),
+                FLASH_SR if option_bytes => Some(0),
+                _ => None,
+            };
+            match word {
+                Some(word) if data.len() == 4 => {
+                    data.copy_from_slice(&word.to_le_bytes());
+                    Ok(())
+                }
+                _ => anyhow::bail!("Simulated bus fault at 0x{:08X}", address),
+            }
+        })
+    }
+
+    fn write_word_32(&mut self, address: u64, value: u32) -> anyhow::Result<()> {
+        let model = self.hw_type.model();
+        let option_bytes = self.has_option_bytes();
+        self.with_state(|state| {
+            if model.ram.contains(address) && address + 4 <= model.ram.end() {
+                let offset = (address - model.ram.base) as usize;
+                state.ram[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+                return Ok(());
+            }
+            match address {
+                FLASH_OPTKEYR if option_bytes => {
+                    state.optkey_stage = match (state.optkey_stage, value) {
+                        (0, OPTKEY1) => 1,
+                        (1, OPTKEY2) => {
+                            state.optcr &= !OPTCR_OPTLOCK;
+                            0
+                        }
+                        _ => 0,
+                    };
+                    Ok(())
+                }
+                FLASH_OPTCR if option_bytes => {
+                    if state.optcr & OPTCR_OPTLOCK != 0 {
+                        // Schreibzugriffe auf gesperrte Option-Bytes werden ignoriert
+                        return Ok(());
+                    }
+                    state.optcr = value & !OPTCR_OPTSTRT;
+                    if value & OPTCR_OPTSTRT != 0 {
+                        let level = RdpLevel::from_byte((value >> 8) as u8);
+                        if state.rdp != RdpLevel::Level0 && level == RdpLevel::Level0 {
+                            // RDP-Regression löscht den gesamten Flash und gilt sofort
+                            state.flash.fill(0xFF);
+                            state.rdp = level;
+                        }
+                    }
+                    Ok(())
+                }
+                FLASH_SR if option_bytes => Ok(()),
+                _ => anyhow::bail!("Simulated bus fault at 0x{:08X}", address),
+            }
+        })
+    }
+
+    fn flash(&mut self, data: &[(u64, &[u8])]) -> anyhow::Result<()> {
+        let flash = self.hw_type.model().flash;
+        self.with_state(|state| {
+            if state.rdp != RdpLevel::Level0 {
+                anyhow::bail!("Flash is protected by readout protection (RDP Level 1)");
+            }
+            for (address, bytes) in data {
+                let range = MemoryRange {
+                    base: *address,
+                    size: bytes.len() as u64,
+                };
+                if range.base < flash.base || range.end() > flash.end() {
+                    anyhow::bail!("{} lies outside the flash {}", range, flash);
+                }
+                self.erase_sectors(state, range);
+            }
+            for (address, bytes) in data {
+                let offset = (address - flash.base) as usize;
+                state.flash[offset..offset + bytes.len()].copy_from_slice(bytes);
+            }
+            Ok(())
+        })
+    }
+
+    fn erase(&mut self, range: Option<MemoryRange>) -> anyhow::Result<()> {
+        self.with_state(|state| {
+            match range {
+                Some(range) => self.erase_sectors(state, range),
+                None => state.flash.fill(0xFF),
+            }
+            Ok(())
+        })
+    }
+
+    fn reset(&mut self) -> anyhow::Result<()> {
+        let reset_vector = self.reset_vector();
+        self.with_state(|state| {
+            state.halted = false;
+            state.pc = reset_vector;
+        });
+        Ok(())
+    }
+
+    fn reset_and_halt(&mut self) -> anyhow::Result<()> {
+        let reset_vector = self.reset_vector();
+        self.with_state(|state| {
+            state.halted = true;
+            state.pc = reset_vector;
+        });
+        Ok(())
+    }
+
+    fn halt(&mut self) -> anyhow::Result<u64> {
+        Ok(self.with_state(|state| {
+            state.halted = true;
+            state.pc
+        }))
+    }
+
+    fn run(&mut self) -> anyhow::Result<()> {
+        self.with_state(|state| state.halted = false);
+        Ok(())
+    }
+}
+
+impl SimulatedDevice {
+    /// Reset-Vektor der Anwendung; ohne Firmware bleibt der Kern im Bootloader-ROM
+    fn reset_vector(&self) -> u64 {
+        let model = self.hw_type.model();
+        let base = model.application().base;
+        self.with_state(|state| {
+            let offset = (base - model.flash.base) as usize + 4;
+            let bytes: [u8; 4] = state.flash[offset..offset + 4].try_into().unwrap();
+            (u32::from_le_bytes(bytes) & !1) as u64
+        })
+    }
+}
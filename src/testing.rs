@@ -0,0 +1,93 @@
+//! Gemeinsame Hilfen für Tests. Jeder Test bekommt ein eigenes temporäres
+//! Verzeichnis für Audit-Log, Register, Cache und Backups.
+
+use crate::backend::ProgrammerBackend;
+use crate::flash::FlashConfig;
+use crate::hardware::{HardwareType, MemoryRange};
+use crate::probe::ProbeConfig;
+use crate::settings::Settings;
+use crate::simulator::SimulatedDevice;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use tempfile::TempDir;
+
+/// Das simulierte Gerät ist global, Tests darauf laufen nacheinander
+static DEVICE: Mutex<()> = Mutex::new(());
+
+/// Einstellungen, die nur in `dir` schreiben
+pub(crate) fn settings(dir: &Path) -> Settings {
+    Settings {
+        data_dir: Some(dir.join("data")),
+        cache_dir: Some(dir.join("cache")),
+        backup_before_flash: false,
+        ..Settings::default()
+    }
+}
+
+/// Temporäres Verzeichnis mit passenden Einstellungen, ohne simuliertes Gerät
+pub(crate) fn environment() -> (TempDir, Settings) {
+    let dir = tempfile::tempdir().expect("temporäres Verzeichnis");
+    let settings = settings(dir.path());
+    (dir, settings)
+}
+
+/// Simuliertes irock-424 im Auslieferungszustand, für die Dauer des Tests gesperrt
+pub(crate) struct TestDevice {
+    pub hw_type: HardwareType,
+    pub settings: Settings,
+    pub dir: TempDir,
+    _guard: MutexGuard<'static, ()>,
+}
+
+pub(crate) fn device() -> TestDevice {
+    let guard = DEVICE.lock().unwrap_or_else(|e| e.into_inner());
+    let hw_type = HardwareType::from_id("irock-424").expect("irock-424 im Katalog");
+    SimulatedDevice::reset_state(hw_type);
+    let (dir, mut settings) = environment();
+    settings.demo_device = Some(hw_type.id().to_string());
+    TestDevice {
+        hw_type,
+        settings,
+        dir,
+        _guard: guard,
+    }
+}
+
+impl TestDevice {
+    pub(crate) fn probe(&self) -> ProbeConfig {
+        ProbeConfig::from_settings(&self.settings)
+    }
+
+    pub(crate) fn connect(&self) -> Box<dyn ProgrammerBackend> {
+        Box::new(SimulatedDevice::connect(self.hw_type, None).unwrap())
+    }
+
+    pub(crate) fn flash_config(&self, images: &[String]) -> FlashConfig {
+        FlashConfig::new(images, self.hw_type, &self.settings)
+    }
+
+    /// Anwendungs-Image mit gültiger Vektortabelle als Datei im Testverzeichnis
+    pub(crate) fn application_image(&self) -> String {
+        let model = self.hw_type.model();
+        let application = model.application();
+        let mut data = vec![0xA5u8; 0x800];
+        data[..4].copy_from_slice(&(model.ram.end() as u32).to_le_bytes());
+        data[4..8].copy_from_slice(&(application.base as u32 + 0x101).to_le_bytes());
+        let path = self.dir.path().join("app.bin");
+        std::fs::write(&path, data).unwrap();
+        path.display().to_string()
+    }
+
+    pub(crate) fn region(&self, name: &str) -> MemoryRange {
+        self.hw_type.model().region(name).unwrap().range
+    }
+}
+
+pub(crate) fn read(
+    backend: &mut dyn ProgrammerBackend,
+    range: MemoryRange,
+) -> anyhow::Result<Vec<u8>> {
+    let mut data = vec![0u8; range.size as usize];
+    backend.read(range.base, &mut data)?;
+    Ok(data)
+}
@@ -0,0 +1,177 @@
+use crate::MyApp;
+use crate::audit::{self, Action, AuditRecord};
+use crate::settings::Settings;
+use eframe::egui;
+use std::path::Path;
+
+impl MyApp {
+    /// Audit-Log durchsuchen, Hash-Kette prüfen und exportieren
+    pub(crate) fn audit_view(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        ui.heading("Audit log");
+        ui.separator();
+        if let Ok(path) = audit::path(settings) {
+            ui.weak(path.display().to_string());
+        }
+        if self.audit_records.is_none() || ui.button("Reload").clicked() {
+            self.audit_records = Some(audit::load(settings).map_err(|e| format!("{:#}", e)));
+        }
+        let records = match &self.audit_records {
+            Some(Ok(records)) => records,
+            Some(Err(e)) => {
+                ui.colored_label(egui::Color32::RED, format!("Fehler beim Laden: {}", e));
+                return;
+            }
+            None => return,
+        };
+        match audit::verify_chain(records) {
+            Ok(()) => {
+                ui.colored_label(
+                    egui::Color32::GREEN,
+                    format!("{} entries, hash chain intact", records.len()),
+                );
+            }
+            Err(index) => {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!(
+                        "Hash chain broken at entry {} ({}): the log was modified",
+                        index + 1,
+                        records[index].time.format("%Y-%m-%d %H:%M:%S")
+                    ),
+                );
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.audit_filter)
+                    .hint_text("serial, hardware, firmware, operator …"),
+            );
+            egui::ComboBox::from_id_salt("audit_action")
+                .selected_text(
+                    self.audit_action
+                        .map_or("All actions".to_string(), |a| a.to_string()),
+                )
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.audit_action, None, "All actions");
+                    for action in Action::all() {
+                        ui.selectable_value(
+                            &mut self.audit_action,
+                            Some(*action),
+                            action.to_string(),
+                        );
+                    }
+                });
+        });
+        let filter = self.audit_filter.trim().to_lowercase();
+        let shown: Vec<AuditRecord> = records
+            .iter()
+            .rev()
+            .filter(|r| self.audit_action.is_none_or(|a| r.action == a))
+            .filter(|r| filter.is_empty() || matches(r, &filter))
+            .cloned()
+            .collect();
+
+        ui.horizontal(|ui| {
+            ui.label("Export to:");
+            ui.text_edit_singleline(&mut self.audit_export_path);
+            let path = self.audit_export_path.trim().to_string();
+            let enabled = !path.is_empty() && !shown.is_empty();
+            if ui
+                .add_enabled(enabled, egui::Button::new("Export CSV"))
+                .clicked()
+            {
+                self.audit_message = Some(export_message(
+                    audit::export_csv(&shown, Path::new(&path)),
+                    shown.len(),
+                    &path,
+                ));
+            }
+            if ui
+                .add_enabled(enabled, egui::Button::new("Export JSON"))
+                .clicked()
+            {
+                self.audit_message = Some(export_message(
+                    audit::export_json(&shown, Path::new(&path)),
+                    shown.len(),
+                    &path,
+                ));
+            }
+        });
+        if let Some(msg) = &self.audit_message {
+            ui.label(msg);
+        }
+        ui.add_space(8.0);
+
+        if shown.is_empty() {
+            ui.label("No entries.");
+            return;
+        }
+        egui::ScrollArea::both().show(ui, |ui| {
+            egui::Grid::new("audit_log")
+                .striped(true)
+                .num_columns(9)
+                .show(ui, |ui| {
+                    for title in [
+                        "Time", "Operator", "Action", "Hardware", "Firmware", "Probe", "Device",
+                        "Serial", "Result",
+                    ] {
+                        ui.strong(title);
+                    }
+                    ui.end_row();
+                    for r in &shown {
+                        ui.label(r.time.format("%Y-%m-%d %H:%M:%S").to_string());
+                        ui.label(r.operator.as_deref().unwrap_or("-"));
+                        ui.label(r.action.to_string());
+                        ui.label(r.hw_type.as_deref().unwrap_or("-"));
+                        let firmware = ui.label(r.firmware_summary());
+                        if !r.firmware.is_empty() {
+                            firmware.on_hover_text(
+                                r.firmware
+                                    .iter()
+                                    .map(|f| format!("{}  SHA-256 {}", f.asset, f.sha256))
+                                    .collect::<Vec<_>>()
+                                    .join("\n"),
+                            );
+                        }
+                        ui.label(r.probe.as_deref().unwrap_or("-"));
+                        ui.monospace(r.unique_id.as_deref().unwrap_or("-"));
+                        ui.monospace(r.serial.as_deref().unwrap_or("-"));
+                        let (color, text) = if r.success {
+                            (egui::Color32::GREEN, "OK")
+                        } else {
+                            (egui::Color32::RED, "FAIL")
+                        };
+                        let result = ui.colored_label(color, text);
+                        if !r.details.is_empty() {
+                            result.on_hover_text(&r.details);
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+}
+
+/// Volltextsuche über die angezeigten Felder; `filter` ist bereits kleingeschrieben
+fn matches(record: &AuditRecord, filter: &str) -> bool {
+    [
+        record.operator.as_deref(),
+        record.hw_type.as_deref(),
+        record.probe.as_deref(),
+        record.serial.as_deref(),
+        Some(record.details.as_str()),
+        Some(record.firmware_summary().as_str()),
+    ]
+    .into_iter()
+    .flatten()
+    .any(|field| field.to_lowercase().contains(filter))
+}
+
+fn export_message(result: anyhow::Result<()>, count: usize, path: &str) -> String {
+    match result {
+        Ok(()) => format!("{} Einträge nach {} exportiert.", count, path),
+        Err(e) => format!("Fehler beim Exportieren: {:#}", e),
+    }
+}
@@ -0,0 +1,72 @@
+use crate::MyApp;
+use crate::backup;
+use crate::hardware::HardwareType;
+use crate::operator::Permission;
+use crate::settings::Settings;
+use eframe::egui;
+
+impl MyApp {
+    /// Liste der Backups für den gewählten Hardware-Typ mit Wiederherstellung
+    pub(crate) fn backups_ui(&mut self, ui: &mut egui::Ui, hw: HardwareType, settings: &Settings) {
+        if !self.require(ui, Permission::RestoreBackup) {
+            return;
+        }
+        if ui.button("Refresh").clicked() {
+            self.backups = None;
+        }
+        let backups = self
+            .backups
+            .get_or_insert_with(|| backup::list(&settings.backup_dir(), Some(hw)));
+        if backups.is_empty() {
+            ui.label("No backups found.");
+            return;
+        }
+        for (i, backup) in backups.iter().enumerate() {
+            let selected = self.selected_backup == Some(i);
+            if ui.selectable_label(selected, backup.label()).clicked() {
+                self.selected_backup = Some(i);
+                self.confirm_restore = false;
+                self.restore_mismatch = false;
+                self.restore_other_device = false;
+                self.restore_message = None;
+            }
+        }
+        if let Some(backup) = self.selected_backup.and_then(|i| backups.get(i)) {
+            ui.add_space(8.0);
+            ui.label(format!("Location: {}", backup.dir.display()));
+            ui.checkbox(
+                &mut self.confirm_restore,
+                "Overwrite the complete flash of the connected device with this backup",
+            );
+            if self.restore_mismatch {
+                ui.checkbox(
+                    &mut self.restore_other_device,
+                    "Restore onto this different device anyway \
+                     (copies serial number and calibration of the backed-up unit)",
+                );
+            }
+            if ui
+                .add_enabled(self.confirm_restore, egui::Button::new("Restore backup"))
+                .clicked()
+            {
+                self.confirm_restore = false;
+                let result = backup::restore(
+                    settings,
+                    backup,
+                    self.restore_mismatch && self.restore_other_device,
+                );
+                self.restore_other_device = false;
+                self.restore_mismatch = result
+                    .as_ref()
+                    .is_err_and(|e| e.downcast_ref::<backup::DeviceMismatch>().is_some());
+                self.restore_message = Some(match result {
+                    Ok(()) => "Backup erfolgreich wiederhergestellt.".to_string(),
+                    Err(e) => format!("Fehler beim Wiederherstellen: {}", e),
+                });
+            }
+        }
+        if let Some(msg) = &self.restore_message {
+            ui.label(msg);
+        }
+    }
+}
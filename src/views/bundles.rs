@@ -0,0 +1,277 @@
+use crate::MyApp;
+use crate::audit::{Action, AuditRecord};
+use crate::bundle::{self, BundleHandle, BundleMsg};
+use crate::hardware::HardwareType;
+use crate::operator::Permission;
+use crate::settings::Settings;
+use eframe::egui;
+use std::path::PathBuf;
+use std::time::Duration;
+
+impl MyApp {
+    /// Offline-Firmware-Paket für Servicepartner zusammenstellen
+    pub(crate) fn bundles_view(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        ui.heading("Export firmware bundle");
+        ui.separator();
+        if !self.require(ui, Permission::ExportBundle) {
+            return;
+        }
+        self.poll_bundle(settings);
+        let busy = self.bundle_handle.is_some();
+        if settings.bundle_signing_key.is_none() {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                "No bundle signing key configured (Settings → Bundle signing key).",
+            );
+        }
+
+        ui.label("1. Select hardware types:");
+        ui.horizontal_wrapped(|ui| {
+            for hw_type in HardwareType::all() {
+                let mut selected = self.bundle_hw_types.contains(hw_type);
+                if ui
+                    .add_enabled(
+                        !busy,
+                        egui::Checkbox::new(&mut selected, hw_type.to_string()),
+                    )
+                    .changed()
+                {
+                    if selected {
+                        self.bundle_hw_types.push(*hw_type);
+                    } else {
+                        self.bundle_hw_types.retain(|hw| hw != hw_type);
+                    }
+                    self.bundle_releases = None;
+                    self.bundle_selection.clear();
+                }
+            }
+        });
+        if ui
+            .add_enabled(
+                !busy && !self.bundle_hw_types.is_empty(),
+                egui::Button::new("Load releases"),
+            )
+            .clicked()
+        {
+            self.bundle_releases = None;
+            self.bundle_selection.clear();
+            self.bundle_message = Some("Loading releases...".to_string());
+            self.bundle_handle = Some(BundleHandle::load_releases(
+                settings.clone(),
+                self.bundle_hw_types.clone(),
+            ));
+        }
+
+        if let Some(Ok(releases)) = self.bundle_releases.clone() {
+            ui.add_space(8.0);
+            ui.label("2. Select releases:");
+            if releases.is_empty() {
+                ui.label("No firmware found.");
+            }
+            egui::ScrollArea::vertical()
+                .max_height(240.0)
+                .show(ui, |ui| {
+                    for (hw_type, release) in &releases {
+                        let key = (*hw_type, release.tag_name.clone());
+                        let mut selected = self.bundle_selection.contains(&key);
+                        let title = format!(
+                            "{} {}{} ({} assets)",
+                            hw_type,
+                            release.tag_name,
+                            if release.prerelease {
+                                " (Pre-release)"
+                            } else {
+                                ""
+                            },
+                            release.stm32_assets.len()
+                        );
+                        if ui
+                            .add_enabled(!busy, egui::Checkbox::new(&mut selected, title))
+                            .changed()
+                        {
+                            if selected {
+                                self.bundle_selection.push(key);
+                            } else {
+                                self.bundle_selection.retain(|k| *k != key);
+                            }
+                        }
+                    }
+                });
+
+            ui.add_space(8.0);
+            ui.label("3. Export:");
+            if self.bundle_path.is_empty() {
+                self.bundle_path = format!(
+                    "firmware-{}.{}",
+                    chrono::Local::now().format("%Y%m%d"),
+                    bundle::EXTENSION
+                );
+            }
+            ui.horizontal(|ui| {
+                ui.label("File:");
+                ui.text_edit_singleline(&mut self.bundle_path);
+            });
+            let ready = !busy
+                && !self.bundle_selection.is_empty()
+                && settings.bundle_signing_key.is_some()
+                && !self.bundle_path.trim().is_empty();
+            if ui
+                .add_enabled(ready, egui::Button::new("Export bundle"))
+                .clicked()
+            {
+                let selected = releases
+                    .iter()
+                    .filter(|(hw, r)| self.bundle_selection.contains(&(*hw, r.tag_name.clone())))
+                    .cloned()
+                    .collect();
+                self.bundle_handle = Some(BundleHandle::export(
+                    settings.clone(),
+                    selected,
+                    PathBuf::from(self.bundle_path.trim()),
+                ));
+            }
+        }
+        if busy {
+            ui.spinner();
+            ui.ctx().request_repaint_after(Duration::from_millis(200));
+        }
+        if let Some(msg) = &self.bundle_message {
+            ui.label(msg);
+        }
+    }
+
+    /// Nachrichten des Hintergrund-Threads übernehmen
+    fn poll_bundle(&mut self, settings: &Settings) {
+        let Some(handle) = &self.bundle_handle else {
+            return;
+        };
+        let mut finished = false;
+        while let Ok(msg) = handle.rx.try_recv() {
+            match msg {
+                BundleMsg::Releases(result) => {
+                    self.bundle_message = result.as_ref().err().cloned();
+                    self.bundle_releases = Some(result);
+                    finished = true;
+                }
+                BundleMsg::Progress(msg) => self.bundle_message = Some(msg),
+                BundleMsg::Exported(result) => {
+                    let mut record = AuditRecord::new(Action::FirmwareBundle, None);
+                    record.success = result.is_ok();
+                    record.details = match &result {
+                        Ok(msg) => msg.clone(),
+                        Err(e) => format!("Export {}: {}", self.bundle_path, e),
+                    };
+                    let mut message = match result {
+                        Ok(msg) => msg,
+                        Err(e) => format!("Fehler: {}", e),
+                    };
+                    if let Err(e) = crate::audit::append(settings, &record) {
+                        message.push_str(&format!(
+                            "\nAudit-Log konnte nicht geschrieben werden: {:#}",
+                            e
+                        ));
+                    }
+                    self.bundle_message = Some(message);
+                    finished = true;
+                }
+            }
+        }
+        if finished {
+            self.bundle_handle = None;
+        }
+    }
+
+    /// Import eines Pakets in der Flash-Ansicht; trägt es als Firmware-Quelle
+    /// der enthaltenen Hardware-Typen ein
+    pub(crate) fn bundle_import_ui(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        if !self.allowed(Permission::ImportBundle) {
+            ui.weak(format!(
+                "{} requires the {} role.",
+                Permission::ImportBundle,
+                Permission::ImportBundle.required_role()
+            ));
+            return;
+        }
+        let sources: Vec<String> = HardwareType::all()
+            .iter()
+            .filter(|hw| bundle::source_dir(&settings.firmware_source(**hw)).is_some())
+            .map(|hw| hw.to_string())
+            .collect();
+        if !sources.is_empty() {
+            ui.weak(format!("Bundle in use for: {}", sources.join(", ")));
+        }
+        if settings
+            .bundle_public_key
+            .as_deref()
+            .is_none_or(|k| k.trim().is_empty())
+        {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                if self.allowed(Permission::Settings) {
+                    "No bundle public key configured: the signature will NOT be verified."
+                } else {
+                    "No bundle public key configured: only an engineer can import bundles."
+                },
+            );
+        }
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.add(
+                egui::TextEdit::singleline(&mut self.bundle_import_path)
+                    .hint_text(format!("*.{}", bundle::EXTENSION)),
+            );
+            let path = self.bundle_import_path.trim().to_string();
+            if ui
+                .add_enabled(!path.is_empty(), egui::Button::new("Import"))
+                .clicked()
+            {
+                self.import_bundle(&PathBuf::from(path), settings);
+            }
+        });
+        if let Some(msg) = &self.bundle_import_message {
+            ui.label(msg);
+        }
+    }
+
+    fn import_bundle(&mut self, path: &std::path::Path, settings: &Settings) {
+        let mut record = AuditRecord::new(Action::FirmwareBundle, None);
+        let allow_unsigned = self.allowed(Permission::Settings);
+        let result = bundle::import(settings, path, allow_unsigned).and_then(|imported| {
+            let source = bundle::source(&imported.dir);
+            for hw_type in &imported.hw_types {
+                self.settings
+                    .firmware_sources
+                    .insert(hw_type.id().to_string(), source.clone());
+                self.settings_draft
+                    .firmware_sources
+                    .insert(hw_type.id().to_string(), source.clone());
+            }
+            self.settings
+                .save()
+                .map_err(|e| anyhow::anyhow!("Fehler beim Speichern der Einstellungen: {}", e))?;
+            Ok(imported)
+        });
+        record.success = result.is_ok();
+        match result {
+            Ok(imported) => {
+                record.details = format!("Import {}: {}", path.display(), imported.summary());
+                self.bundle_import_message = Some(imported.summary());
+                self.firmware_policy = None;
+                self.selected_firmware = None;
+                self.reset_download();
+            }
+            Err(e) => {
+                record.details = format!("Import {}: {:#}", path.display(), e);
+                self.bundle_import_message = Some(format!("Fehler: {:#}", e));
+            }
+        }
+        if let Err(e) = crate::audit::append(settings, &record) {
+            self.bundle_import_message
+                .get_or_insert_default()
+                .push_str(&format!(
+                    "\nAudit-Log konnte nicht geschrieben werden: {:#}",
+                    e
+                ));
+        }
+    }
+}
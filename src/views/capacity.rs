@@ -0,0 +1,111 @@
+use crate::MyApp;
+use crate::audit::{Action, AuditRecord};
+use crate::operator::Permission;
+use crate::parameters::{self, ParameterValue};
+use crate::probe::ProbeConfig;
+use crate::settings::Settings;
+use eframe::egui;
+
+impl MyApp {
+    /// Kapazität lesen und schreiben
+    pub(crate) fn capacity_view(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        ui.heading("Set capacity");
+        ui.separator();
+        if !self.require(ui, Permission::SetCapacity) {
+            return;
+        }
+        let Some(hw) = self.selected_hw_type else {
+            ui.label("Select the hardware type in the Flash view first.");
+            return;
+        };
+        ui.label(format!("Hardware type: {}", hw));
+        if hw.model().parameter("capacity").is_none() {
+            ui.label("This hardware type has no capacity parameter.");
+            return;
+        }
+        let target = hw.model().target.clone();
+        let elf = self
+            .downloaded_images
+            .iter()
+            .filter_map(|i| i.as_ref().ok())
+            .find(|i| i.is_elf)
+            .cloned();
+
+        ui.horizontal(|ui| {
+            if ui.button("Read current capacity").clicked() {
+                let result = (|| {
+                    let mut backend =
+                        crate::probe::attach(&ProbeConfig::from_settings(settings), Some(&target))?;
+                    parameters::read(backend.as_mut(), hw, "capacity", elf.as_ref())
+                })();
+                self.capacity_message = None;
+                self.capacity_current = match result {
+                    Ok(Some(value)) => {
+                        if let ParameterValue::U32(raw) = &value {
+                            self.capacity_input = *raw;
+                        }
+                        Some(value.to_string())
+                    }
+                    Ok(None) => Some("(none)".to_string()),
+                    Err(e) => {
+                        self.capacity_message = Some(format!("Fehler beim Lesen: {:#}", e));
+                        None
+                    }
+                };
+            }
+            if let Some(current) = &self.capacity_current {
+                ui.label(format!("Current: {}", current));
+            }
+        });
+        ui.add_space(8.0);
+
+        ui.horizontal(|ui| {
+            ui.label("New capacity:");
+            ui.add(egui::DragValue::new(&mut self.capacity_input));
+        });
+        if ui.button("Write capacity").clicked() {
+            let value = self.capacity_input;
+            let record = AuditRecord::new(Action::SetCapacity, Some(hw));
+            let result = crate::audit::run(settings, record, |record| {
+                let mut backend =
+                    crate::probe::attach(&ProbeConfig::from_settings(settings), Some(&target))?;
+                let identity = crate::device::identify(backend.as_mut())?;
+                record.device(backend.as_ref(), &identity);
+                if identity.contradicts(hw) {
+                    anyhow::bail!(
+                        "Angeschlossenes Gerät passt nicht zu {}: {}",
+                        hw,
+                        identity.summary()
+                    );
+                }
+                record.serial = parameters::read(backend.as_mut(), hw, "serial", elf.as_ref())
+                    .ok()
+                    .flatten()
+                    .map(|serial| serial.to_string());
+                let previous = parameters::read(backend.as_mut(), hw, "capacity", elf.as_ref())?;
+                record.details = match &previous {
+                    Some(previous) => format!("{} → {}", previous, value),
+                    None => value.to_string(),
+                };
+                parameters::write(
+                    backend.as_mut(),
+                    hw,
+                    "capacity",
+                    &ParameterValue::U32(value),
+                    elf.as_ref(),
+                )
+            });
+            self.capacity_message = Some(match result {
+                Ok(()) => {
+                    crate::oplog::record(format!("Kapazität {} geschrieben", value));
+                    self.capacity_current = Some(value.to_string());
+                    format!("Kapazität {} geschrieben.", value)
+                }
+                Err(e) => format!("Fehler: {:#}", e),
+            });
+        }
+        if let Some(msg) = &self.capacity_message {
+            ui.label(msg);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::diagnostics::{Diagnosis, ErrorKind};
+use crate::settings::Language;
+use crate::{MyApp, View};
+use eframe::egui;
+
+impl MyApp {
+    /// Fehlerklasse mit Erklärung, Abhilfe und Verweis auf das Handbuch
+    pub(crate) fn diagnosis_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        diagnosis: &Diagnosis,
+        language: Language,
+    ) {
+        let kind = diagnosis.kind;
+        if kind == ErrorKind::Unknown {
+            return;
+        }
+        ui.colored_label(
+            egui::Color32::RED,
+            egui::RichText::new(kind.title(language)).strong(),
+        );
+        ui.label(kind.explanation(language));
+        ui.label(format!("→ {}", kind.fix(language)));
+        if let Some(voltage) = diagnosis.target_voltage {
+            ui.label(format!("VTref: {:.2} V", voltage));
+        }
+        ui.horizontal(|ui| {
+            if ui.link(manual_link(language)).clicked() {
+                self.help_section = Some(kind);
+                self.active_view = View::Help;
+            }
+            if matches!(kind, ErrorKind::Wiring | ErrorKind::Locked)
+                && ui.link(recovery_link(language)).clicked()
+            {
+                self.active_view = View::Recovery;
+            }
+        });
+    }
+
+    /// Handbuch mit Abschnitten zur Fehlerbehebung
+    pub(crate) fn help_view(&mut self, ui: &mut egui::Ui, language: Language) {
+        ui.heading("Help / Manual");
+        ui.separator();
+        ui.strong(match language {
+            Language::De => "Fehlerbehebung",
+            Language::En => "Troubleshooting",
+        });
+        let open = self.help_section.take();
+        for kind in ErrorKind::all() {
+            let mut header = egui::CollapsingHeader::new(kind.title(language));
+            if open == Some(*kind) {
+                header = header.open(Some(true));
+            }
+            let response = header.show(ui, |ui| {
+                ui.label(kind.explanation(language));
+                ui.label(format!("→ {}", kind.fix(language)));
+            });
+            if open == Some(*kind) {
+                response
+                    .header_response
+                    .scroll_to_me(Some(egui::Align::TOP));
+            }
+        }
+    }
+}
+
+fn manual_link(language: Language) -> &'static str {
+    match language {
+        Language::De => "Im Handbuch anzeigen",
+        Language::En => "Show in manual",
+    }
+}
+
+fn recovery_link(language: Language) -> &'static str {
+    match language {
+        Language::De => "Wiederherstellungs-Assistent",
+        Language::En => "Recovery wizard",
+    }
+}
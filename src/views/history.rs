@@ -0,0 +1,156 @@
+use crate::MyApp;
+use crate::history::{self, Event, Snapshot};
+use crate::settings::Settings;
+use eframe::egui;
+use std::collections::BTreeMap;
+
+/// Systemwerte einer Momentaufnahme; geänderte Werte sind hervorgehoben und
+/// zeigen beim Überfahren den vorherigen Wert
+pub(super) fn snapshot_grid(
+    ui: &mut egui::Ui,
+    id: impl std::hash::Hash,
+    snapshot: &Snapshot,
+    changes: &BTreeMap<String, Option<String>>,
+) {
+    egui::Grid::new(id)
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            for (name, value) in &snapshot.values {
+                ui.label(name);
+                match changes.get(name) {
+                    Some(before) => {
+                        ui.colored_label(egui::Color32::YELLOW, value)
+                            .on_hover_text(format!(
+                                "Previously: {}",
+                                before.as_deref().unwrap_or("(not recorded)")
+                            ));
+                    }
+                    None => {
+                        ui.monospace(value);
+                    }
+                }
+                ui.end_row();
+            }
+            for (name, before) in changes {
+                if snapshot.values.contains_key(name) {
+                    continue;
+                }
+                ui.label(name);
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    format!("(missing, previously {})", before.as_deref().unwrap_or("-")),
+                );
+                ui.end_row();
+            }
+        });
+}
+
+impl MyApp {
+    /// Verlauf eines Geräts: Vorgänge und Momentaufnahmen zur Seriennummer
+    pub(crate) fn history_view(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        ui.heading("Device history");
+        ui.separator();
+        let mut search = false;
+        ui.horizontal(|ui| {
+            ui.label("Serial number:");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.history_serial)
+                    .hint_text("Type or scan the serial number"),
+            );
+            // Scanner schließen die Eingabe mit Enter ab
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                search = true;
+                response.request_focus();
+            }
+            if self.history.is_none() && !response.has_focus() {
+                response.request_focus();
+            }
+            if ui
+                .add_enabled(
+                    !self.history_serial.trim().is_empty(),
+                    egui::Button::new("Look up"),
+                )
+                .clicked()
+            {
+                search = true;
+            }
+        });
+        if search && !self.history_serial.trim().is_empty() {
+            self.history = Some(
+                history::lookup(settings, &self.history_serial).map_err(|e| format!("{:#}", e)),
+            );
+        }
+
+        let history = match &self.history {
+            Some(Ok(history)) => history,
+            Some(Err(e)) => {
+                ui.colored_label(egui::Color32::RED, format!("Fehler beim Laden: {}", e));
+                return;
+            }
+            None => return,
+        };
+        ui.add_space(8.0);
+        ui.strong(format!(
+            "{}  {}",
+            history.serial,
+            history.hw_type().unwrap_or("unknown hardware type")
+        ));
+        match &history.issued {
+            Some(entry) => {
+                ui.label(format!("Issued {}", entry.issued.format("%Y-%m-%d %H:%M")));
+            }
+            None => {
+                ui.weak("Not issued from the local serial number ledger.");
+            }
+        }
+        if history.events.is_empty() {
+            ui.label("No operations or snapshots recorded for this serial number.");
+            return;
+        }
+        ui.add_space(8.0);
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (i, event) in history.events.iter().enumerate().rev() {
+                let time = event.time().format("%Y-%m-%d %H:%M:%S");
+                match event {
+                    Event::Operation(record) => {
+                        let (color, result) = if record.success {
+                            (egui::Color32::GREEN, "OK")
+                        } else {
+                            (egui::Color32::RED, "FAIL")
+                        };
+                        ui.horizontal(|ui| {
+                            ui.monospace(time.to_string());
+                            ui.strong(record.action.to_string());
+                            ui.colored_label(color, result);
+                            if !record.firmware.is_empty() {
+                                ui.label(record.firmware_summary());
+                            }
+                            if let Some(operator) = &record.operator {
+                                ui.weak(operator);
+                            }
+                        });
+                        if !record.details.is_empty() {
+                            ui.indent(("history_details", i), |ui| {
+                                ui.weak(&record.details);
+                            });
+                        }
+                    }
+                    Event::Snapshot { snapshot, changes } => {
+                        let title = if changes.is_empty() {
+                            format!("{}  System values", time)
+                        } else {
+                            format!("{}  System values ({} changed)", time, changes.len())
+                        };
+                        egui::CollapsingHeader::new(title)
+                            .id_salt(("history_snapshot", i))
+                            .default_open(!changes.is_empty())
+                            .show(ui, |ui| {
+                                snapshot_grid(ui, ("history_values", i), snapshot, changes);
+                            });
+                    }
+                }
+            }
+        });
+    }
+}
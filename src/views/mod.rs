@@ -0,0 +1,17 @@
+// Views, die nicht direkt in main.rs stehen. Jede Datei erweitert `MyApp`.
+mod audit;
+mod backup;
+mod bundles;
+mod capacity;
+mod diagnostics;
+mod history;
+mod operations;
+mod operators;
+mod oplog;
+mod option_bytes;
+mod production;
+mod recovery;
+mod scan;
+mod serial;
+mod settings;
+mod system;
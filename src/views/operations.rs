@@ -0,0 +1,158 @@
+use crate::MyApp;
+use crate::flash::FlashConfig;
+use crate::hardware::{HardwareType, MemoryRange};
+use crate::operations::{self, parse_number};
+use crate::operator::Permission;
+use crate::settings::Settings;
+use eframe::egui;
+use std::path::Path;
+
+/// Bereich aus Adresse und Größe; leere Felder stehen für den gesamten Flash
+fn range_input(address: &str, size: &str, flash: MemoryRange) -> Result<MemoryRange, String> {
+    if address.trim().is_empty() && size.trim().is_empty() {
+        return Ok(flash);
+    }
+    let base = parse_number(address).ok_or_else(|| format!("Invalid address: {}", address))?;
+    let size = parse_number(size).ok_or_else(|| format!("Invalid size: {}", size))?;
+    Ok(MemoryRange { base, size })
+}
+
+impl MyApp {
+    /// Prüfen ohne Schreiben, Auslesen in eine Datei und Löschen
+    pub(crate) fn operations_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        hw: HardwareType,
+        settings: &Settings,
+    ) {
+        let flash = hw.model().flash;
+        let mut config = FlashConfig::new(&self.downloaded_paths, hw, settings);
+        config.allow_model_mismatch = self.allow_model_mismatch;
+        config.firmware_tag = self.selected_firmware.as_ref().map(|sel| sel.tag.clone());
+
+        let can_verify = self.download_done && !self.downloaded_paths.is_empty();
+        if ui
+            .add_enabled(
+                can_verify,
+                egui::Button::new("Verify against downloaded firmware"),
+            )
+            .on_disabled_hover_text("Download a firmware first")
+            .clicked()
+        {
+            self.operation_message = Some(match operations::verify(&config) {
+                Ok(report) => report.summary(),
+                Err(e) => format!("Fehler beim Prüfen: {}", e),
+            });
+        }
+
+        ui.add_space(8.0);
+        if !self.require(ui, Permission::ReadOutErase) {
+            if let Some(msg) = &self.operation_message {
+                ui.add_space(8.0);
+                ui.label(msg);
+            }
+            return;
+        }
+        ui.label(format!(
+            "Read out (empty address and size = whole flash {}):",
+            flash
+        ));
+        egui::Grid::new("readout").num_columns(2).show(ui, |ui| {
+            ui.label("Address");
+            ui.text_edit_singleline(&mut self.readout_address);
+            ui.end_row();
+            ui.label("Size");
+            ui.text_edit_singleline(&mut self.readout_size);
+            ui.end_row();
+            ui.label("File (.bin/.hex)");
+            ui.text_edit_singleline(&mut self.readout_path);
+            ui.end_row();
+        });
+        if ui
+            .add_enabled(
+                !self.readout_path.trim().is_empty(),
+                egui::Button::new("Read out"),
+            )
+            .clicked()
+        {
+            let path = Path::new(self.readout_path.trim());
+            self.operation_message = Some(
+                match range_input(&self.readout_address, &self.readout_size, flash) {
+                    Ok(range) => match operations::read_out(&config, range, path) {
+                        Ok(()) => format!("{} gespeichert in {}", range, path.display()),
+                        Err(e) => format!("Fehler beim Auslesen: {}", e),
+                    },
+                    Err(e) => e,
+                },
+            );
+        }
+
+        ui.add_space(8.0);
+        ui.label("Erase (empty address and size = whole chip):");
+        egui::Grid::new("erase").num_columns(2).show(ui, |ui| {
+            ui.label("Address");
+            ui.text_edit_singleline(&mut self.erase_address);
+            ui.end_row();
+            ui.label("Size");
+            ui.text_edit_singleline(&mut self.erase_size);
+            ui.end_row();
+        });
+        let whole_chip = self.erase_address.trim().is_empty() && self.erase_size.trim().is_empty();
+        ui.checkbox(
+            &mut self.confirm_erase,
+            if whole_chip {
+                "Erase the complete chip, including configuration and calibration"
+            } else {
+                "Erase this range of the connected device"
+            },
+        );
+        let preview = if whole_chip {
+            Some(None)
+        } else {
+            range_input(&self.erase_address, &self.erase_size, flash)
+                .ok()
+                .map(Some)
+        };
+        let protected = preview
+            .map(|range| operations::protected_in_erase(hw, range))
+            .unwrap_or_default();
+        if !protected.is_empty() {
+            ui.checkbox(
+                &mut self.erase_protected,
+                format!(
+                    "Also erase protected regions: {}",
+                    protected
+                        .iter()
+                        .map(|r| r.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            );
+        }
+        if ui
+            .add_enabled(self.confirm_erase, egui::Button::new("Erase"))
+            .clicked()
+        {
+            self.confirm_erase = false;
+            config.allow_protected_overwrite = self.erase_protected;
+            self.erase_protected = false;
+            let range = if whole_chip {
+                Ok(None)
+            } else {
+                range_input(&self.erase_address, &self.erase_size, flash).map(Some)
+            };
+            self.operation_message = Some(match range {
+                Ok(range) => match operations::erase(&config, range) {
+                    Ok(()) => "Löschen erfolgreich.".to_string(),
+                    Err(e) => format!("Fehler beim Löschen: {}", e),
+                },
+                Err(e) => e,
+            });
+        }
+
+        if let Some(msg) = &self.operation_message {
+            ui.add_space(8.0);
+            ui.label(msg);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use crate::audit::{Action, AuditRecord};
+use crate::operator::{self, Operator, Operators, Permission, Role};
+use crate::{MyApp, View};
+use eframe::egui;
+use std::time::{Duration, Instant};
+
+/// Fehlversuche, nach denen die Anmeldung gesperrt wird
+const MAX_LOGIN_ATTEMPTS: u32 = 3;
+const LOGIN_BLOCK: Duration = Duration::from_secs(30);
+
+impl MyApp {
+    pub(crate) fn allowed(&self, permission: Permission) -> bool {
+        self.operator.as_ref().is_some_and(|o| o.allows(permission))
+    }
+
+    /// Zeigt einen Hinweis, wenn dem angemeldeten Bediener `permission` fehlt
+    pub(crate) fn require(&self, ui: &mut egui::Ui, permission: Permission) -> bool {
+        if self.allowed(permission) {
+            return true;
+        }
+        ui.colored_label(
+            egui::Color32::YELLOW,
+            format!(
+                "{} requires the {} role.",
+                permission,
+                permission.required_role()
+            ),
+        );
+        false
+    }
+
+    /// Anmeldung beim Start; ohne Profile wird zuerst ein Engineer angelegt
+    pub(crate) fn login_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Login");
+        ui.separator();
+        let operators = match &self.operators {
+            Ok(operators) => operators.clone(),
+            Err(e) => {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!("Fehler beim Laden der Bedienerprofile: {}", e),
+                );
+                return;
+            }
+        };
+        if operators.operators.is_empty() {
+            ui.label("No operator profiles exist yet. Create the first engineer profile.");
+            self.operator_form_role = Role::Engineer;
+            self.operator_form_ui(ui, true);
+            return;
+        }
+
+        let blocked = self
+            .login_blocked_until
+            .filter(|until| Instant::now() < *until);
+        let mut submit = false;
+        egui::Grid::new("login_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("Operator:");
+                let name = ui.add(
+                    egui::TextEdit::singleline(&mut self.login_name).hint_text("Name or badge"),
+                );
+                if ui.ctx().memory(|m| m.focused().is_none()) {
+                    name.request_focus();
+                }
+                ui.end_row();
+
+                ui.label("PIN:");
+                let pin = ui.add(egui::TextEdit::singleline(&mut self.login_pin).password(true));
+                // Ausweis-Scanner schließen den Namen mit Enter ab
+                if name.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    pin.request_focus();
+                }
+                if pin.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    submit = true;
+                }
+                ui.end_row();
+            });
+        let ready = blocked.is_none() && !self.login_name.trim().is_empty();
+        if ui.add_enabled(ready, egui::Button::new("Log in")).clicked() {
+            submit = true;
+        }
+        if let Some(until) = blocked {
+            ui.colored_label(
+                egui::Color32::RED,
+                format!(
+                    "Too many failed attempts, try again in {} s.",
+                    until.saturating_duration_since(Instant::now()).as_secs() + 1
+                ),
+            );
+            ui.ctx().request_repaint_after(Duration::from_millis(500));
+        } else if submit && ready {
+            self.login(&operators);
+        }
+        if let Some(msg) = &self.login_message {
+            ui.label(msg);
+        }
+    }
+
+    fn login(&mut self, operators: &Operators) {
+        let name = self.login_name.trim().to_string();
+        let mut record = AuditRecord::new(Action::Login, None);
+        record.operator = Some(name.clone());
+        match operators.login(&name, &self.login_pin) {
+            Ok(operator) => {
+                record.success = true;
+                record.details = operator.role.to_string();
+                operator::set_current(Some(&operator.name));
+                crate::oplog::record(format!("Angemeldet: {} ({})", operator.name, operator.role));
+                self.operator = Some(operator);
+                self.login_failures = 0;
+                self.login_message = None;
+            }
+            Err(e) => {
+                self.login_failures += 1;
+                if self.login_failures >= MAX_LOGIN_ATTEMPTS {
+                    self.login_failures = 0;
+                    self.login_blocked_until = Some(Instant::now() + LOGIN_BLOCK);
+                }
+                self.login_message = Some(e.to_string());
+            }
+        }
+        crate::audit::append_logged(&self.effective_settings(), &record);
+        self.login_pin.clear();
+    }
+
+    /// Abmelden; Auswahl und Entwürfe gehen nicht an den nächsten Bediener über
+    pub(crate) fn logout(&mut self) {
+        let mut record = AuditRecord::new(Action::Logout, None);
+        record.success = true;
+        crate::audit::append_logged(&self.effective_settings(), &record);
+        operator::set_current(None);
+        self.operator = None;
+        self.login_name.clear();
+        self.login_message = None;
+        self.active_view = View::default();
+        self.settings_draft = self.settings.clone();
+        self.selected_firmware = None;
+        self.reset_download();
+        self.skip_rdp = false;
+        self.allow_protected_overwrite = false;
+        self.allow_model_mismatch = false;
+    }
+
+    /// Bedienerprofile verwalten
+    pub(crate) fn operators_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Operator profiles");
+        ui.separator();
+        if !self.require(ui, Permission::Settings) {
+            return;
+        }
+        if let Ok(path) = Operators::path(&self.effective_settings()) {
+            ui.label(format!("Profile file: {}", path.display()));
+        }
+        let Ok(operators) = self.operators.clone() else {
+            return;
+        };
+        let current = self.operator.as_ref().map(|o| o.name.clone());
+        let mut remove = None;
+        egui::Grid::new("operators_grid")
+            .num_columns(3)
+            .striped(true)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                ui.strong("Name");
+                ui.strong("Role");
+                ui.label("");
+                ui.end_row();
+                for o in &operators.operators {
+                    ui.label(&o.name);
+                    ui.label(o.role.to_string());
+                    ui.horizontal(|ui| {
+                        if ui.button("Edit").clicked() {
+                            self.operator_form_name = o.name.clone();
+                            self.operator_form_role = o.role;
+                            self.operator_form_pin.clear();
+                            self.operator_form_pin_repeat.clear();
+                        }
+                        // Das eigene Profil kann nicht gelöscht werden
+                        if ui
+                            .add_enabled(
+                                current.as_deref() != Some(o.name.as_str()),
+                                egui::Button::new("Remove"),
+                            )
+                            .clicked()
+                        {
+                            remove = Some(o.name.clone());
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        if let Some(name) = remove {
+            let mut updated = operators.clone();
+            let result = updated
+                .remove(&name)
+                .and_then(|()| updated.save(&self.effective_settings()));
+            self.operator_saved(updated, &name, "removed", result);
+        }
+        ui.add_space(8.0);
+        ui.label("Add or change a profile (leave the PIN empty to keep it):");
+        self.operator_form_ui(ui, false);
+    }
+
+    /// Formular für Name, Rolle und PIN. Beim Einrichten ist die Rolle fest Engineer.
+    fn operator_form_ui(&mut self, ui: &mut egui::Ui, setup: bool) {
+        let Ok(operators) = self.operators.clone() else {
+            return;
+        };
+        egui::Grid::new("operator_form_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("Name:");
+                ui.text_edit_singleline(&mut self.operator_form_name);
+                ui.end_row();
+
+                ui.label("Role:");
+                ui.add_enabled_ui(!setup, |ui| {
+                    egui::ComboBox::from_id_salt("operator_form_role")
+                        .selected_text(self.operator_form_role.to_string())
+                        .show_ui(ui, |ui| {
+                            for role in Role::all() {
+                                ui.selectable_value(
+                                    &mut self.operator_form_role,
+                                    *role,
+                                    role.to_string(),
+                                );
+                            }
+                        });
+                });
+                ui.end_row();
+
+                ui.label("PIN:");
+                ui.add(egui::TextEdit::singleline(&mut self.operator_form_pin).password(true));
+                ui.end_row();
+
+                ui.label("Repeat PIN:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.operator_form_pin_repeat).password(true),
+                );
+                ui.end_row();
+            });
+        let name = self.operator_form_name.trim().to_string();
+        if ui
+            .add_enabled(!name.is_empty(), egui::Button::new("Save profile"))
+            .clicked()
+        {
+            let pin = self.operator_form_pin.clone();
+            let role = self.operator_form_role;
+            let mut updated = operators.clone();
+            let result = if pin != self.operator_form_pin_repeat {
+                Err(anyhow::anyhow!("Die PINs stimmen nicht überein"))
+            } else {
+                match operators.get(&name) {
+                    Some(existing) if pin.is_empty() => {
+                        let mut changed = existing.clone();
+                        changed.role = role;
+                        Ok(changed)
+                    }
+                    Some(existing) => {
+                        let mut changed = existing.clone();
+                        changed.role = role;
+                        changed.set_pin(&pin).map(|()| changed)
+                    }
+                    None => Operator::new(&name, role, &pin),
+                }
+                .and_then(|operator| updated.upsert(operator))
+                .and_then(|()| updated.save(&self.effective_settings()))
+            };
+            let details = if pin.is_empty() {
+                format!("saved as {}", role)
+            } else {
+                format!("saved as {}, PIN set", role)
+            };
+            if result.is_ok() {
+                self.operator_form_name.clear();
+                self.operator_form_pin.clear();
+                self.operator_form_pin_repeat.clear();
+                if setup {
+                    self.login_name = name.clone();
+                    self.login_message =
+                        Some("Profile created. Log in with the new PIN.".to_string());
+                }
+            }
+            self.operator_saved(updated, &name, &details, result);
+        }
+        if let Some(msg) = &self.operator_message {
+            ui.label(msg);
+        }
+    }
+
+    /// Übernimmt geänderte Profile und hält die Änderung im Audit-Log fest
+    fn operator_saved(
+        &mut self,
+        updated: Operators,
+        name: &str,
+        details: &str,
+        result: anyhow::Result<()>,
+    ) {
+        let mut record = AuditRecord::new(Action::OperatorProfile, None);
+        record.details = format!("{}: {}", name, details);
+        record.success = result.is_ok();
+        match result {
+            Ok(()) => {
+                self.operator_message = Some(format!("Profil {}: {}", name, details));
+                // Änderungen am eigenen Profil gelten sofort
+                if let Some(current) = &self.operator
+                    && let Some(changed) = updated.get(&current.name)
+                {
+                    self.operator = Some(changed.clone());
+                }
+                self.operators = Ok(updated);
+            }
+            Err(e) => {
+                record.details = format!("{}: {:#}", name, e);
+                self.operator_message = Some(format!("Fehler: {:#}", e));
+            }
+        }
+        if let Err(e) = crate::audit::append(&self.effective_settings(), &record) {
+            self.operator_message = Some(format!(
+                "Audit-Log konnte nicht geschrieben werden: {:#}",
+                e
+            ));
+        }
+    }
+}
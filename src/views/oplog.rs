@@ -0,0 +1,28 @@
+use crate::MyApp;
+use crate::oplog;
+use eframe::egui;
+
+impl MyApp {
+    /// Verbindungsversuche, Wiederholungen und Ergebnisse der letzten Vorgänge
+    pub(crate) fn operation_log_ui(&mut self, ui: &mut egui::Ui) {
+        let entries = oplog::entries();
+        if ui
+            .add_enabled(!entries.is_empty(), egui::Button::new("Clear"))
+            .clicked()
+        {
+            oplog::clear();
+        }
+        if entries.is_empty() {
+            ui.label("No operations yet.");
+            return;
+        }
+        egui::ScrollArea::vertical()
+            .max_height(200.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for entry in &entries {
+                    ui.monospace(entry.to_string());
+                }
+            });
+    }
+}
@@ -0,0 +1,187 @@
+use crate::MyApp;
+use crate::audit::{Action, AuditRecord};
+use crate::backend::ProgrammerBackend;
+use crate::hardware::HardwareType;
+use crate::operator::Permission;
+use crate::option_bytes::{self, OptionBytesKind, RdpLevel};
+use crate::probe::ProbeConfig;
+use crate::settings::Settings;
+use eframe::egui;
+
+/// Ohne Geräteerkennung verbinden, da bei aktiver Readout-Protection der Flash nicht lesbar ist
+fn with_backend<T>(
+    settings: &Settings,
+    hw: HardwareType,
+    f: impl FnOnce(&mut dyn ProgrammerBackend) -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let mut backend = crate::probe::attach(
+        &ProbeConfig::from_settings(settings),
+        Some(&hw.model().target),
+    )?;
+    f(backend.as_mut())
+}
+
+impl MyApp {
+    /// Anzeige der Option-Bytes und Ändern der Readout-Protection
+    pub(crate) fn option_bytes_ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        hw: HardwareType,
+        kind: OptionBytesKind,
+        settings: &Settings,
+    ) {
+        let policy = hw.model().production_rdp;
+        ui.label(format!("Production policy: {}", policy));
+        if !self.require(ui, Permission::OptionBytes) {
+            return;
+        }
+        if ui.button("Read option bytes").clicked() {
+            match with_backend(settings, hw, |backend| option_bytes::read(backend, kind)) {
+                Ok(bytes) => {
+                    self.option_bytes = Some(bytes);
+                    self.option_bytes_message = None;
+                }
+                Err(e) => {
+                    self.option_bytes = None;
+                    self.option_bytes_message =
+                        Some(format!("Fehler beim Lesen der Option-Bytes: {}", e));
+                }
+            }
+        }
+        let Some(bytes) = self.option_bytes else {
+            if let Some(msg) = &self.option_bytes_message {
+                ui.label(msg);
+            }
+            return;
+        };
+        egui::Grid::new("option_bytes")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for (name, value) in bytes.fields() {
+                    ui.label(name);
+                    ui.label(value);
+                    ui.end_row();
+                }
+            });
+        let current = bytes.rdp();
+        if current != policy {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!(
+                    "Device is at {}, production devices must end at {}.",
+                    current, policy
+                ),
+            );
+        }
+
+        let mut target = None;
+        if current == RdpLevel::Level0 {
+            ui.checkbox(
+                &mut self.confirm_rdp_enable,
+                "Enable readout protection (flash can no longer be read via SWD)",
+            );
+            if ui
+                .add_enabled(
+                    self.confirm_rdp_enable,
+                    egui::Button::new("Set RDP Level 1"),
+                )
+                .clicked()
+            {
+                target = Some(RdpLevel::Level1);
+            }
+        } else if current == RdpLevel::Level1 {
+            ui.colored_label(
+                egui::Color32::RED,
+                "RDP regression to Level 0 mass-erases the complete flash, \
+                 including configuration and calibration!",
+            );
+            ui.checkbox(
+                &mut self.confirm_rdp_regression,
+                "Erase everything and remove readout protection",
+            );
+            if ui
+                .add_enabled(
+                    self.confirm_rdp_regression,
+                    egui::Button::new("RDP regression to Level 0"),
+                )
+                .clicked()
+            {
+                target = Some(RdpLevel::Level0);
+            }
+        }
+        if let Some(level) = target {
+            self.confirm_rdp_enable = false;
+            self.confirm_rdp_regression = false;
+            let mut record = AuditRecord::new(Action::OptionBytes, Some(hw));
+            record.details = format!("{} → {}", current, level);
+            let result = crate::audit::run(settings, record, |record| {
+                with_backend(settings, hw, |backend| {
+                    record.probe = backend.probe();
+                    option_bytes::set_rdp(backend, kind, level)?;
+                    option_bytes::read(backend, kind)
+                })
+            });
+            self.option_bytes_message = Some(match result {
+                Ok(bytes) => {
+                    self.option_bytes = Some(bytes);
+                    format!(
+                        "RDP auf {} gesetzt. Bitte das Gerät aus- und wieder einschalten.",
+                        level
+                    )
+                }
+                Err(e) => format!("Fehler beim Setzen der Readout-Protection: {}", e),
+            });
+        }
+        if let Some(msg) = &self.option_bytes_message {
+            ui.label(msg);
+        }
+    }
+
+    /// Nach dem Flashen zurückgestellte Readout-Protection. Erst setzen, wenn
+    /// Seriennummer und Parameter geschrieben sind, da der Flash danach nicht
+    /// mehr lesbar ist.
+    pub(crate) fn pending_rdp_ui(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        let Some((hw, level)) = self.pending_rdp else {
+            if let Some(msg) = &self.pending_rdp_message {
+                ui.label(msg);
+            }
+            return;
+        };
+        let Some(kind) = hw.model().option_bytes else {
+            self.pending_rdp = None;
+            return;
+        };
+        ui.add_space(8.0);
+        ui.colored_label(
+            egui::Color32::YELLOW,
+            format!(
+                "Readout protection ({}) has not been applied yet. Write the serial number \
+                 and parameters first, then apply it before the device leaves the station.",
+                level
+            ),
+        );
+        if ui.button(format!("Apply {} now", level)).clicked() {
+            let mut record = AuditRecord::new(Action::OptionBytes, Some(hw));
+            record.details = level.to_string();
+            let result = crate::audit::run(settings, record, |record| {
+                with_backend(settings, hw, |backend| {
+                    record.probe = backend.probe();
+                    option_bytes::set_rdp(backend, kind, level)
+                })
+            });
+            self.pending_rdp_message = Some(match result {
+                Ok(()) => {
+                    self.pending_rdp = None;
+                    format!(
+                        "RDP auf {} gesetzt. Bitte das Gerät aus- und wieder einschalten.",
+                        level
+                    )
+                }
+                Err(e) => format!("Fehler beim Setzen der Readout-Protection: {}", e),
+            });
+        }
+        if let Some(msg) = &self.pending_rdp_message {
+            ui.label(msg);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use crate::MyApp;
+use crate::batch::{Batch, Job, Phase};
+use crate::operator::Permission;
+use crate::policy::Policy;
+use crate::serial::Ledger;
+use crate::settings::Settings;
+use eframe::egui;
+
+impl MyApp {
+    /// Serienfertigung: Hardware-Typ und Firmware festlegen, dann Gerät für Gerät
+    /// flashen, prüfen und mit fortlaufender Seriennummer versehen
+    pub(crate) fn production_view(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        ui.heading("Production mode");
+        ui.separator();
+        if let Some(batch) = &mut self.batch {
+            batch.poll();
+            if batch.is_running() {
+                ui.ctx()
+                    .request_repaint_after(std::time::Duration::from_millis(200));
+            }
+        }
+        match &self.batch {
+            Some(_) => self.batch_ui(ui),
+            None => self.batch_setup_ui(ui, settings),
+        }
+    }
+
+    fn batch_setup_ui(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        let (Some(hw), Some(firmware)) = (self.selected_hw_type, self.selected_firmware.clone())
+        else {
+            ui.label("Select the hardware type and firmware in the Flash view first.");
+            return;
+        };
+        if !self.download_done {
+            ui.label("Wait for the firmware download in the Flash view to finish.");
+            return;
+        }
+        ui.label(format!("Hardware type: {}", hw));
+        ui.label(format!(
+            "Firmware: {} ({})",
+            firmware.tag,
+            firmware.assets.join(", ")
+        ));
+        let findings = self.image_findings(hw, false);
+        for finding in &findings {
+            let color = match finding.severity {
+                crate::sanity::Severity::Error => egui::Color32::RED,
+                crate::sanity::Severity::Warning => egui::Color32::YELLOW,
+            };
+            ui.colored_label(color, &finding.message);
+        }
+        let images_ok = self.downloaded_images.iter().all(|i| i.is_ok())
+            && !crate::sanity::has_errors(&findings);
+        let policy = self
+            .firmware_policy
+            .get_or_insert_with(|| Policy::load(settings))
+            .clone();
+        let policy_check = policy.check_assets(hw, &firmware.tag, &firmware.assets);
+        if policy.is_active() {
+            ui.weak(policy.summary());
+        }
+        if let Err(e) = &policy_check {
+            ui.colored_label(egui::Color32::RED, e.to_string());
+        }
+        ui.add_space(8.0);
+
+        let has_serial = hw.model().parameter("serial").is_some();
+        let format = settings.serial_format(hw);
+        let mut format_ok = true;
+        if has_serial {
+            ui.checkbox(
+                &mut self.production_write_serial,
+                "Assign serial numbers from the serial number ledger",
+            );
+            if self.production_write_serial {
+                let next = Ledger::open(settings).and_then(|ledger| ledger.peek(&format));
+                match format.validate(hw).map_err(anyhow::Error::msg).and(next) {
+                    Ok(next) => {
+                        ui.label(format!("Next serial number: {}", next));
+                    }
+                    Err(e) => {
+                        format_ok = false;
+                        ui.colored_label(egui::Color32::RED, e.to_string());
+                    }
+                }
+            }
+        }
+        ui.checkbox(&mut self.production_verify, "Verify after flashing");
+        if let Some(level) = crate::flash::production_rdp(hw) {
+            let allowed = self.allowed(Permission::OptionBytes);
+            ui.add_enabled(
+                allowed,
+                egui::Checkbox::new(
+                    &mut self.skip_rdp,
+                    format!(
+                        "Skip readout protection (policy: {}; development units only)",
+                        level
+                    ),
+                ),
+            );
+        }
+        ui.add_space(8.0);
+        if ui
+            .add_enabled(
+                images_ok && format_ok && policy_check.is_ok(),
+                egui::Button::new(egui::RichText::new("Start production").heading()),
+            )
+            .clicked()
+        {
+            self.batch = Some(Batch::start(Job {
+                hw_type: hw,
+                firmware_tag: firmware.tag.clone(),
+                images: self.downloaded_paths.clone(),
+                serial_format: (has_serial && self.production_write_serial).then_some(format),
+                verify: self.production_verify,
+                skip_rdp: self.skip_rdp,
+                settings: settings.clone(),
+            }));
+        }
+    }
+
+    fn batch_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(batch) = &self.batch else {
+            return;
+        };
+        ui.label(format!(
+            "{}  –  firmware {}",
+            batch.job.hw_type, batch.job.firmware_tag
+        ));
+        ui.add_space(8.0);
+
+        // Große Anzeige, auch aus der Entfernung lesbar
+        let (color, text, detail) = match (batch.phase, batch.last()) {
+            (Phase::WaitingForRemoval, Some(last)) if last.passed => {
+                (egui::Color32::DARK_GREEN, "PASS", last.message.clone())
+            }
+            (Phase::WaitingForRemoval, Some(last)) => {
+                (egui::Color32::DARK_RED, "FAIL", last.message.clone())
+            }
+            (Phase::Flashing | Phase::Verifying | Phase::WritingSerial, _) => {
+                (egui::Color32::from_rgb(0, 70, 140), "BUSY", String::new())
+            }
+            _ => (egui::Color32::DARK_GRAY, "READY", String::new()),
+        };
+        egui::Frame::new()
+            .fill(color)
+            .inner_margin(24.0)
+            .corner_radius(8.0)
+            .show(ui, |ui| {
+                ui.set_min_width(ui.available_width());
+                ui.vertical_centered(|ui| {
+                    ui.label(
+                        egui::RichText::new(text)
+                            .size(72.0)
+                            .strong()
+                            .color(egui::Color32::WHITE),
+                    );
+                    ui.label(
+                        egui::RichText::new(batch.phase.to_string())
+                            .size(24.0)
+                            .color(egui::Color32::WHITE),
+                    );
+                    ui.label(egui::RichText::new(detail).color(egui::Color32::WHITE));
+                });
+            });
+        ui.add_space(8.0);
+
+        let total = batch.passed + batch.failed;
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(format!("Passed: {}", batch.passed)).heading());
+            ui.separator();
+            ui.label(egui::RichText::new(format!("Failed: {}", batch.failed)).heading());
+            ui.separator();
+            ui.label(egui::RichText::new(format!("Total: {}", total)).heading());
+            if total > 0 {
+                ui.separator();
+                ui.label(format!(
+                    "Yield: {:.1} %",
+                    batch.passed as f64 * 100.0 / total as f64
+                ));
+            }
+        });
+        if let Some(serial) = &batch.next_serial {
+            ui.label(format!("Next serial number: {}", serial));
+        }
+
+        let mut close = false;
+        ui.horizontal(|ui| {
+            if batch.is_running() {
+                if ui.button("Stop").clicked() {
+                    batch.stop();
+                }
+                if ui
+                    .add_enabled(
+                        batch.phase == Phase::WaitingForRemoval,
+                        egui::Button::new("Next unit"),
+                    )
+                    .on_hover_text("Continue without waiting for the device to be removed")
+                    .clicked()
+                {
+                    batch.next_unit();
+                }
+            } else if ui.button("New batch").clicked() {
+                close = true;
+            }
+        });
+
+        ui.add_space(8.0);
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("batch_results")
+                .num_columns(4)
+                .striped(true)
+                .show(ui, |ui| {
+                    for result in &batch.results {
+                        ui.monospace(result.time.format("%H:%M:%S").to_string());
+                        ui.monospace(result.serial.as_deref().unwrap_or("-"));
+                        if result.passed {
+                            ui.colored_label(egui::Color32::GREEN, "PASS");
+                        } else {
+                            ui.colored_label(egui::Color32::RED, "FAIL");
+                        }
+                        ui.label(&result.message);
+                        ui.end_row();
+                    }
+                });
+        });
+        if close {
+            self.batch = None;
+        }
+    }
+}
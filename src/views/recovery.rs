@@ -0,0 +1,101 @@
+use crate::MyApp;
+use crate::operator::Permission;
+use crate::probe::ProbeConfig;
+use crate::recovery::{Step, Wizard};
+use crate::settings::Settings;
+use eframe::egui;
+
+impl MyApp {
+    /// Schrittweise Wiederherstellung nicht erreichbarer oder gesperrter Geräte
+    pub(crate) fn recovery_view(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        ui.heading("Recovery wizard");
+        ui.separator();
+        // Der Assistent löscht den Chip und setzt die Readout-Protection zurück
+        if !self.require(ui, Permission::OptionBytes) {
+            return;
+        }
+        let Some(hw) = self.selected_hw_type else {
+            ui.label("Select the hardware type in the Flash view first.");
+            return;
+        };
+        ui.label(format!("Hardware type: {}", hw));
+        if self.downloaded_paths.is_empty() {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                "No firmware downloaded yet. Download a known-good release in the Flash view \
+                 before starting, so the device can be flashed at the end.",
+            );
+        } else {
+            ui.label(format!("Firmware: {}", self.downloaded_paths.join(", ")));
+        }
+        let wizard = self
+            .recovery
+            .get_or_insert_with(|| Wizard::new(ProbeConfig::from_settings(settings)));
+        ui.add_space(8.0);
+
+        for (i, step) in Step::all().iter().enumerate() {
+            let text = format!("{}. {}", i + 1, step.title());
+            if ui.selectable_label(i == wizard.step, text).clicked() {
+                wizard.go_to(i);
+            }
+        }
+        ui.add_space(8.0);
+
+        let step = wizard.current();
+        ui.strong(step.title());
+        ui.label(step.explanation());
+        let confirmed = match step.data_loss() {
+            Some(loss) => {
+                ui.colored_label(egui::Color32::RED, loss);
+                ui.checkbox(&mut wizard.confirm_data_loss, "I understand the data loss");
+                wizard.confirm_data_loss
+            }
+            None => true,
+        };
+        if step == Step::FlashFirmware {
+            // Die Version wird beim Flashen geprüft
+            ui.checkbox(
+                &mut wizard.allow_version_change,
+                "Allow a downgrade or re-flashing the installed version",
+            );
+        }
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(confirmed, egui::Button::new("Run step"))
+                .clicked()
+            {
+                let tag = self.selected_firmware.as_ref().map(|sel| sel.tag.as_str());
+                wizard.run(hw, &self.downloaded_paths, tag, settings);
+            }
+            if ui
+                .add_enabled(
+                    wizard.step + 1 < Step::all().len(),
+                    egui::Button::new("Skip"),
+                )
+                .clicked()
+            {
+                wizard.go_to(wizard.step + 1);
+            }
+            if ui.button("Start over").clicked() {
+                *wizard = Wizard::new(ProbeConfig::from_settings(settings));
+            }
+        });
+
+        ui.add_space(8.0);
+        ui.label(format!(
+            "Connection: {} kHz, {}",
+            wizard
+                .probe
+                .swd_speed_khz
+                .map_or("default".to_string(), |s| s.to_string()),
+            if wizard.probe.connect_under_reset {
+                "under reset"
+            } else {
+                "normal"
+            }
+        ));
+        for line in &wizard.log {
+            ui.label(line);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::flash::Release;
+use crate::operator::Permission;
+use crate::policy::Policy;
+use crate::scan::{self, ScanResult};
+use crate::settings::Settings;
+use crate::{MyApp, SelectedFirmware, View};
+use eframe::egui;
+
+impl MyApp {
+    /// Eingabefeld für Barcode-Scanner. Behält den Fokus, solange kein anderes
+    /// Feld aktiv ist, damit ohne Maus gescannt werden kann.
+    pub(crate) fn scan_bar(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        ui.horizontal(|ui| {
+            ui.label("Scan:");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.scan_input)
+                    .hint_text("Device label or work order")
+                    .desired_width(320.0),
+            );
+            // Scanner schließen die Eingabe mit Enter ab
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                let text = std::mem::take(&mut self.scan_input);
+                if !text.trim().is_empty() {
+                    match scan::parse(settings, &text) {
+                        Ok(result) => self.apply_scan(result, settings),
+                        Err(e) => self.scan_message = Some(format!("{:#}", e)),
+                    }
+                }
+                response.request_focus();
+            } else if ui.ctx().memory(|m| m.focused().is_none()) {
+                response.request_focus();
+            }
+            if let Some(msg) = &self.scan_message {
+                ui.label(msg);
+            }
+        });
+    }
+
+    /// Übernimmt Hardware-Typ und Seriennummer; die Firmware eines
+    /// Arbeitsauftrags wird gewählt, sobald die Releases geladen sind
+    fn apply_scan(&mut self, result: ScanResult, settings: &Settings) {
+        crate::oplog::record(format!("Scan: {}", result.summary()));
+        self.scan_message = Some(format!("Scanned: {}", result.summary()));
+        if let Some(hw) = result.hw_type {
+            if self.selected_hw_type != Some(hw) {
+                self.selected_hw_type = Some(hw);
+                self.allow_model_mismatch = false;
+                self.backups = None;
+                self.selected_backup = None;
+                self.restore_message = None;
+                self.option_bytes = None;
+                self.option_bytes_message = None;
+                self.serial_current = None;
+                self.capacity_current = None;
+            }
+            self.hw_type_manual = true;
+        }
+        if let Some(serial) = &result.serial {
+            self.serial_manual = true;
+            self.serial_input = serial.clone();
+            self.serial_message = None;
+            self.history_serial = serial.clone();
+            self.history = None;
+        }
+        if self.active_view == View::Production {
+            // Laufende Serie nicht verlassen
+            return;
+        }
+        if result.tag.is_some() {
+            self.scan_work_order = Some(result);
+            self.active_view = View::Flash;
+        } else if result.serial.is_some() {
+            if self.active_view == View::History {
+                self.history = Some(
+                    crate::history::lookup(settings, &self.history_serial)
+                        .map_err(|e| format!("{:#}", e)),
+                );
+            } else {
+                self.active_view = View::SetSerial;
+            }
+        } else if result.hw_type.is_some() {
+            self.active_view = View::Flash;
+        }
+    }
+
+    /// Wählt die vom gescannten Arbeitsauftrag verlangte Firmware aus `releases`
+    /// (Releases des aktuell gewählten Hardware-Typs)
+    pub(crate) fn resolve_work_order(&mut self, releases: &[Release], settings: &Settings) {
+        let Some(order) = self.scan_work_order.take() else {
+            return;
+        };
+        if order.hw_type.is_some() && order.hw_type != self.selected_hw_type {
+            // Hardware-Typ wurde inzwischen geändert, Auftrag verwerfen
+            return;
+        }
+        let tag = order.tag.clone().unwrap_or_default();
+        let Some(release) = releases.iter().find(|r| r.tag_name == tag) else {
+            self.scan_message = Some(format!("Release {} nicht gefunden", tag));
+            return;
+        };
+        if release.prerelease && !settings.show_prereleases {
+            self.scan_message = Some(format!(
+                "Release {} ist ein Pre-release; \"Show pre-releases\" ist nicht aktiviert",
+                tag
+            ));
+            return;
+        }
+        if release.prerelease && !self.allowed(Permission::PrereleaseFirmware) {
+            self.scan_message = Some(format!(
+                "Release {} ist ein Pre-release: {} requires the {} role",
+                tag,
+                Permission::PrereleaseFirmware,
+                Permission::PrereleaseFirmware.required_role()
+            ));
+            return;
+        }
+        let assets: Vec<String> = match &order.asset {
+            Some(pattern) => release
+                .stm32_assets
+                .iter()
+                .filter(|a| crate::hardware::wildcard_match(pattern, a))
+                .cloned()
+                .collect(),
+            // Ohne Asset im Auftrag nur ein Format, sonst würde dieselbe
+            // Firmware als .bin, .hex und .elf nacheinander geflasht
+            None => match self.selected_hw_type {
+                Some(hw) => hw.model().preferred_assets(&release.stm32_assets),
+                None => Vec::new(),
+            },
+        };
+        if assets.is_empty() {
+            self.scan_message = Some(format!(
+                "Kein Asset von {} passt zu {}",
+                tag,
+                order.asset.as_deref().unwrap_or("-")
+            ));
+            return;
+        }
+        let policy = self
+            .firmware_policy
+            .get_or_insert_with(|| Policy::load(settings));
+        if let Some(hw) = self.selected_hw_type
+            && let Err(e) = policy.check_assets(hw, &tag, &assets)
+        {
+            self.scan_message = Some(format!("{:#}", e));
+            return;
+        }
+        self.scan_message = Some(format!(
+            "Order {}: firmware {} ({})",
+            order.order.as_deref().unwrap_or("-"),
+            tag,
+            assets.join(", ")
+        ));
+        self.selected_firmware = Some(SelectedFirmware { tag, assets });
+        self.reset_download();
+    }
+}
@@ -0,0 +1,197 @@
+use crate::MyApp;
+use crate::audit::{Action, AuditRecord};
+use crate::operator::Permission;
+use crate::parameters::{self, ParameterValue};
+use crate::probe::ProbeConfig;
+use crate::serial::Ledger;
+use crate::settings::Settings;
+use eframe::egui;
+
+/// Angezeigte Einträge aus dem Seriennummern-Register
+const LEDGER_ROWS: usize = 50;
+
+impl MyApp {
+    /// Seriennummer lesen und aus dem Register oder manuell vergeben
+    pub(crate) fn serial_view(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        ui.heading("Set serial number");
+        ui.separator();
+        if !self.require(ui, Permission::SetSerial) {
+            return;
+        }
+        let Some(hw) = self.selected_hw_type else {
+            ui.label("Select the hardware type in the Flash view first.");
+            return;
+        };
+        ui.label(format!("Hardware type: {}", hw));
+        if hw.model().parameter("serial").is_none() {
+            ui.label("This hardware type has no serial number parameter.");
+            return;
+        }
+        let format = settings.serial_format(hw);
+        let target = hw.model().target.clone();
+        let elf = self
+            .downloaded_images
+            .iter()
+            .filter_map(|i| i.as_ref().ok())
+            .find(|i| i.is_elf)
+            .cloned();
+        let ledger = Ledger::open(settings);
+
+        ui.horizontal(|ui| {
+            if ui.button("Read current serial number").clicked() {
+                let result = (|| {
+                    let mut backend =
+                        crate::probe::attach(&ProbeConfig::from_settings(settings), Some(&target))?;
+                    parameters::read(backend.as_mut(), hw, "serial", elf.as_ref())
+                })();
+                self.serial_message = None;
+                self.serial_current = match result {
+                    Ok(Some(serial)) => Some(serial.to_string()),
+                    Ok(None) => Some("(none)".to_string()),
+                    Err(e) => {
+                        self.serial_message = Some(format!("Fehler beim Lesen: {:#}", e));
+                        None
+                    }
+                };
+            }
+            if let Some(current) = &self.serial_current {
+                ui.label(format!("Current: {}", current));
+            }
+        });
+        ui.add_space(8.0);
+
+        ui.radio_value(
+            &mut self.serial_manual,
+            false,
+            "Next serial number from the ledger",
+        );
+        ui.radio_value(&mut self.serial_manual, true, "Manual entry");
+        let mut can_write = true;
+        if self.serial_manual {
+            ui.add(egui::TextEdit::singleline(&mut self.serial_input).hint_text(format.example()));
+            let input = self.serial_input.trim();
+            can_write = !input.is_empty();
+            if !input.is_empty() && !format.check(input) {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    "The check digit does not match the configured format.",
+                );
+            }
+            if let Ok(ledger) = &ledger
+                && ledger.contains(input)
+            {
+                can_write = false;
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!("Serial number {} has already been issued.", input),
+                );
+            }
+        } else {
+            match format
+                .validate(hw)
+                .map_err(anyhow::Error::msg)
+                .and_then(|_| ledger.as_ref().map_err(|e| anyhow::anyhow!("{:#}", e)))
+                .and_then(|ledger| ledger.peek(&format))
+            {
+                Ok(next) => {
+                    ui.label(format!("Next serial number: {}", next));
+                }
+                Err(e) => {
+                    can_write = false;
+                    ui.colored_label(egui::Color32::RED, e.to_string());
+                }
+            }
+        }
+
+        if ui
+            .add_enabled(can_write, egui::Button::new("Write serial number"))
+            .clicked()
+        {
+            let manual = self
+                .serial_manual
+                .then(|| self.serial_input.trim().to_string());
+            let record = AuditRecord::new(Action::SetSerial, Some(hw));
+            let result = crate::audit::run(settings, record, |record| {
+                let mut backend =
+                    crate::probe::attach(&ProbeConfig::from_settings(settings), Some(&target))?;
+                let identity = crate::device::identify(backend.as_mut())?;
+                record.device(backend.as_ref(), &identity);
+                if identity.contradicts(hw) {
+                    anyhow::bail!(
+                        "Angeschlossenes Gerät passt nicht zu {}: {}",
+                        hw,
+                        identity.summary()
+                    );
+                }
+                // Erst eintragen, dann schreiben: eine Nummer wird nie doppelt vergeben
+                let mut ledger = Ledger::open(settings)?;
+                let manual_entry = manual.is_some();
+                let serial = match manual {
+                    Some(serial) => {
+                        ledger.register(hw, &serial, identity.unique_id.as_deref())?;
+                        serial
+                    }
+                    None => ledger.allocate(hw, &format, identity.unique_id.as_deref())?,
+                };
+                record.serial = Some(serial.clone());
+                record.details = if manual_entry { "manual" } else { "ledger" }.to_string();
+                parameters::write(
+                    backend.as_mut(),
+                    hw,
+                    "serial",
+                    &ParameterValue::String(serial.clone()),
+                    elf.as_ref(),
+                )
+                .map_err(|e| {
+                    e.context(format!(
+                        "Seriennummer {} vergeben, aber nicht geschrieben",
+                        serial
+                    ))
+                })?;
+                Ok(serial)
+            });
+            self.serial_message = Some(match result {
+                Ok(serial) => {
+                    crate::oplog::record(format!("Seriennummer {} geschrieben", serial));
+                    self.serial_current = Some(serial.clone());
+                    self.serial_input.clear();
+                    format!("Seriennummer {} geschrieben.", serial)
+                }
+                Err(e) => format!("Fehler: {:#}", e),
+            });
+        }
+        if let Some(msg) = &self.serial_message {
+            ui.label(msg);
+        }
+        self.pending_rdp_ui(ui, settings);
+
+        ui.add_space(8.0);
+        ui.collapsing("Issued serial numbers", |ui| match &ledger {
+            Ok(ledger) => {
+                let entries: Vec<_> = ledger
+                    .entries()
+                    .iter()
+                    .rev()
+                    .filter(|e| e.hw_type == hw.id())
+                    .take(LEDGER_ROWS)
+                    .collect();
+                if entries.is_empty() {
+                    ui.label("No serial numbers issued yet.");
+                }
+                egui::Grid::new("serial_ledger")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for entry in entries {
+                            ui.monospace(&entry.serial);
+                            ui.label(entry.issued.format("%Y-%m-%d %H:%M").to_string());
+                            ui.monospace(entry.unique_id.as_deref().unwrap_or_default());
+                            ui.end_row();
+                        }
+                    });
+            }
+            Err(e) => {
+                ui.colored_label(egui::Color32::RED, format!("{:#}", e));
+            }
+        });
+    }
+}
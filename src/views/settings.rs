@@ -0,0 +1,498 @@
+use crate::MyApp;
+use crate::hardware::HardwareType;
+use crate::label::{CodeKind, LabelData};
+use crate::operator::Permission;
+use crate::probe::AfterFlash;
+use crate::scan::ScanPattern;
+use crate::serial::SerialFormat;
+use crate::settings::{Language, Settings};
+use crate::simulator::SimulatedDevice;
+use eframe::egui;
+use std::path::PathBuf;
+
+/// Textfeld für einen optionalen String; leere Eingabe entspricht `None`
+fn optional_text(ui: &mut egui::Ui, value: &mut Option<String>, hint: &str, password: bool) {
+    let mut text = value.clone().unwrap_or_default();
+    let edit = egui::TextEdit::singleline(&mut text)
+        .hint_text(hint)
+        .password(password);
+    if ui.add(edit).changed() {
+        *value = if text.trim().is_empty() {
+            None
+        } else {
+            Some(text.trim().to_string())
+        };
+    }
+}
+
+impl MyApp {
+    pub(crate) fn settings_view(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Settings");
+        ui.separator();
+        if !self.require(ui, Permission::Settings) {
+            return;
+        }
+        if let Some(path) = Settings::path() {
+            ui.label(format!("Configuration file: {}", path.display()));
+        }
+        if !self.overrides.is_empty() {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                "Some values are overridden by environment variables or command line options.",
+            );
+        }
+        ui.add_space(8.0);
+
+        let draft = &mut self.settings_draft;
+        egui::Grid::new("settings_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("Preferred probe:");
+                optional_text(ui, &mut draft.preferred_probe, "VID:PID[:SERIAL]", false);
+                ui.end_row();
+
+                ui.label("SWD speed (kHz):");
+                ui.horizontal(|ui| {
+                    let mut custom = draft.swd_speed_khz.is_some();
+                    if ui.checkbox(&mut custom, "").changed() {
+                        draft.swd_speed_khz = custom.then_some(4000);
+                    }
+                    if let Some(speed) = &mut draft.swd_speed_khz {
+                        ui.add(egui::DragValue::new(speed).range(100..=50_000));
+                    } else {
+                        ui.label("Probe default");
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Connect under reset:");
+                ui.checkbox(&mut draft.connect_under_reset, "");
+                ui.end_row();
+
+                ui.label("Connection attempts:");
+                ui.add(egui::DragValue::new(&mut draft.retry.attach_attempts).range(1..=10));
+                ui.end_row();
+
+                ui.label("Programming attempts:");
+                ui.add(egui::DragValue::new(&mut draft.retry.flash_attempts).range(1..=5));
+                ui.end_row();
+
+                ui.label("Lower SWD speed on retry:");
+                ui.checkbox(&mut draft.retry.lower_speed_on_retry, "");
+                ui.end_row();
+
+                ui.label("After flashing:");
+                egui::ComboBox::from_id_salt("after_flash")
+                    .selected_text(draft.after_flash.to_string())
+                    .show_ui(ui, |ui| {
+                        for mode in AfterFlash::all() {
+                            ui.selectable_value(&mut draft.after_flash, *mode, mode.to_string());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Default hardware type:");
+                let selected = draft
+                    .default_hw_type
+                    .as_deref()
+                    .and_then(HardwareType::from_id);
+                egui::ComboBox::from_id_salt("default_hw_type")
+                    .selected_text(selected.map(|hw| hw.to_string()).unwrap_or("None".into()))
+                    .show_ui(ui, |ui| {
+                        if ui.selectable_label(selected.is_none(), "None").clicked() {
+                            draft.default_hw_type = None;
+                        }
+                        for hw_type in HardwareType::all() {
+                            let is_selected = selected == Some(*hw_type);
+                            if ui
+                                .selectable_label(is_selected, hw_type.to_string())
+                                .clicked()
+                            {
+                                draft.default_hw_type = Some(hw_type.id().to_string());
+                            }
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Demo device:");
+                ui.horizontal(|ui| {
+                    let demo = draft.demo_device.as_deref().and_then(HardwareType::from_id);
+                    egui::ComboBox::from_id_salt("demo_device")
+                        .selected_text(
+                            demo.map(|hw| hw.to_string())
+                                .unwrap_or("None (debug probe)".into()),
+                        )
+                        .show_ui(ui, |ui| {
+                            if ui
+                                .selectable_label(demo.is_none(), "None (debug probe)")
+                                .clicked()
+                            {
+                                draft.demo_device = None;
+                            }
+                            for hw_type in HardwareType::all() {
+                                if ui
+                                    .selectable_label(demo == Some(*hw_type), hw_type.to_string())
+                                    .clicked()
+                                {
+                                    draft.demo_device = Some(hw_type.id().to_string());
+                                }
+                            }
+                        });
+                    if let Some(hw_type) = demo
+                        && ui
+                            .button("Reset demo device")
+                            .on_hover_text("Restore the factory state of the simulated device")
+                            .clicked()
+                    {
+                        SimulatedDevice::reset_state(hw_type);
+                    }
+                    if let Some(hw_type) = demo
+                        && ui
+                            .button("Power cycle")
+                            .on_hover_text(
+                                "Reload the option bytes, e.g. to activate readout protection",
+                            )
+                            .clicked()
+                    {
+                        SimulatedDevice::power_cycle(hw_type);
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Language:");
+                egui::ComboBox::from_id_salt("language")
+                    .selected_text(draft.language.to_string())
+                    .show_ui(ui, |ui| {
+                        for language in Language::all() {
+                            ui.selectable_value(
+                                &mut draft.language,
+                                *language,
+                                language.to_string(),
+                            );
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Show pre-releases:");
+                ui.checkbox(&mut draft.show_prereleases, "");
+                ui.end_row();
+
+                ui.label("Firmware policy:");
+                let mut policy = draft
+                    .firmware_policy
+                    .as_ref()
+                    .map(|p| p.display().to_string());
+                optional_text(ui, &mut policy, "No restriction", false);
+                draft.firmware_policy = policy.map(PathBuf::from);
+                ui.end_row();
+
+                ui.label("Policy public key:");
+                optional_text(
+                    ui,
+                    &mut draft.policy_public_key,
+                    "Ed25519, Base64 or hex; requires a signed policy",
+                    false,
+                );
+                ui.end_row();
+
+                ui.label("Bundle signing key:");
+                let mut key_file = draft
+                    .bundle_signing_key
+                    .as_ref()
+                    .map(|p| p.display().to_string());
+                optional_text(ui, &mut key_file, "Private key file for exports", false);
+                draft.bundle_signing_key = key_file.map(PathBuf::from);
+                ui.end_row();
+                // Öffentlichen Schlüssel zum Weitergeben an Servicepartner anzeigen
+                if let Some(key_file) = &draft.bundle_signing_key {
+                    ui.label("");
+                    match crate::signing::public_key(key_file) {
+                        Ok(key) => {
+                            ui.horizontal(|ui| {
+                                ui.weak(format!("Public key: {}", key));
+                                if ui.small_button("Copy").clicked() {
+                                    ui.ctx().copy_text(key);
+                                }
+                            });
+                        }
+                        Err(e) => {
+                            ui.colored_label(egui::Color32::RED, format!("{:#}", e));
+                        }
+                    }
+                    ui.end_row();
+                }
+
+                ui.label("Bundle public key:");
+                optional_text(
+                    ui,
+                    &mut draft.bundle_public_key,
+                    "Ed25519, Base64 or hex; imported bundles must be signed",
+                    false,
+                );
+                ui.end_row();
+
+                ui.label("GitHub token:");
+                optional_text(ui, &mut draft.github_token, "optional", true);
+                ui.end_row();
+
+                ui.label("Cache directory:");
+                let mut cache_dir = draft.cache_dir.as_ref().map(|d| d.display().to_string());
+                let default_dir = Settings::default().cache_dir().display().to_string();
+                optional_text(ui, &mut cache_dir, &default_dir, false);
+                draft.cache_dir = cache_dir.map(PathBuf::from);
+                ui.end_row();
+
+                ui.label("Cache size (MB):");
+                ui.add(egui::DragValue::new(&mut draft.cache_size_mb).range(16..=65_536));
+                ui.end_row();
+
+                ui.label("Data directory:")
+                    .on_hover_text("Audit log, serial number ledger, device history and operators");
+                let mut data_dir = draft.data_dir.as_ref().map(|d| d.display().to_string());
+                let default_dir = Settings::config_dir()
+                    .map(|d| d.display().to_string())
+                    .unwrap_or_default();
+                optional_text(ui, &mut data_dir, &default_dir, false);
+                draft.data_dir = data_dir.map(PathBuf::from);
+                ui.end_row();
+
+                ui.label("Backup before flashing:");
+                ui.checkbox(&mut draft.backup_before_flash, "not in production mode");
+                ui.end_row();
+            });
+
+        ui.add_space(8.0);
+        ui.label("Firmware sources (GitHub repository per hardware type):");
+        egui::Grid::new("firmware_sources_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                for hw_type in HardwareType::all() {
+                    ui.label(hw_type.to_string());
+                    let mut repo = draft.firmware_sources.get(hw_type.id()).cloned();
+                    optional_text(ui, &mut repo, hw_type.repo(), false);
+                    match repo {
+                        Some(repo) => {
+                            draft
+                                .firmware_sources
+                                .insert(hw_type.id().to_string(), repo);
+                        }
+                        None => {
+                            draft.firmware_sources.remove(hw_type.id());
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+
+        ui.add_space(8.0);
+        ui.label("Serial number formats:");
+        egui::Grid::new("serial_formats_grid")
+            .num_columns(6)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label("Prefix");
+                ui.label("Year/week");
+                ui.label("Digits");
+                ui.label("Check digit");
+                ui.label("Example");
+                ui.end_row();
+                for hw_type in HardwareType::all() {
+                    if hw_type.model().parameter("serial").is_none() {
+                        continue;
+                    }
+                    let mut format = draft.serial_format(*hw_type);
+                    ui.label(hw_type.to_string());
+                    ui.add(egui::TextEdit::singleline(&mut format.prefix).desired_width(80.0));
+                    ui.checkbox(&mut format.year_week, "");
+                    ui.add(egui::DragValue::new(&mut format.digits).range(1..=9));
+                    ui.checkbox(&mut format.check_digit, "");
+                    match format.validate(*hw_type) {
+                        Ok(()) => ui.monospace(format.example()),
+                        Err(e) => ui.colored_label(egui::Color32::RED, e),
+                    };
+                    if format == SerialFormat::for_model(*hw_type) {
+                        draft.serial_formats.remove(hw_type.id());
+                    } else {
+                        draft
+                            .serial_formats
+                            .insert(hw_type.id().to_string(), format);
+                    }
+                    ui.end_row();
+                }
+            });
+
+        ui.add_space(8.0);
+        ui.label("Labels:");
+        let label = &mut draft.label;
+        egui::Grid::new("label_grid")
+            .num_columns(2)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                ui.label("Size (mm):");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut label.width_mm).range(10.0..=200.0));
+                    ui.label("×");
+                    ui.add(egui::DragValue::new(&mut label.height_mm).range(10.0..=200.0));
+                });
+                ui.end_row();
+
+                ui.label("Printer resolution (dpi):");
+                egui::ComboBox::from_id_salt("label_dpi")
+                    .selected_text(label.dpi.to_string())
+                    .show_ui(ui, |ui| {
+                        for dpi in [203, 300, 600] {
+                            ui.selectable_value(&mut label.dpi, dpi, dpi.to_string());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Text lines:");
+                let mut lines = label.lines.join("\n");
+                if ui
+                    .add(egui::TextEdit::multiline(&mut lines).desired_rows(4))
+                    .on_hover_text(
+                        "Placeholders: {model} {hw_type} {serial} {capacity} {firmware} {date}",
+                    )
+                    .changed()
+                {
+                    label.lines = lines.lines().map(str::to_string).collect();
+                }
+                ui.end_row();
+
+                ui.label("Code:");
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_salt("label_code")
+                        .selected_text(label.code.to_string())
+                        .show_ui(ui, |ui| {
+                            for kind in CodeKind::all() {
+                                ui.selectable_value(&mut label.code, *kind, kind.to_string());
+                            }
+                        });
+                    ui.add_enabled(
+                        label.code != CodeKind::None,
+                        egui::TextEdit::singleline(&mut label.code_content).hint_text("{serial}"),
+                    );
+                });
+                ui.end_row();
+
+                ui.label("File formats:");
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut label.zpl, "ZPL");
+                    ui.checkbox(&mut label.pdf, "PDF");
+                    ui.checkbox(&mut label.png, "PNG");
+                });
+                ui.end_row();
+
+                ui.label("Save to folder:");
+                let mut folder = label.folder.as_ref().map(|d| d.display().to_string());
+                optional_text(ui, &mut folder, "Do not save", false);
+                label.folder = folder.map(PathBuf::from);
+                ui.end_row();
+
+                ui.label("Label printer (ZPL):");
+                optional_text(ui, &mut label.printer, "HOST:9100", false);
+                ui.end_row();
+
+                ui.label("Production mode:");
+                ui.checkbox(&mut label.after_production, "Label for every passed unit");
+                ui.end_row();
+            });
+        if ui.button("Create test label").clicked() {
+            let hw = self
+                .selected_hw_type
+                .or_else(|| HardwareType::all().first().copied());
+            self.settings_message = Some(match hw {
+                Some(hw) => match crate::label::output(&draft.label, &LabelData::sample(hw)) {
+                    Ok(msg) => msg,
+                    Err(e) => format!("Fehler beim Etikett: {:#}", e),
+                },
+                None => "Kein Hardware-Typ im Katalog".to_string(),
+            });
+        }
+
+        ui.add_space(8.0);
+        ui.label("Barcode scan patterns (checked in order):");
+        let mut remove = None;
+        let serial_pattern = crate::scan::serial_pattern(draft);
+        egui::Grid::new("scan_patterns_grid")
+            .num_columns(4)
+            .spacing([16.0, 8.0])
+            .show(ui, |ui| {
+                for (i, pattern) in draft.scan_patterns.iter_mut().enumerate() {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut pattern.name)
+                            .hint_text("Name")
+                            .desired_width(120.0),
+                    );
+                    ui.add(
+                        egui::TextEdit::singleline(&mut pattern.pattern)
+                            .code_editor()
+                            .desired_width(420.0),
+                    )
+                    .on_hover_text(
+                        "Regular expression with the named groups hw, serial, tag, asset and order; \
+                         {serial} matches a serial number in one of the configured formats",
+                    );
+                    match pattern.regex(&serial_pattern) {
+                        Ok(_) => ui.label(""),
+                        Err(e) => ui
+                            .colored_label(egui::Color32::RED, "Invalid")
+                            .on_hover_text(e.to_string()),
+                    };
+                    if ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+        if let Some(i) = remove {
+            draft.scan_patterns.remove(i);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Add pattern").clicked() {
+                draft.scan_patterns.push(ScanPattern {
+                    name: String::new(),
+                    pattern: String::new(),
+                });
+            }
+            if ui.button("Default patterns").clicked() {
+                draft.scan_patterns = ScanPattern::defaults();
+            }
+        });
+
+        ui.add_space(16.0);
+        ui.horizontal(|ui| {
+            let changed = self.settings_draft != self.settings;
+            if ui.add_enabled(changed, egui::Button::new("Save")).clicked() {
+                match self.settings_draft.save() {
+                    Ok(()) => {
+                        self.settings = self.settings_draft.clone();
+                        self.firmware_policy = None;
+                        self.settings_message = Some("Settings saved.".to_string());
+                    }
+                    Err(e) => {
+                        self.settings_message =
+                            Some(format!("Fehler beim Speichern der Einstellungen: {}", e));
+                    }
+                }
+            }
+            if ui
+                .add_enabled(changed, egui::Button::new("Discard"))
+                .clicked()
+            {
+                self.settings_draft = self.settings.clone();
+                self.settings_message = None;
+            }
+            if ui.button("Reset to defaults").clicked() {
+                self.settings_draft = Settings::default();
+            }
+        });
+        if let Some(msg) = &self.settings_message {
+            ui.add_space(8.0);
+            ui.label(msg);
+        }
+    }
+}
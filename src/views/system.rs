@@ -0,0 +1,101 @@
+use crate::history::{self, Snapshot};
+use crate::label::LabelData;
+use crate::probe::ProbeConfig;
+use crate::settings::Settings;
+use crate::{MyApp, View};
+use eframe::egui;
+
+impl MyApp {
+    /// Systemwerte lesen und als Momentaufnahme in der Gerätedatenbank speichern
+    pub(crate) fn system_view(&mut self, ui: &mut egui::Ui, settings: &Settings) {
+        ui.heading("Read system values");
+        ui.separator();
+        let Some(hw) = self.selected_hw_type else {
+            ui.label("Select the hardware type in the Flash view first.");
+            return;
+        };
+        ui.label(format!("Hardware type: {}", hw));
+        if ui.button("Read system values").clicked() {
+            let elf = self
+                .downloaded_images
+                .iter()
+                .filter_map(|i| i.as_ref().ok())
+                .find(|i| i.is_elf)
+                .cloned();
+            let result = (|| -> anyhow::Result<(Snapshot, LabelData)> {
+                let mut backend = crate::probe::attach(
+                    &ProbeConfig::from_settings(settings),
+                    Some(&hw.model().target),
+                )?;
+                let identity = crate::device::identify(backend.as_mut())?;
+                if identity.contradicts(hw) {
+                    anyhow::bail!(
+                        "Angeschlossenes Gerät passt nicht zu {}: {}",
+                        hw,
+                        identity.summary()
+                    );
+                }
+                let snapshot = Snapshot::capture(backend.as_mut(), hw, &identity, elf.as_ref());
+                let label = LabelData::from_snapshot(hw, &snapshot, &identity);
+                Ok((snapshot, label))
+            })();
+            self.system_message = None;
+            self.system_label = None;
+            self.system_snapshot = match result {
+                Ok((snapshot, label)) => {
+                    self.system_label = Some(label);
+                    let previous = snapshot
+                        .serial
+                        .as_deref()
+                        .and_then(|serial| history::latest(settings, serial));
+                    let changes = previous
+                        .as_ref()
+                        .map(|p| snapshot.changes(p))
+                        .unwrap_or_default();
+                    self.system_message = Some(match history::append(settings, &snapshot) {
+                        Ok(()) => match &previous {
+                            Some(p) => format!(
+                                "Gespeichert. Verglichen mit der Momentaufnahme vom {}.",
+                                p.time.format("%Y-%m-%d %H:%M")
+                            ),
+                            None => "Gespeichert (erste Momentaufnahme).".to_string(),
+                        },
+                        Err(e) => format!("Momentaufnahme nicht gespeichert: {:#}", e),
+                    });
+                    Some((snapshot, changes))
+                }
+                Err(e) => {
+                    self.system_message = Some(format!("Fehler beim Lesen: {:#}", e));
+                    None
+                }
+            };
+        }
+        if let Some(msg) = &self.system_message {
+            ui.label(msg);
+        }
+        let Some((snapshot, changes)) = &self.system_snapshot else {
+            return;
+        };
+        ui.add_space(8.0);
+        super::history::snapshot_grid(ui, "system_values", snapshot, changes);
+        ui.add_space(8.0);
+        ui.horizontal(|ui| {
+            if let Some(serial) = &snapshot.serial
+                && ui.button("Show device history").clicked()
+            {
+                self.history_serial = serial.clone();
+                self.history =
+                    Some(history::lookup(settings, serial).map_err(|e| format!("{:#}", e)));
+                self.active_view = View::History;
+            }
+            if let Some(label) = &self.system_label
+                && ui.button("Print label").clicked()
+            {
+                self.system_message = Some(match crate::label::output(&settings.label, label) {
+                    Ok(msg) => msg,
+                    Err(e) => format!("Fehler beim Etikett: {:#}", e),
+                });
+            }
+        });
+    }
+}